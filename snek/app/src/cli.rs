@@ -0,0 +1,163 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use snek_core::entities::topology::TopologyKind;
+use structopt::StructOpt;
+
+/// Play Snek, watch the computer play it, or train AIs to play it
+#[derive(StructOpt, Debug)]
+pub struct Opt {
+    /// What to start straight away, skipping the title menu: 'menu', 'human', 'computer', 'two-player', 'vs-computer' or 'train'
+    #[structopt(short, long, default_value = "menu", parse(try_from_str = GameMode::from_str))]
+    pub mode: GameMode,
+
+    /// Grid size as "WIDTHxHEIGHT", e.g. '20x15' (ignored when playing a level)
+    #[structopt(short, long, parse(try_from_str = Size::from_str))]
+    pub grid: Option<Size>,
+
+    /// How the squares of the Grid join up: 'square', 'diagonal' (Sneks can move diagonally too) or 'hex' (ignored when playing a level)
+    #[structopt(long, default_value = "square", parse(try_from_str = TopologyKind::from_str))]
+    pub topology: TopologyKind,
+
+    /// The seed for the first game, with each game after it seeded from the one before, e.g. '42' (random if not given, or the one a replay was recorded with)
+    #[structopt(short, long)]
+    pub seed: Option<u64>,
+
+    /// How many ticks per second each game starts at, e.g. '8'
+    #[structopt(short, long)]
+    pub tick_rate: Option<u32>,
+
+    /// TOML file with [food] and [speed] tables to play by, e.g. 'rules.toml'
+    #[structopt(short, long, parse(from_os_str))]
+    pub rules: Option<PathBuf>,
+
+    /// Level to play on - one of the built-in levels by name, or the path to a level file
+    #[structopt(short, long)]
+    pub level: Option<String>,
+
+    /// Saved AI brain for the computer players, or where to save the champion when training, e.g. 'brain.toml'
+    #[structopt(short, long, parse(from_os_str))]
+    pub brain: Option<PathBuf>,
+
+    /// Recorded game to play back instead of a live one, e.g. 'last_game.toml'
+    #[structopt(long, parse(from_os_str))]
+    pub replay: Option<PathBuf>,
+
+    /// Window size as "WIDTHxHEIGHT", e.g. '1024x768'
+    #[structopt(short, long, parse(try_from_str = Size::from_str))]
+    pub window: Option<Size>,
+
+    /// Config file to use instead of the usual one, e.g. 'snek.toml' - any of the other options override what it says
+    #[structopt(short, long, parse(from_os_str))]
+    pub config: Option<PathBuf>,
+
+    /// Play without opening a window, printing how each game went instead
+    #[structopt(long)]
+    pub headless: bool,
+
+    /// Play in the terminal instead of opening a window, e.g. over SSH
+    #[structopt(long)]
+    pub tui: bool,
+
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+}
+
+// The things that can be done other than playing (a doc comment here would replace the description in --help)
+#[derive(StructOpt, Debug)]
+pub enum Command {
+    /// Train an AI with deep Q-learning, without opening a window
+    Dqn {
+        /// How many training games to play
+        #[structopt(default_value = "5000")]
+        episodes: usize,
+
+        /// Where to save the trained brain
+        #[structopt(default_value = "brain.toml", parse(from_os_str))]
+        brain: PathBuf,
+    },
+
+    /// Draw a saved brain as an SVG diagram, without opening a window
+    Diagram {
+        /// The brain to draw
+        #[structopt(parse(from_os_str))]
+        brain: PathBuf,
+
+        /// Where to save the diagram
+        #[structopt(default_value = "brain.svg", parse(from_os_str))]
+        svg: PathBuf,
+    },
+
+    /// Host a networked game, and wait for the other players to join
+    Host {
+        /// How many players the game is for, including the host
+        #[structopt(default_value = "2")]
+        players: u8,
+
+        /// The port to listen on (the default port if not given)
+        port: Option<u16>,
+    },
+
+    /// Join a networked game
+    Join {
+        /// The host's address, e.g. '192.168.1.10' or '192.168.1.10:7777'
+        address: String,
+    },
+}
+
+/// What to start when the app opens
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum GameMode {
+    /// The title menu
+    Menu,
+    /// A game played by hand
+    Human,
+    /// A game played by the computer
+    Computer,
+    /// Two people playing against each other on the same keyboard
+    TwoPlayer,
+    /// A person playing against the computer
+    VsComputer,
+    /// Evolving AIs, with a window onto how it's going
+    Train,
+}
+
+impl FromStr for GameMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "menu" => Ok(GameMode::Menu),
+            "human" => Ok(GameMode::Human),
+            "computer" => Ok(GameMode::Computer),
+            "two-player" => Ok(GameMode::TwoPlayer),
+            "vs-computer" => Ok(GameMode::VsComputer),
+            "train" => Ok(GameMode::Train),
+            _ => Err(format!(
+                "'{}' isn't one of menu, human, computer, two-player, vs-computer or train",
+                s
+            )),
+        }
+    }
+}
+
+/// A size in Grid squares or pixels
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Size {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl FromStr for Size {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("'{}' isn't a size like '20x15'", s);
+
+        let (width, height) = s.split_once('x').ok_or_else(err)?;
+        let width = width.trim().parse().map_err(|_| err())?;
+        let height = height.trim().parse().map_err(|_| err())?;
+
+        Ok(Self { width, height })
+    }
+}
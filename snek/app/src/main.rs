@@ -1,53 +1,484 @@
-use ggez::{conf, GameResult};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use ggez::event::EventLoop;
+use ggez::{conf, Context, GameError, GameResult};
+use neural_network::diagram::Diagram;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 use simple_logger::SimpleLogger;
+use structopt::StructOpt;
 
-use snek_ai::AiPlayer;
-use snek_core::players::Move;
-use snek_core::players::{human::player::HumanPlayer, Player};
-use snek_core::settings::{self, HUMAN};
+use snek_ai::{
+    dqn::{DqnAgent, DqnConfig},
+    evolution::{Trainer, Training},
+    visualiser::TrainingView,
+    AiPlayer,
+};
+use snek_core::config::{DisplayConfig, GameConfig};
+use snek_core::env::SnekEnv;
+use snek_core::level::{self, Level};
+use snek_core::net::{self, NetGame, Session};
+use snek_core::observation::{Rays, Senses};
+use snek_core::players::human::player::{Controls, HumanPlayer};
+use snek_core::players::replay::{Replay, ReplayPlayer};
+use snek_core::players::{Move, Player};
+use snek_core::scores::HighScores;
+use snek_core::settings::{self, Rules};
 use snek_core::{
     entities::grid::Grid,
-    game::{self, state::GameState},
+    game::{mode::Mode, scene::Scenes, snapshot::Snapshot, state::GameState, terminal},
 };
 
+use cli::{Command, GameMode, Opt};
+
+mod cli;
+
+/// Headless games still going after this many ticks are called off, in case the players are going round in circles
+const HEADLESS_TICKS: usize = 20_000;
+
 fn main() -> GameResult {
-    // Turn on logging
-    SimpleLogger::new()
-        .with_level(log::LevelFilter::Warn)
-        .init()
-        .unwrap();
-
-    // Create a new ggez Context and EventLoop
-    let (ctx, event_loop) = ggez::ContextBuilder::new(settings::GAME_TITLE, "Rufus Stone")
+    let opt = Opt::from_args();
+    let config = configure(&opt)?;
+
+    // Anything that doesn't play a game happens straight away
+    match &opt.command {
+        Some(Command::Dqn { episodes, brain }) => {
+            return train_dqn(&opt, &config, *episodes, brain)
+        }
+        Some(Command::Diagram { brain, svg }) => return draw_diagram(brain, svg),
+        _ => {}
+    }
+
+    let setup = Setup::new(&opt, &config)?;
+
+    if (opt.tui || opt.headless)
+        && matches!(
+            opt.command,
+            Some(Command::Host { .. }) | Some(Command::Join { .. })
+        )
+    {
+        return Err(GameError::CustomError(
+            "Networked games can only be played in a window for now".to_owned(),
+        ));
+    }
+
+    // Training and headless games happen before logging is turned on, as the games go by far too fast to log every one
+    if opt.mode == GameMode::Train {
+        return train(&opt, &config, &setup);
+    }
+    if opt.headless {
+        return play_headless(&opt, &setup);
+    }
+
+    // Turn on logging - apart from in the terminal, where it would be written all over the game
+    if !opt.tui {
+        SimpleLogger::new()
+            .with_level(log::LevelFilter::Warn)
+            .init()
+            .unwrap();
+    }
+
+    // Networked games have their own game loop, which waits for the other players
+    if let Some(session) = start_session(&opt, &setup)? {
+        let (ctx, event_loop) = window(&config.display);
+        let player = HumanPlayer::with_controls(setup.controls[0].clone());
+        let net_game = NetGame::new(session, Box::new(player));
+        ggez::event::run(ctx, event_loop, net_game);
+    }
+
+    // Replays are watched like the computer playing, and don't count towards the high scores
+    let replaying = setup.replay.is_some();
+    let mode = match replaying {
+        true => GameMode::Computer,
+        false => opt.mode,
+    };
+
+    // Every new game (including restarts) is set up from scratch, seeded from the game before it
+    let mut seeds = ChaCha8Rng::seed_from_u64(setup.seed);
+    let controls = setup.controls.clone();
+    let mut scenes = Scenes::new(move |game_mode: &Mode, controls: &[Controls]| {
+        setup.game(game_mode.players(), controls, seeds.next_u64())
+    })
+    .with_controls(controls);
+
+    if !replaying {
+        scenes = scenes.with_high_scores(HighScores::load_default());
+
+        // Controls changed on the Controls screen are kept for next time
+        if let Some(path) = Controls::default_path() {
+            scenes = scenes.with_controls_path(path);
+        }
+
+        // Keep a replay of the last game played by hand, so it can be benchmarked against the computer players
+        if let Some(path) = Replay::default_path() {
+            scenes = scenes.with_replay_path(path);
+        }
+
+        // F5 and F9 save the game in progress and carry on from it later
+        if let Some(path) = Snapshot::<ChaCha8Rng>::default_path() {
+            scenes = scenes.with_quick_save_path(path);
+        }
+    }
+
+    // Skip the title menu if the command line asked for a particular game
+    if let Some(idx) = menu_entry(mode) {
+        scenes.move_selection(idx as i32);
+        scenes.start()?;
+    }
+
+    // Start the game!
+    if opt.tui {
+        return terminal::run(scenes);
+    }
+    let (ctx, event_loop) = window(&config.display);
+    ggez::event::run(ctx, event_loop, scenes);
+}
+
+/// Everything the config file and command line say about how each game should be set up
+struct Setup {
+    grid: Grid,
+    level: Option<Level>,
+    rules: Option<Rules>,
+    tick_rate: Option<u32>,
+    /// The controls for each human player, in the order they're handed out
+    controls: Vec<Controls>,
+    /// The brain for the computer players, if they shouldn't just have random ones
+    brain: Option<AiPlayer>,
+    replay: Option<Replay>,
+    seed: u64,
+}
+
+impl Setup {
+    /// Load everything the command line asks for, giving up if any of it can't be loaded
+    fn new(opt: &Opt, config: &GameConfig) -> GameResult<Self> {
+        // When training, the brain file is where the champion gets saved rather than a brain to play with
+        let brain = match opt.mode {
+            GameMode::Train => None,
+            _ => opt.brain.as_ref().map(AiPlayer::load).transpose()?,
+        };
+
+        // Levels bring their own rules, so the ones in the config file only apply to games on an empty Grid
+        let level = opt.level.as_deref().map(load_level).transpose()?;
+        let rules = match (&opt.rules, &level) {
+            (Some(path), _) => Some(Rules::load(path)?),
+            (None, Some(_)) => None,
+            (None, None) => Some(config.rules()),
+        };
+
+        // Controls saved from the Controls screen take the place of the ones in the config file
+        let controls = match Controls::default_path().filter(|path| path.exists()) {
+            Some(path) => Controls::load_all(path)?,
+            None => config.controls.clone(),
+        };
+
+        // Replays are played back on the seed they were recorded with, unless the command line says otherwise
+        let replay = opt.replay.as_ref().map(Replay::load).transpose()?;
+        let seed = opt
+            .seed
+            .or_else(|| replay.as_ref().and_then(Replay::seed))
+            .unwrap_or_else(rand::random);
+
+        Ok(Self {
+            grid: grid(opt)?,
+            level,
+            rules,
+            tick_rate: opt.tick_rate,
+            controls,
+            brain,
+            replay,
+            seed,
+        })
+    }
+
+    /// Set up a new game on the specified seed for the specified players, or for the replay if there is one
+    fn game(
+        &self,
+        players: &[Player],
+        controls: &[Controls],
+        seed: u64,
+    ) -> GameResult<GameState<ChaCha8Rng, Box<dyn Move>>> {
+        // Replays always start over from the same seed, so that they play out the same every time
+        let (players, seed): (Vec<Box<dyn Move>>, u64) = match &self.replay {
+            Some(replay) => (vec![Box::new(ReplayPlayer::new(replay.clone()))], self.seed),
+            None => (create_players(players, controls, self.brain.as_ref()), seed),
+        };
+        log::warn!("Game seed: {}", seed);
+
+        // Play on the chosen level, or on an empty Grid
+        let mut state = match &self.level {
+            Some(level) => GameState::seeded_level(level, seed, players)
+                .map_err(|e| GameError::ResourceLoadError(e.to_string()))?,
+            None => GameState::seeded(self.grid.clone(), seed, players),
+        };
+
+        if let Some(rules) = &self.rules {
+            state.food_config = rules.food.clone();
+            state.speed = rules.speed.clone();
+        }
+        if let Some(rate) = self.tick_rate {
+            state.speed.base_rate = rate.max(1);
+            state.speed.max_rate = state.speed.max_rate.max(state.speed.base_rate);
+        }
+
+        Ok(state)
+    }
+}
+
+/// Get the Grid asked for on the command line, or the default one
+fn grid(opt: &Opt) -> GameResult<Grid> {
+    let grid = match opt.grid {
+        Some(size) => Grid::new(size.width, size.height).ok_or_else(|| {
+            GameError::CustomError(format!(
+                "Can't play on a {}x{} Grid",
+                size.width, size.height
+            ))
+        })?,
+        None => Grid::default(),
+    };
+
+    Ok(grid.with_topology(opt.topology))
+}
+
+/// Find the title menu entry that starts the specified kind of game, if there is one
+fn menu_entry(mode: GameMode) -> Option<usize> {
+    settings::MENU.iter().position(|(_, entry)| match mode {
+        GameMode::Human => matches!(entry, Mode::OnePlayer(Player::Human)),
+        GameMode::Computer => matches!(entry, Mode::OnePlayer(Player::Computer)),
+        GameMode::TwoPlayer => matches!(entry, Mode::MultiPlayer([Player::Human, Player::Human])),
+        GameMode::VsComputer => {
+            matches!(entry, Mode::MultiPlayer([Player::Human, Player::Computer]))
+        }
+        GameMode::Menu | GameMode::Train => false,
+    })
+}
+
+/// Load the config file, with anything set on the command line taking its place
+fn configure(opt: &Opt) -> GameResult<GameConfig> {
+    let mut config = match &opt.config {
+        Some(path) => GameConfig::load(path)?,
+        None => GameConfig::load_default()?,
+    };
+
+    if let Some(size) = opt.window {
+        config.display.width = size.width as f32;
+        config.display.height = size.height as f32;
+    }
+
+    Ok(config)
+}
+
+/// Open the game window
+fn window(display: &DisplayConfig) -> (Context, EventLoop<()>) {
+    ggez::ContextBuilder::new(settings::GAME_TITLE, "Rufus Stone")
         .window_setup(
             conf::WindowSetup::default()
                 .title(settings::GAME_TITLE)
-                .vsync(true),
-        )
-        .window_mode(
-            conf::WindowMode::default().dimensions(settings::SCREEN_WIDTH, settings::SCREEN_HEIGHT),
+                .vsync(display.vsync),
         )
+        .window_mode(conf::WindowMode::default().dimensions(display.width, display.height))
         .build()
-        .unwrap();
+        .unwrap()
+}
 
-    // Either start training the AI or playing the game
-    let game_mode = HUMAN;
+/// Create a player for each of the specified player types, giving each human their own set of controls
+/// Computer players get a copy of the specified brain if there is one, or a random brain otherwise
+fn create_players(
+    players: &[Player],
+    controls: &[Controls],
+    brain: Option<&AiPlayer>,
+) -> Vec<Box<dyn Move>> {
+    let mut controls = controls.iter();
 
-    // Create an appropriate player for the current game mode
-    let player: Box<dyn Move> = match game_mode {
-        game::mode::Mode::OnePlayer(Player::Human) => Box::new(HumanPlayer::default()),
-        _ => Box::new(AiPlayer::default()),
-    };
+    players
+        .iter()
+        .map(|player| -> Box<dyn Move> {
+            match player {
+                Player::Human => {
+                    let controls = controls
+                        .next()
+                        .expect("Not enough controls to go around the human players!");
+                    Box::new(HumanPlayer::with_controls(controls.clone()))
+                }
+                Player::Computer => Box::new(brain.cloned().unwrap_or_default()),
+            }
+        })
+        .collect()
+}
 
-    // Create a proper prng
-    let prng = rand::thread_rng();
+/// Play a single game without opening a window, then print how it went
+fn play_headless(opt: &Opt, setup: &Setup) -> GameResult {
+    let humans = matches!(
+        opt.mode,
+        GameMode::Human | GameMode::TwoPlayer | GameMode::VsComputer
+    );
+    if humans && setup.replay.is_none() {
+        return Err(GameError::CustomError(
+            "Humans need a window to play in - try '--mode computer' or '--replay'".to_owned(),
+        ));
+    }
 
-    // Create a default grid
-    let grid = Grid::default();
+    let mut state = setup.game(settings::COMPUTER.players(), &setup.controls, setup.seed)?;
 
-    // Create a new GameState
-    let game_state = GameState::new(grid, prng, player);
+    // Nobody is pressing any keys
+    let keys = HashSet::new();
 
-    // Start the game!
-    ggez::event::run(ctx, event_loop, game_state);
+    let mut ticks = 0;
+    while state.play && ticks < HEADLESS_TICKS {
+        state.poll_players(&keys);
+        state.step();
+        ticks += 1;
+    }
+
+    println!("Seed: {}", setup.seed);
+    println!("{}", state);
+    for (idx, snek) in state.sneks.iter().enumerate() {
+        let fate = match (state.won, state.deaths[idx]) {
+            (true, _) => "filled the Grid".to_owned(),
+            (false, Some(death)) => death.to_string(),
+            (false, None) => format!("still going after {} ticks", ticks),
+        };
+
+        println!("P{}: {} points - {}", idx + 1, snek.score(), fate);
+    }
+
+    Ok(())
+}
+
+/// Evolve AIs on the Grid asked for, saving the champion to the brain file
+/// With a window the champion can be watched playing, and is saved whenever S is pressed - without one, it's saved after every generation
+fn train(opt: &Opt, config: &GameConfig, setup: &Setup) -> GameResult {
+    let path = opt
+        .brain
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("brain.toml"));
+    let (width, height) = (setup.grid.width(), setup.grid.height());
+    let topology = setup.grid.topology().kind();
+    let mut trainer = Trainer::new(
+        &config.ai.for_encoder(&Senses),
+        Box::new(Senses),
+        width,
+        height,
+        setup.seed,
+    )
+    .with_topology(topology);
+
+    if !opt.headless {
+        let (ctx, event_loop) = window(&config.display);
+        let view = TrainingView::new(Training::start(trainer), width, height, path)
+            .with_topology(topology);
+        ggez::event::run(ctx, event_loop, view);
+    }
+
+    loop {
+        let generation = trainer.evolve();
+        println!(
+            "Generation {}: fitness min {:.2}, avg {:.2}, max {:.2} (best score {})",
+            generation.number,
+            generation.statistics.min_fitness(),
+            generation.statistics.avg_fitness(),
+            generation.statistics.max_fitness(),
+            generation.best_score
+        );
+
+        generation.champion.save(&path)?;
+    }
+}
+
+/// Draw a saved brain as an SVG diagram, without opening a window
+fn draw_diagram(brain: &Path, svg: &Path) -> GameResult {
+    let ai = AiPlayer::load(brain)?;
+    let diagram = Diagram::new(ai.brain(), settings::SCREEN_WIDTH, settings::SCREEN_HEIGHT);
+    std::fs::write(svg, diagram.to_svg(None))?;
+
+    println!("Drew {} to {}", brain.display(), svg.display());
+    Ok(())
+}
+
+/// Train an AI with deep Q-learning on the Grid asked for, then save its brain so it can be benchmarked or played against
+fn train_dqn(opt: &Opt, config: &GameConfig, episodes: usize, path: &Path) -> GameResult {
+    let grid = grid(opt)?;
+    let encoder = Rays;
+    let ai = config.ai.for_encoder(&encoder);
+    let mut env = SnekEnv::new(grid.width(), grid.height(), Box::new(encoder))
+        .with_topology(grid.topology().kind());
+    let seed = opt.seed.unwrap_or_else(rand::random);
+    let mut agent = DqnAgent::new(&ai, DqnConfig::default(), Box::new(encoder), seed);
+
+    // Report how the last batch of games went every so often
+    let mut scores = Vec::new();
+    for episode in 1..=episodes {
+        scores.push(
+            agent
+                .train_episode(&mut env, seed.wrapping_add(episode as u64))
+                .score,
+        );
+
+        if scores.len() == 100 || episode == episodes {
+            println!(
+                "Episode {}: mean score {:.2} over the last {} games (epsilon {:.2})",
+                episode,
+                scores.iter().sum::<usize>() as f32 / scores.len() as f32,
+                scores.len(),
+                agent.epsilon()
+            );
+            scores.clear();
+        }
+    }
+
+    agent.player().save(path)?;
+    println!("Saved the brain to {}", path.display());
+
+    Ok(())
+}
+
+/// Host or join a networked game, if the command line asks for one
+fn start_session(opt: &Opt, setup: &Setup) -> std::io::Result<Option<Session>> {
+    // Clients are only told the seed and the size of the Grid, so everyone has to play on an empty one by the default rules
+    let networked = matches!(
+        opt.command,
+        Some(Command::Host { .. }) | Some(Command::Join { .. })
+    );
+    if networked && (opt.level.is_some() || opt.rules.is_some() || opt.tick_rate.is_some()) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "networked games are played on an empty Grid by the default rules, so they can't have a --level, --rules or --tick-rate",
+        ));
+    }
+
+    match &opt.command {
+        Some(Command::Host { players, port }) => Session::host(
+            ("0.0.0.0", port.unwrap_or(net::DEFAULT_PORT)),
+            *players,
+            setup.seed,
+            net::DEFAULT_DELAY,
+            &setup.grid,
+        )
+        .map(Some),
+        Some(Command::Join { address }) => {
+            // Assume the default port if none was given
+            let address = match address.contains(':') {
+                true => address.to_owned(),
+                false => format!("{}:{}", address, net::DEFAULT_PORT),
+            };
+
+            Session::join(address.as_str()).map(Some)
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Load a level - built-in levels are picked by name, anything else is treated as a path
+fn load_level(name: &str) -> GameResult<Level> {
+    Level::built_in(name)
+        .unwrap_or_else(|| Level::load(name))
+        .map_err(|e| {
+            let names: Vec<&str> = level::BUILT_IN.iter().map(|(name, _)| *name).collect();
+            GameError::ResourceLoadError(format!(
+                "{}: {} (the built-in levels are {}, or give the path to a level file)",
+                name,
+                e,
+                names.join(", ")
+            ))
+        })
 }
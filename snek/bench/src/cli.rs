@@ -0,0 +1,94 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use structopt::StructOpt;
+
+/// Plays lots of seeded headless games with each player, and reports how well they got on
+#[derive(StructOpt, Debug)]
+pub struct Opt {
+    /// How many games each player gets to play
+    #[structopt(short, long, default_value = "100")]
+    pub games: usize,
+
+    /// The seed for the first game, with each game after it using the next seed along, e.g. '42'
+    #[structopt(short, long, default_value = "0")]
+    pub seed: u64,
+
+    /// Grid size as "WIDTHxHEIGHT", e.g. '20x15'
+    #[structopt(long, default_value = "10x10", parse(try_from_str = Size::from_str))]
+    pub grid: Size,
+
+    /// Games still going after this many ticks are called off
+    #[structopt(long, default_value = "20000")]
+    pub max_steps: usize,
+
+    /// Which of the built-in bots to play, e.g. 'greedy,astar' (all of them if not given)
+    #[structopt(short, long, use_delimiter = true)]
+    pub bots: Option<Vec<String>>,
+
+    /// Saved AI brains to play, e.g. 'brain.toml'
+    #[structopt(long = "brain", parse(from_os_str))]
+    pub brains: Vec<PathBuf>,
+
+    /// Recorded games to play back, e.g. 'last_game.toml'
+    #[structopt(long = "replay", parse(from_os_str))]
+    pub replays: Vec<PathBuf>,
+
+    /// How to write out the results: 'table', 'csv' or 'json'
+    #[structopt(short, long, default_value = "table", parse(try_from_str = Format::from_str))]
+    pub format: Format,
+
+    /// File to write the results to, instead of the terminal
+    #[structopt(short, long, parse(from_os_str))]
+    pub output: Option<PathBuf>,
+}
+
+/// The size of the Grid to play on
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Size {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl FromStr for Size {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("'{}' isn't a size like '10x10'", s);
+
+        let (width, height) = s.split_once('x').ok_or_else(err)?;
+        let width = width.trim().parse().map_err(|_| err())?;
+        let height = height.trim().parse().map_err(|_| err())?;
+
+        // Every Grid needs at least one square
+        if width == 0 || height == 0 {
+            return Err(format!(
+                "'{}' is too small - a Grid needs at least one square",
+                s
+            ));
+        }
+
+        Ok(Self { width, height })
+    }
+}
+
+/// The ways the results can be written out
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Format {
+    Table,
+    Csv,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "table" => Ok(Format::Table),
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("'{}' isn't one of table, csv or json", s)),
+        }
+    }
+}
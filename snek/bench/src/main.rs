@@ -0,0 +1,84 @@
+use std::io::Write;
+
+use structopt::StructOpt;
+
+mod cli;
+mod players;
+mod report;
+mod run;
+
+fn main() {
+    let opt = cli::Opt::from_args();
+
+    // Pick out the bots that were asked for, or all of them if none were
+    let mut entrants: Vec<players::Entrant> = match &opt.bots {
+        Some(names) => {
+            let mut bots = players::bots();
+            names
+                .iter()
+                .map(|name| {
+                    let idx = bots
+                        .iter()
+                        .position(|bot| &bot.name == name)
+                        .unwrap_or_else(|| {
+                            let known: Vec<String> =
+                                players::bots().into_iter().map(|bot| bot.name).collect();
+                            exit(&format!(
+                                "Unknown bot '{}' - try one of: {}",
+                                name,
+                                known.join(", ")
+                            ))
+                        });
+                    bots.remove(idx)
+                })
+                .collect()
+        }
+        None => players::bots(),
+    };
+
+    // Add in the saved brains and replays, giving up straight away if any of them can't be read
+    for path in &opt.brains {
+        let entrant = players::Entrant::brain(path)
+            .unwrap_or_else(|e| exit(&format!("Couldn't load brain {}: {}", path.display(), e)));
+        entrants.push(entrant);
+    }
+    for path in &opt.replays {
+        let entrant = players::Entrant::replay(path)
+            .unwrap_or_else(|e| exit(&format!("Couldn't load replay {}: {}", path.display(), e)));
+        entrants.push(entrant);
+    }
+
+    let summaries: Vec<run::Summary> = entrants
+        .iter()
+        .map(|entrant| {
+            eprintln!("Playing {} games with {}...", opt.games, entrant.name);
+            run::benchmark(
+                entrant,
+                opt.games,
+                opt.seed,
+                opt.grid.width,
+                opt.grid.height,
+                opt.max_steps,
+            )
+        })
+        .collect();
+
+    // Write to the terminal unless a file was given
+    let mut out: Box<dyn Write> = match &opt.output {
+        Some(path) => Box::new(
+            std::fs::File::create(path)
+                .unwrap_or_else(|e| exit(&format!("Couldn't create {}: {}", path.display(), e))),
+        ),
+        None => Box::new(std::io::stdout()),
+    };
+
+    if let Err(e) = report::write(&mut out, &summaries, opt.format) {
+        exit(&format!("Couldn't write the results: {}", e));
+    }
+}
+
+/// Give up with the specified message
+fn exit(msg: &str) -> ! {
+    eprintln!("{}", msg);
+    std::process::exit(1)
+}
@@ -0,0 +1,63 @@
+use std::path::Path;
+
+use snek_ai::AiPlayer;
+use snek_core::players::bots::{AStarBot, GreedyBot, HamiltonianBot};
+use snek_core::players::replay::{Replay, ReplayPlayer};
+use snek_core::players::Move;
+
+/// A player taking part in the benchmark, along with a way to make a fresh one for each game
+pub struct Entrant {
+    pub name: String,
+    make: Box<dyn Fn() -> Box<dyn Move>>,
+}
+
+impl Entrant {
+    pub fn new(name: impl Into<String>, make: impl Fn() -> Box<dyn Move> + 'static) -> Self {
+        Self {
+            name: name.into(),
+            make: Box::new(make),
+        }
+    }
+
+    /// Make a fresh player, ready for a new game
+    pub fn player(&self) -> Box<dyn Move> {
+        (self.make)()
+    }
+
+    /// An AI player with a brain loaded from the specified file
+    pub fn brain(path: &Path) -> std::io::Result<Self> {
+        let player = AiPlayer::load(path)?;
+
+        Ok(Self::new(label("brain", path), move || {
+            Box::new(player.clone())
+        }))
+    }
+
+    /// A player that plays back the game recorded in the specified file
+    pub fn replay(path: &Path) -> std::io::Result<Self> {
+        let replay = Replay::load(path)?;
+
+        Ok(Self::new(label("replay", path), move || {
+            Box::new(ReplayPlayer::new(replay.clone()))
+        }))
+    }
+}
+
+/// The bots that come with the game, by name
+pub fn bots() -> Vec<Entrant> {
+    vec![
+        Entrant::new("greedy", || Box::new(GreedyBot::new())),
+        Entrant::new("astar", || Box::new(AStarBot::new())),
+        Entrant::new("hamiltonian", || Box::new(HamiltonianBot::new())),
+    ]
+}
+
+/// Name a player after the file it came from
+fn label(kind: &str, path: &Path) -> String {
+    let name = path.file_stem().map_or_else(
+        || path.display().to_string(),
+        |stem| stem.to_string_lossy().into_owned(),
+    );
+
+    format!("{}:{}", kind, name)
+}
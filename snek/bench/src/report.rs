@@ -0,0 +1,121 @@
+use std::io::Write;
+
+use crate::cli::Format;
+use crate::run::Summary;
+
+/// Write the summaries out in the specified format
+pub fn write(out: &mut dyn Write, summaries: &[Summary], format: Format) -> std::io::Result<()> {
+    match format {
+        Format::Table => write_table(out, summaries),
+        Format::Csv => write_csv(out, summaries),
+        Format::Json => write_json(out, summaries),
+    }
+}
+
+/// Write the summaries as a table lined up for reading in a terminal
+fn write_table(out: &mut dyn Write, summaries: &[Summary]) -> std::io::Result<()> {
+    let width = summaries
+        .iter()
+        .map(|summary| summary.player.len())
+        .chain(std::iter::once("player".len()))
+        .max()
+        .unwrap_or(0);
+
+    writeln!(
+        out,
+        "{:<width$}  {:>6}  {:>10}  {:>10}  {:>6}  {:>10}  {:>10}  {:>5}  {:>8}  {:>5}  {:>5}",
+        "player",
+        "games",
+        "mean score",
+        "med. score",
+        "best",
+        "mean steps",
+        "med. steps",
+        "wins",
+        "timeouts",
+        "wall",
+        "self",
+        width = width
+    )?;
+
+    for s in summaries {
+        writeln!(
+            out,
+            "{:<width$}  {:>6}  {:>10.2}  {:>10.1}  {:>6}  {:>10.1}  {:>10.1}  {:>5}  {:>8}  {:>5}  {:>5}",
+            s.player,
+            s.games,
+            s.mean_score,
+            s.median_score,
+            s.best_score,
+            s.mean_steps,
+            s.median_steps,
+            s.wins,
+            s.timeouts,
+            s.wall_deaths,
+            s.self_deaths,
+            width = width
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Write the summaries as CSV, with a header row
+fn write_csv(out: &mut dyn Write, summaries: &[Summary]) -> std::io::Result<()> {
+    let mut writer = csv::Writer::from_writer(out);
+    for summary in summaries {
+        writer.serialize(summary)?;
+    }
+
+    writer.flush()
+}
+
+/// Write the summaries as a JSON array
+fn write_json(out: &mut dyn Write, summaries: &[Summary]) -> std::io::Result<()> {
+    serde_json::to_writer_pretty(&mut *out, summaries)?;
+    writeln!(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(player: &str) -> Summary {
+        Summary {
+            player: player.to_owned(),
+            games: 2,
+            mean_score: 3.5,
+            median_score: 3.5,
+            best_score: 5,
+            mean_steps: 20.0,
+            median_steps: 20.0,
+            wins: 0,
+            timeouts: 0,
+            wall_deaths: 1,
+            self_deaths: 1,
+        }
+    }
+
+    fn render(format: Format) -> String {
+        let mut out = Vec::new();
+        write(&mut out, &[summary("greedy"), summary("astar")], format).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn formats() {
+        let csv = render(Format::Csv);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("player,games,mean_score,median_score"));
+        assert!(lines[1].starts_with("greedy,2,3.5,3.5,5,"));
+
+        let json: serde_json::Value = serde_json::from_str(&render(Format::Json)).unwrap();
+        assert_eq!(json[1]["player"], "astar");
+        assert_eq!(json[1]["wall_deaths"], 1);
+
+        let table = render(Format::Table);
+        assert_eq!(table.lines().count(), 3);
+        assert!(table.lines().nth(2).unwrap().starts_with("astar "));
+    }
+}
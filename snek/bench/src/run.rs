@@ -0,0 +1,195 @@
+use std::collections::HashSet;
+
+use serde::Serialize;
+use snek_core::entities::grid::Grid;
+use snek_core::game::state::{Death, GameState};
+
+use crate::players::Entrant;
+
+/// How a single game ended
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum End {
+    /// The Snek filled the whole Grid
+    Won,
+    /// The game was still going when it was called off
+    Timeout,
+    Died(Death),
+}
+
+/// How a player got on in a single game
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Outcome {
+    pub score: usize,
+    /// How many ticks the Snek stayed alive for
+    pub steps: usize,
+    pub end: End,
+}
+
+/// Play a single headless game on a fresh Grid with the specified seed
+pub fn play(
+    entrant: &Entrant,
+    width: usize,
+    height: usize,
+    seed: u64,
+    max_steps: usize,
+) -> Outcome {
+    let grid = Grid::new(width, height).expect("Grids need to be at least 1x1");
+    let mut state = GameState::seeded(grid, seed, vec![entrant.player()]);
+
+    // Nobody is pressing any keys
+    let keys = HashSet::new();
+
+    let mut steps = 0;
+    while state.play && steps < max_steps {
+        state.poll_players(&keys);
+        state.step();
+        steps += 1;
+    }
+
+    let end = match (state.won, state.deaths[0]) {
+        (true, _) => End::Won,
+        (false, Some(death)) => End::Died(death),
+        (false, None) => End::Timeout,
+    };
+
+    Outcome {
+        score: state.sneks[0].score(),
+        steps,
+        end,
+    }
+}
+
+/// How a player got on across all of its games
+/// Every field is a plain value, so that each Summary makes one row of a CSV file
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Summary {
+    pub player: String,
+    pub games: usize,
+    pub mean_score: f64,
+    pub median_score: f64,
+    pub best_score: usize,
+    pub mean_steps: f64,
+    pub median_steps: f64,
+    pub wins: usize,
+    pub timeouts: usize,
+    pub wall_deaths: usize,
+    pub self_deaths: usize,
+}
+
+impl Summary {
+    /// Sum up the outcomes of a player's games
+    pub fn new(player: &str, outcomes: &[Outcome]) -> Self {
+        let scores: Vec<usize> = outcomes.iter().map(|outcome| outcome.score).collect();
+        let steps: Vec<usize> = outcomes.iter().map(|outcome| outcome.steps).collect();
+        let count = |end: fn(&End) -> bool| outcomes.iter().filter(|o| end(&o.end)).count();
+
+        Self {
+            player: player.to_owned(),
+            games: outcomes.len(),
+            mean_score: mean(&scores),
+            median_score: median(&scores),
+            best_score: scores.iter().copied().max().unwrap_or(0),
+            mean_steps: mean(&steps),
+            median_steps: median(&steps),
+            wins: count(|end| *end == End::Won),
+            timeouts: count(|end| *end == End::Timeout),
+            wall_deaths: count(|end| *end == End::Died(Death::Wall)),
+            // There's only one Snek in each game, so anything other than a wall must have been itself
+            self_deaths: count(|end| matches!(end, End::Died(death) if *death != Death::Wall)),
+        }
+    }
+}
+
+/// Play the specified number of games with the player, one for each seed starting from `seed`
+/// Every player gets the same seeds, so they all face the same Food in the same places (for as long as they make the same moves)
+pub fn benchmark(
+    entrant: &Entrant,
+    games: usize,
+    seed: u64,
+    width: usize,
+    height: usize,
+    max_steps: usize,
+) -> Summary {
+    let outcomes: Vec<Outcome> = (0..games as u64)
+        .map(|game| play(entrant, width, height, seed.wrapping_add(game), max_steps))
+        .collect();
+
+    Summary::new(&entrant.name, &outcomes)
+}
+
+fn mean(values: &[usize]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+
+    values.iter().sum::<usize>() as f64 / values.len() as f64
+}
+
+/// The middle value, or the mean of the two middle values if there's an even number of them
+fn median(values: &[usize]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+
+    let mut sorted = values.to_vec();
+    sorted.sort_unstable();
+
+    let mid = sorted.len() / 2;
+    match sorted.len() % 2 {
+        0 => (sorted[mid - 1] + sorted[mid]) as f64 / 2.0,
+        _ => sorted[mid] as f64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::players;
+
+    use super::*;
+
+    #[test]
+    fn summary() {
+        let outcome = |score, steps, end| Outcome { score, steps, end };
+        let outcomes = vec![
+            outcome(4, 40, End::Died(Death::Wall)),
+            outcome(1, 10, End::Died(Death::Itself)),
+            outcome(9, 90, End::Won),
+            outcome(2, 100, End::Timeout),
+        ];
+
+        let summary = Summary::new("test", &outcomes);
+        assert_eq!(summary.games, 4);
+        assert!((summary.mean_score - 4.0).abs() < f64::EPSILON);
+        assert!((summary.median_score - 3.0).abs() < f64::EPSILON);
+        assert_eq!(summary.best_score, 9);
+        assert!((summary.median_steps - 65.0).abs() < f64::EPSILON);
+        assert_eq!(
+            (
+                summary.wins,
+                summary.timeouts,
+                summary.wall_deaths,
+                summary.self_deaths
+            ),
+            (1, 1, 1, 1)
+        );
+    }
+
+    #[test]
+    fn seeded_games_repeat() {
+        // The same seed always plays out the same way
+        for entrant in players::bots() {
+            let first = play(&entrant, 6, 6, 7, 500);
+            let second = play(&entrant, 6, 6, 7, 500);
+            assert_eq!(first, second, "{}", entrant.name);
+        }
+
+        // The Hamiltonian bot always fills an even Grid
+        let hamiltonian = players::bots().pop().unwrap();
+        let summary = benchmark(&hamiltonian, 3, 0, 4, 4, 1000);
+        assert_eq!(summary.wins, 3);
+
+        // Seeds near the top wrap around rather than overflowing
+        let summary = benchmark(&hamiltonian, 2, u64::MAX, 4, 4, 1000);
+        assert_eq!(summary.wins, 2);
+    }
+}
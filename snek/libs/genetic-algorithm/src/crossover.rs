@@ -11,7 +11,7 @@ pub trait CrossoverMethod {
     ) -> Chromosome;
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct UniformCrossover;
 
 impl UniformCrossover {
@@ -3,6 +3,7 @@ use individual::Individual;
 use mutation::MutationMethod;
 use rand::RngCore;
 use selection::SelectionMethod;
+use statistics::Statistics;
 
 pub mod chromosome;
 pub mod crossover;
@@ -33,13 +34,14 @@ where
         }
     }
 
-    pub fn evolve<I>(&self, prng: &mut dyn RngCore, population: &[I]) -> Vec<I>
+    /// Breed the next generation from the specified population, returning it along with stats about how fit the old one was
+    pub fn evolve<I>(&self, prng: &mut dyn RngCore, population: &[I]) -> (Vec<I>, Statistics)
     where
         I: Individual,
     {
         assert!(!population.is_empty());
 
-        (0..population.len())
+        let new_population = (0..population.len())
             .map(|_| {
                 // Selection
                 let parent_a = self.selection_method.select(prng, population).chromosome();
@@ -54,7 +56,9 @@ where
                 // Create a new individual
                 I::create(child)
             })
-            .collect()
+            .collect();
+
+        (new_population, Statistics::new(population))
     }
 }
 
@@ -90,9 +94,15 @@ mod tests {
         ];
 
         for _ in 0..10 {
-            population = ga.evolve(&mut prng, &population);
+            population = ga.evolve(&mut prng, &population).0;
         }
 
+        let (_, stats) = ga.evolve(&mut prng, &population);
+        approx::assert_relative_eq!(
+            stats.sum_fitness(),
+            population.iter().map(|i| i.fitness()).sum()
+        );
+
         /*let expected_population = vec![
             individual(&[0.44769490, 2.0648358, 4.3058133]),
             individual(&[1.21268670, 1.5538777, 2.8869110]),
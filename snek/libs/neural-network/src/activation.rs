@@ -0,0 +1,27 @@
+/// What a Layer does to each neuron's weighted sum before passing it on
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum Activation {
+    /// Negative values become 0, which is what every Layer uses unless told otherwise
+    #[default]
+    Relu,
+    /// Values are passed on untouched, which lets the outputs of a Network go negative
+    Linear,
+}
+
+impl Activation {
+    /// Apply the activation to a neuron's weighted sum
+    pub fn apply(&self, sum: f32) -> f32 {
+        match self {
+            Activation::Relu => sum.max(0.0),
+            Activation::Linear => sum,
+        }
+    }
+
+    /// How much the output changes for a small change in the weighted sum, worked out from the output itself
+    pub fn derivative(&self, output: f32) -> f32 {
+        match self {
+            Activation::Relu if output <= 0.0 => 0.0,
+            _ => 1.0,
+        }
+    }
+}
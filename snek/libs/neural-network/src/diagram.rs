@@ -0,0 +1,296 @@
+use std::fmt::Write;
+
+use crate::Network;
+
+/// An RGB colour
+pub type Rgb = (u8, u8, u8);
+
+/// The colours of weights that push a neuron up, and weights that hold it down
+const POSITIVE: Rgb = (60, 120, 255);
+const NEGATIVE: Rgb = (255, 70, 70);
+
+/// How thick the strongest weight in the Network is drawn
+const MAX_THICKNESS: f32 = 4.0;
+
+/// The gap left around the edge of the diagram
+const MARGIN: f32 = 20.0;
+
+/// Where a neuron goes on the diagram
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Node {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// A weight between a neuron and one in the layer before it, with how it should be drawn
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Edge {
+    pub from: Node,
+    pub to: Node,
+    pub weight: f32,
+    pub colour: Rgb,
+    pub thickness: f32,
+    /// From 0.0 (the weakest weight in the Network) to 1.0 (the strongest)
+    pub opacity: f32,
+}
+
+/// A Network laid out as a diagram, with the inputs in a column on the left and each layer in its own column to the right
+/// The layout only depends on the Network and the size of the diagram, so it can be worked out once and drawn every frame
+#[derive(Debug, Clone)]
+pub struct Diagram {
+    width: f32,
+    height: f32,
+    layers: Vec<Vec<Node>>,
+    edges: Vec<Edge>,
+    radius: f32,
+}
+
+impl Diagram {
+    /// Lay out the Network to fit in the specified width and height
+    pub fn new(network: &Network, width: f32, height: f32) -> Self {
+        let topology = network.topology();
+        let columns = topology.len();
+        let gap = (width - 2.0 * MARGIN) / (columns - 1) as f32;
+
+        let layers: Vec<Vec<Node>> = topology
+            .iter()
+            .enumerate()
+            .map(|(column, layer)| {
+                let spacing = (height - 2.0 * MARGIN) / layer.neurons as f32;
+
+                (0..layer.neurons)
+                    .map(|row| Node {
+                        x: MARGIN + gap * column as f32,
+                        y: MARGIN + spacing * (row as f32 + 0.5),
+                    })
+                    .collect()
+            })
+            .collect();
+
+        // Nodes are as big as they can be without touching their neighbours, up to a point
+        let tallest = topology
+            .iter()
+            .map(|layer| layer.neurons)
+            .max()
+            .unwrap_or(1);
+        let radius = ((height - 2.0 * MARGIN) / tallest as f32 / 2.5).clamp(1.0, 10.0);
+
+        // Each weight is drawn relative to the strongest one
+        let strongest = network
+            .layers()
+            .iter()
+            .flat_map(|layer| layer.neurons())
+            .flat_map(|neuron| neuron.weights())
+            .fold(0.0_f32, |strongest, weight| strongest.max(weight.abs()));
+
+        let mut edges = Vec::new();
+        for (idx, layer) in network.layers().iter().enumerate() {
+            for (to, neuron) in layer.neurons().iter().enumerate() {
+                for (from, &weight) in neuron.weights().iter().enumerate() {
+                    let strength = match strongest > 0.0 {
+                        true => weight.abs() / strongest,
+                        false => 0.0,
+                    };
+
+                    edges.push(Edge {
+                        from: layers[idx][from],
+                        to: layers[idx + 1][to],
+                        weight,
+                        colour: match weight < 0.0 {
+                            true => NEGATIVE,
+                            false => POSITIVE,
+                        },
+                        thickness: 0.5 + (MAX_THICKNESS - 0.5) * strength,
+                        opacity: strength,
+                    });
+                }
+            }
+        }
+
+        Self {
+            width,
+            height,
+            layers,
+            edges,
+            radius,
+        }
+    }
+
+    /// Get where each neuron goes, layer by layer, starting with the inputs
+    pub fn layers(&self) -> &[Vec<Node>] {
+        &self.layers
+    }
+
+    /// Get every weight in the Network, in the order they should be drawn
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    /// How big each neuron is drawn
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    /// Get the colour of every neuron, given a trace of what each layer put out (see `Network::trace`)
+    /// Each layer is shaded relative to its own biggest value, so quiet layers still show which neurons are firing
+    pub fn shades(&self, trace: &[Vec<f32>]) -> Vec<Vec<Rgb>> {
+        assert_eq!(
+            trace.len(),
+            self.layers.len(),
+            "The trace doesn't fit the diagram"
+        );
+
+        trace
+            .iter()
+            .map(|values| {
+                let biggest = values
+                    .iter()
+                    .fold(0.0_f32, |biggest, v| biggest.max(v.abs()));
+
+                values
+                    .iter()
+                    .map(|&value| match biggest > 0.0 {
+                        true => shade(value / biggest),
+                        false => shade(0.0),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Draw the diagram as an SVG image, with the neurons shaded by the specified trace if there is one
+    pub fn to_svg(&self, trace: Option<&[Vec<f32>]>) -> String {
+        let shades = match trace {
+            Some(trace) => self.shades(trace),
+            None => self
+                .layers
+                .iter()
+                .map(|layer| vec![shade(0.0); layer.len()])
+                .collect(),
+        };
+
+        let mut svg = String::new();
+
+        // Writing to a String can't fail
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+            w = self.width,
+            h = self.height
+        );
+        let _ = writeln!(svg, r#"<rect width="100%" height="100%" fill="black"/>"#);
+
+        for edge in &self.edges {
+            let _ = writeln!(
+                svg,
+                r#"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" stroke="{}" stroke-width="{:.2}" stroke-opacity="{:.2}"/>"#,
+                edge.from.x,
+                edge.from.y,
+                edge.to.x,
+                edge.to.y,
+                css(edge.colour),
+                edge.thickness,
+                edge.opacity
+            );
+        }
+
+        for (layer, shades) in self.layers.iter().zip(&shades) {
+            for (node, &colour) in layer.iter().zip(shades) {
+                let _ = writeln!(
+                    svg,
+                    r#"<circle cx="{:.1}" cy="{:.1}" r="{:.1}" fill="{}" stroke="white"/>"#,
+                    node.x,
+                    node.y,
+                    self.radius,
+                    css(colour)
+                );
+            }
+        }
+
+        svg.push_str("</svg>\n");
+
+        svg
+    }
+}
+
+/// The colour of a neuron putting out the specified value, from -1.0 (bright red) through 0.0 (dark grey) to 1.0 (bright green)
+pub fn shade(value: f32) -> Rgb {
+    let value = value.clamp(-1.0, 1.0);
+    let bright = (40.0 + 215.0 * value.abs()) as u8;
+
+    match value < 0.0 {
+        true => (bright, 40, 40),
+        false => (40, bright, 40),
+    }
+}
+
+/// Write a colour the way SVG expects
+fn css((r, g, b): Rgb) -> String {
+    format!("rgb({},{},{})", r, g, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::topology::LayerTopology;
+
+    fn network() -> Network {
+        let layers = [
+            LayerTopology { neurons: 2 },
+            LayerTopology { neurons: 3 },
+            LayerTopology { neurons: 1 },
+        ];
+        // Each neuron is a bias followed by its weights
+        let weights = vec![
+            0.0, 1.0, -2.0, // Hidden 0
+            0.0, 0.5, 0.0, // Hidden 1
+            0.0, -1.0, 1.0, // Hidden 2
+            0.1, 1.0, 1.0, 1.0, // Output
+        ];
+
+        Network::from_weights(&layers, weights)
+    }
+
+    #[test]
+    fn layout() {
+        let diagram = Diagram::new(&network(), 240.0, 140.0);
+
+        let columns: Vec<usize> = diagram.layers().iter().map(Vec::len).collect();
+        assert_eq!(columns, vec![2, 3, 1]);
+        assert_eq!(diagram.layers()[0][0], Node { x: 20.0, y: 45.0 });
+        assert_eq!(diagram.layers()[1][1], Node { x: 120.0, y: 70.0 });
+        assert_eq!(diagram.layers()[2][0], Node { x: 220.0, y: 70.0 });
+
+        // The strongest weight is drawn thickest, and negative weights are drawn in their own colour
+        assert_eq!(diagram.edges().len(), 2 * 3 + 3);
+        let strongest = diagram.edges()[1];
+        assert_eq!(strongest.weight, -2.0);
+        assert_eq!(strongest.colour, NEGATIVE);
+        assert_eq!(strongest.thickness, MAX_THICKNESS);
+        assert_eq!(diagram.edges()[3].opacity, 0.0);
+    }
+
+    #[test]
+    fn shading() {
+        let network = network();
+        let diagram = Diagram::new(&network, 240.0, 140.0);
+        let shades = diagram.shades(&network.trace(vec![1.0, 0.0]));
+
+        // Hidden neuron 0 fires hardest, and neuron 2 doesn't fire at all
+        assert_eq!(shades[1], vec![shade(1.0), shade(0.5), shade(0.0)]);
+        assert_eq!(shade(-1.0), (255, 40, 40));
+    }
+
+    #[test]
+    fn svg() {
+        let network = network();
+        let diagram = Diagram::new(&network, 240.0, 140.0);
+        let svg = diagram.to_svg(Some(&network.trace(vec![1.0, 0.0])));
+
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<line").count(), 9);
+        assert_eq!(svg.matches("<circle").count(), 6);
+        assert!(svg.contains(r#"fill="rgb(40,255,40)""#));
+    }
+}
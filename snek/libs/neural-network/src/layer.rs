@@ -1,8 +1,10 @@
+use crate::activation::Activation;
 use crate::neuron::Neuron;
 
 #[derive(Debug, Clone)]
 pub struct Layer {
     pub(crate) neurons: Vec<Neuron>,
+    pub(crate) activation: Activation,
 }
 
 impl Layer {
@@ -10,7 +12,16 @@ impl Layer {
     pub fn new(neurons: Vec<Neuron>) -> Self {
         assert!(!neurons.is_empty());
 
-        Self { neurons }
+        Self {
+            neurons,
+            activation: Activation::default(),
+        }
+    }
+
+    /// Use the specified activation for every neuron in the Layer
+    pub fn with_activation(mut self, activation: Activation) -> Self {
+        self.activation = activation;
+        self
     }
 
     /// Create a new Layer with randomly chosen neurons
@@ -23,7 +34,7 @@ impl Layer {
             .map(|_| Neuron::random(prng, input_neurons))
             .collect();
 
-        Layer { neurons }
+        Self::new(neurons)
     }
 
     /// Create a new Layer from the specified weights
@@ -51,13 +62,43 @@ impl Layer {
 
         self.neurons
             .iter()
-            .map(|neuron| neuron.propagate(&inputs))
+            .map(|neuron| self.activation.apply(neuron.sum(&inputs)))
             .collect()
     }
 
+    /// Work out how the loss changes with each of the Layer's weights, and with each of its inputs, given how it changes with each of its outputs
+    /// The weight gradients are laid out the same way as `Network::weights()`, i.e. each neuron's bias followed by its weights
+    pub fn backpropagate(
+        &self,
+        inputs: &[f32],
+        outputs: &[f32],
+        output_gradients: &[f32],
+    ) -> (Vec<f32>, Vec<f32>) {
+        let mut weight_gradients = Vec::with_capacity(self.neurons.len() * (inputs.len() + 1));
+        let mut input_gradients = vec![0.0; inputs.len()];
+
+        for ((neuron, output), gradient) in self.neurons.iter().zip(outputs).zip(output_gradients) {
+            // How the loss changes with the neuron's weighted sum
+            let delta = gradient * self.activation.derivative(*output);
+
+            weight_gradients.push(delta);
+            weight_gradients.extend(inputs.iter().map(|input| delta * input));
+
+            for (input_gradient, weight) in input_gradients.iter_mut().zip(&neuron.weights) {
+                *input_gradient += delta * weight;
+            }
+        }
+
+        (weight_gradients, input_gradients)
+    }
+
     pub fn neurons(&self) -> &[Neuron] {
         &self.neurons
     }
+
+    pub fn activation(&self) -> Activation {
+        self.activation
+    }
 }
 
 #[cfg(test)]
@@ -1,7 +1,10 @@
+pub mod activation;
+pub mod diagram;
 mod layer;
 mod neuron;
 pub mod topology;
 
+use activation::Activation;
 use layer::Layer;
 use topology::LayerTopology;
 
@@ -59,10 +62,88 @@ impl Network {
             .fold(inputs, |inputs, layer| layer.propagate(inputs))
     }
 
+    /// Propagate the inputs through the Network, keeping hold of what goes into and comes out of every layer
+    /// The first entry is the inputs themselves and the last is the Network's outputs, ready to be handed to `backpropagate`
+    pub fn trace(&self, inputs: Vec<f32>) -> Vec<Vec<f32>> {
+        let mut trace = vec![inputs];
+
+        for layer in &self.layers {
+            let outputs = layer.propagate(trace[trace.len() - 1].clone());
+            trace.push(outputs);
+        }
+
+        trace
+    }
+
+    /// Work out how the loss changes with every weight in the Network, given a trace and how the loss changes with each output
+    /// The gradients are laid out the same way as `weights()`
+    pub fn backpropagate(&self, trace: &[Vec<f32>], output_gradients: &[f32]) -> Vec<f32> {
+        assert_eq!(trace.len(), self.layers.len() + 1);
+
+        // Work backwards from the outputs, passing the gradients for each layer's inputs on to the layer before it
+        let mut gradients = output_gradients.to_vec();
+        let mut per_layer = Vec::with_capacity(self.layers.len());
+        for (idx, layer) in self.layers.iter().enumerate().rev() {
+            let (weight_gradients, input_gradients) =
+                layer.backpropagate(&trace[idx], &trace[idx + 1], &gradients);
+            per_layer.push(weight_gradients);
+            gradients = input_gradients;
+        }
+
+        per_layer.into_iter().rev().flatten().collect()
+    }
+
+    /// Nudge every weight a little way against its gradient, so that the loss goes down
+    pub fn descend(&mut self, gradients: &[f32], learning_rate: f32) {
+        let mut gradients = gradients.iter();
+
+        for neuron in self
+            .layers
+            .iter_mut()
+            .flat_map(|layer| layer.neurons.iter_mut())
+        {
+            for value in once(&mut neuron.bias).chain(neuron.weights.iter_mut()) {
+                *value -= learning_rate * gradients.next().expect("Not enough gradients!");
+            }
+        }
+
+        assert!(gradients.next().is_none(), "Too many gradients!");
+    }
+
+    /// Use the specified activation for the output layer, e.g. Linear for outputs that need to go negative
+    pub fn with_output_activation(mut self, activation: Activation) -> Self {
+        let last = self.layers.len() - 1;
+        self.layers[last].activation = activation;
+        self
+    }
+
+    /// Get the activation used by the output layer
+    pub fn output_activation(&self) -> Activation {
+        self.layers[self.layers.len() - 1].activation
+    }
+
     pub fn layers(&self) -> &[Layer] {
         &self.layers
     }
 
+    /// Get the number of neurons in each layer, starting with the inputs, in the form taken by `random` and `from_weights`
+    pub fn topology(&self) -> Vec<LayerTopology> {
+        let inputs = self.layers[0].neurons[0].weights.len();
+
+        once(inputs)
+            .chain(self.layers.iter().map(|layer| layer.neurons.len()))
+            .map(|neurons| LayerTopology { neurons })
+            .collect()
+    }
+
+    /// Get the number of weights (including biases) that a Network with the specified layers is made up of
+    pub fn weight_count(layers: &[LayerTopology]) -> usize {
+        layers
+            .windows(2)
+            .map(|layers| (layers[0].neurons + 1) * layers[1].neurons)
+            .sum()
+    }
+
     pub fn weights(&self) -> impl Iterator<Item = f32> + '_ {
         self.layers
             .iter()
@@ -180,6 +261,67 @@ mod tests {
         approx::assert_relative_eq!(actual.as_slice(), weights.as_slice());
     }
 
+    #[test]
+    fn network_topology() {
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+        let layers = [
+            LayerTopology { neurons: 3 },
+            LayerTopology { neurons: 2 },
+            LayerTopology { neurons: 1 },
+        ];
+
+        let network = Network::random(&mut prng, &layers);
+
+        assert_eq!(network.topology(), layers);
+        assert_eq!(Network::weight_count(&layers), network.weights().count());
+    }
+
+    #[test]
+    fn backpropagation() {
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+        let layers = [
+            LayerTopology { neurons: 3 },
+            LayerTopology { neurons: 4 },
+            LayerTopology { neurons: 2 },
+        ];
+        let network =
+            Network::random(&mut prng, &layers).with_output_activation(Activation::Linear);
+        let inputs = vec![0.5, -0.2, 0.9];
+
+        // Use half the sum of the squared outputs as the loss, so the gradient for each output is just the output itself
+        let loss = |network: &Network| {
+            network
+                .propagate(inputs.clone())
+                .iter()
+                .map(|output| output * output / 2.0)
+                .sum::<f32>()
+        };
+        let trace = network.trace(inputs.clone());
+        let outputs = trace.last().unwrap().clone();
+        let expected = network.propagate(inputs.clone());
+        approx::assert_relative_eq!(outputs.as_slice(), expected.as_slice());
+        let gradients = network.backpropagate(&trace, &outputs);
+
+        // Compare each gradient to what happens to the loss when its weight is nudged a little either way
+        let weights: Vec<f32> = network.weights().collect();
+        assert_eq!(gradients.len(), weights.len());
+        let step = 1e-3;
+        for idx in 0..weights.len() {
+            let nudged = |delta: f32| {
+                let mut weights = weights.clone();
+                weights[idx] += delta;
+                Network::from_weights(&layers, weights).with_output_activation(Activation::Linear)
+            };
+            let numerical = (loss(&nudged(step)) - loss(&nudged(-step))) / (2.0 * step);
+            approx::assert_abs_diff_eq!(gradients[idx], numerical, epsilon = 1e-2);
+        }
+
+        // Following the gradients downhill makes the loss smaller
+        let mut trained = network.clone();
+        trained.descend(&gradients, 0.1);
+        assert!(loss(&trained) < loss(&network));
+    }
+
     #[test]
     fn weights_collection() {
         // Build two layers with one neuron each
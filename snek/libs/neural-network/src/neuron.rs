@@ -51,6 +51,12 @@ impl Neuron {
 
     /// Combine the inputs and propogate the output
     pub fn propagate(&self, inputs: &[f32]) -> f32 {
+        // Finally, add the bias to the sum, and return whichever is the bigger of that value or 0.0
+        self.sum(inputs).max(0.0)
+    }
+
+    /// Combine the inputs with the weights and bias, without applying any activation
+    pub fn sum(&self, inputs: &[f32]) -> f32 {
         // There should always be an equal number of inputs and weights (as the weights modify each input)
         assert_eq!(inputs.len(), self.weights.len());
 
@@ -76,8 +82,7 @@ impl Neuron {
         //output += self.bias;
         //output.max(0.0) // Return the whatever is the largest of output and 0.0
 
-        // Finally, add the bias to the sum, and return whichever is the bigger of that value or 0.0
-        (self.bias + output).max(0.0)
+        self.bias + output
     }
 
     pub fn bias(&self) -> f32 {
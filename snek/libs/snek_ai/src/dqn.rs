@@ -0,0 +1,324 @@
+use neural_network::{activation::Activation, topology::LayerTopology, Network};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use snek_core::{
+    env::{Action, Environment},
+    observation::Encoder,
+    settings::Config,
+};
+
+use crate::experience::{Experience, ExperienceBuffer};
+use crate::{argmax, rebuild, topology, AiPlayer};
+
+/// How a DqnAgent learns
+#[derive(Clone, Debug)]
+pub struct DqnConfig {
+    pub learning_rate: f32,
+    pub discount: f32,
+    pub epsilon_start: f32,
+    pub epsilon_end: f32,
+    pub epsilon_decay: usize,
+    pub buffer_size: usize,
+    pub batch_size: usize,
+    pub warmup: usize,
+    pub target_sync: usize,
+}
+
+impl Default for DqnConfig {
+    fn default() -> Self {
+        Self {
+            learning_rate: 0.001,  // How big a step to take down the gradient each time
+            discount: 0.95, // How much rewards in the future count for, compared to rewards right now
+            epsilon_start: 1.0, // The chance of making a random move, to begin with
+            epsilon_end: 0.05, // The chance of making a random move, once the agent has learnt a bit
+            epsilon_decay: 20_000, // How many moves it takes to go from epsilon_start to epsilon_end
+            buffer_size: 50_000,   // How many past moves to remember
+            batch_size: 32,        // How many past moves to learn from at once
+            warmup: 1_000,         // How many moves to make before starting to learn
+            target_sync: 1_000,    // How many moves to make between updates of the target network
+        }
+    }
+}
+
+/// How a single training game went
+#[derive(Clone, Debug, PartialEq)]
+pub struct Episode {
+    pub score: usize,
+    pub steps: usize,
+    pub reward: f32,
+    /// The mean loss of the training done during the game, if there was any
+    pub loss: Option<f32>,
+}
+
+/// Learns to play with deep Q-learning: the brain estimates how much reward each move will lead to, and gets better at it by learning from past moves
+/// It uses the same encoders and brain layout as the evolved AiPlayers, so the two can be compared fairly
+pub struct DqnAgent {
+    /// The brain being trained
+    online: Network,
+    /// An older copy of the brain, used to work out what the online brain should be aiming for so that it isn't chasing its own tail
+    target: Network,
+    encoder: Box<dyn Encoder>,
+    memory: ExperienceBuffer,
+    config: DqnConfig,
+    prng: ChaCha8Rng,
+    /// How many moves have been made so far, across every game
+    steps: usize,
+    /// How many more moves until the target brain catches up with the online one
+    until_sync: usize,
+}
+
+impl DqnAgent {
+    /// Create a new DqnAgent with a freshly wired brain, laid out according to the Config
+    pub fn new(config: &Config, dqn: DqnConfig, encoder: Box<dyn Encoder>, seed: u64) -> Self {
+        assert_eq!(
+            config.eye_photoreceptors,
+            encoder.size(),
+            "The Config doesn't match the encoder - use Config::for_encoder"
+        );
+        assert_eq!(
+            config.outputs,
+            Action::ALL.len(),
+            "The brain needs one output per action"
+        );
+
+        let mut prng = ChaCha8Rng::seed_from_u64(seed);
+        let layers = topology(config);
+
+        // Estimated rewards can be negative, so the outputs mustn't be cut off at 0
+        let online = Network::from_weights(&layers, initial_weights(&mut prng, &layers))
+            .with_output_activation(Activation::Linear);
+
+        Self {
+            target: online.clone(),
+            online,
+            encoder,
+            memory: ExperienceBuffer::new(dqn.buffer_size),
+            until_sync: dqn.target_sync,
+            config: dqn,
+            prng,
+            steps: 0,
+        }
+    }
+
+    /// Get an immutable ref to the brain being trained
+    pub fn brain(&self) -> &Network {
+        &self.online
+    }
+
+    /// The chance of making a random move right now, which goes down as training goes on
+    pub fn epsilon(&self) -> f32 {
+        let progress = (self.steps as f32 / self.config.epsilon_decay.max(1) as f32).min(1.0);
+
+        self.config.epsilon_start + (self.config.epsilon_end - self.config.epsilon_start) * progress
+    }
+
+    /// The move the brain thinks will lead to the most reward
+    pub fn best_action(&self, observation: &[f32]) -> usize {
+        argmax(&self.online.propagate(observation.to_vec()))
+    }
+
+    /// Pick a move to make while training - usually the best one, but sometimes a random one to try something new
+    pub fn act(&mut self, observation: &[f32]) -> usize {
+        if self.prng.gen::<f32>() < self.epsilon() {
+            self.prng.gen_range(0..Action::ALL.len())
+        } else {
+            self.best_action(observation)
+        }
+    }
+
+    /// Remember a move, and learn from a batch of past moves once there are enough of them
+    /// Returns the loss from the learning, if there was any
+    pub fn remember(&mut self, experience: Experience) -> Option<f32> {
+        self.memory.push(experience);
+        self.steps += 1;
+
+        self.until_sync = self.until_sync.saturating_sub(1);
+        if self.until_sync == 0 {
+            self.target = self.online.clone();
+            self.until_sync = self.config.target_sync;
+        }
+
+        if self.steps < self.config.warmup || self.memory.len() < self.config.batch_size {
+            return None;
+        }
+
+        Some(self.learn())
+    }
+
+    /// Play a whole game in the environment with the specified seed, learning as it goes
+    pub fn train_episode(&mut self, env: &mut dyn Environment, seed: u64) -> Episode {
+        let mut observation = env.reset(seed);
+        let mut steps = 0;
+        let mut reward = 0.0;
+        let mut losses = Vec::new();
+
+        let score = loop {
+            let action = self.act(&observation);
+            let step = env.step(Action::ALL[action]);
+            steps += 1;
+            reward += step.reward;
+
+            // Games that are cut short still had more reward to come, so only the ones that really ended count as done
+            let experience = Experience {
+                observation,
+                action,
+                reward: step.reward,
+                next_observation: step.observation.clone(),
+                done: step.done,
+            };
+            losses.extend(self.remember(experience));
+
+            if step.is_over() {
+                break step.score;
+            }
+            observation = step.observation;
+        };
+
+        Episode {
+            score,
+            steps,
+            reward,
+            loss: match losses.is_empty() {
+                true => None,
+                false => Some(losses.iter().sum::<f32>() / losses.len() as f32),
+            },
+        }
+    }
+
+    /// Make an AiPlayer that plays with a copy of the trained brain, so it can play real games or be saved
+    pub fn player(&self) -> AiPlayer {
+        AiPlayer::with_encoder(self.online.clone(), rebuild(self.encoder.as_ref()))
+    }
+
+    /// Take one step down the gradient, using a random batch of past moves
+    /// Returns the mean loss for the batch
+    fn learn(&mut self) -> f32 {
+        let batch = self.memory.sample(&mut self.prng, self.config.batch_size);
+        let mut gradients = vec![0.0; self.online.weights().count()];
+        let mut loss = 0.0;
+
+        for experience in &batch {
+            // Aim for the reward that was given, plus the best the target brain thinks can be done from where the move ended up
+            let mut target = experience.reward;
+            if !experience.done {
+                let next = self.target.propagate(experience.next_observation.clone());
+                target += self.config.discount * next[argmax(&next)];
+            }
+
+            let trace = self.online.trace(experience.observation.clone());
+            let outputs = &trace[trace.len() - 1];
+
+            // Only the move that was actually made has anything to learn from - the error is clipped (i.e. Huber loss) so that big surprises don't throw everything off
+            let error = outputs[experience.action] - target;
+            let clipped = error.clamp(-1.0, 1.0);
+            loss += match error.abs() <= 1.0 {
+                true => error * error / 2.0,
+                false => error.abs() - 0.5,
+            };
+
+            let mut output_gradients = vec![0.0; outputs.len()];
+            output_gradients[experience.action] = clipped;
+            for (total, gradient) in gradients
+                .iter_mut()
+                .zip(self.online.backpropagate(&trace, &output_gradients))
+            {
+                *total += gradient;
+            }
+        }
+
+        let size = batch.len() as f32;
+        self.online
+            .descend(&gradients, self.config.learning_rate / size);
+
+        loss / size
+    }
+}
+
+/// Pick random starting weights, scaled by how many inputs each neuron has so that the outputs start off a sensible size, with the biases starting at 0
+fn initial_weights(prng: &mut ChaCha8Rng, layers: &[LayerTopology]) -> Vec<f32> {
+    let mut weights = Vec::with_capacity(Network::weight_count(layers));
+
+    for pair in layers.windows(2) {
+        let limit = (6.0 / pair[0].neurons as f32).sqrt();
+        for _ in 0..pair[1].neurons {
+            weights.push(0.0);
+            weights.extend((0..pair[0].neurons).map(|_| prng.gen_range(-limit..=limit)));
+        }
+    }
+
+    weights
+}
+
+#[cfg(test)]
+mod tests {
+    use snek_core::env::SnekEnv;
+    use snek_core::observation::{EncoderKind, Senses};
+
+    use super::*;
+
+    fn agent(dqn: DqnConfig) -> DqnAgent {
+        DqnAgent::new(&Config::default(), dqn, Box::new(Senses), 0)
+    }
+
+    #[test]
+    fn learns_the_value_of_a_move() {
+        let mut agent = agent(DqnConfig {
+            warmup: 0,
+            batch_size: 4,
+            learning_rate: 0.05,
+            ..DqnConfig::default()
+        });
+
+        // Turning left here always ends the game with a reward of -1
+        let observation = vec![1.0, 0.0, 0.0, 0.5, 0.0];
+        for _ in 0..500 {
+            agent.remember(Experience {
+                observation: observation.clone(),
+                action: 1,
+                reward: -1.0,
+                next_observation: observation.clone(),
+                done: true,
+            });
+        }
+
+        let values = agent.brain().propagate(observation);
+        assert!((values[1] + 1.0).abs() < 0.05, "{:?}", values);
+    }
+
+    #[test]
+    fn training_is_repeatable() {
+        let dqn = DqnConfig {
+            warmup: 50,
+            epsilon_decay: 200,
+            ..DqnConfig::default()
+        };
+
+        let mut episodes = Vec::new();
+        for _ in 0..2 {
+            let mut agent = agent(dqn.clone());
+            let mut env = SnekEnv::new(6, 6, Box::new(Senses));
+            let played: Vec<Episode> = (0..5)
+                .map(|seed| agent.train_episode(&mut env, seed))
+                .collect();
+            assert!(played.iter().any(|episode| episode.loss.is_some()));
+            episodes.push(played);
+        }
+        assert_eq!(episodes[0], episodes[1]);
+
+        // Epsilon falls off as training goes on
+        let mut agent = agent(dqn);
+        assert!((agent.epsilon() - 1.0).abs() < f32::EPSILON);
+        agent.steps = 1_000;
+        assert!((agent.epsilon() - 0.05).abs() < f32::EPSILON);
+
+        // The trained brain can play as a normal AiPlayer, and keeps its negative outputs when saved
+        let player = agent.player();
+        assert_eq!(player.brain().output_activation(), Activation::Linear);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("brain.toml");
+        player.save(&path).unwrap();
+        let loaded = AiPlayer::load(&path).unwrap();
+        assert_eq!(loaded.brain().output_activation(), Activation::Linear);
+        assert_eq!(loaded.encoder.kind(), EncoderKind::Senses);
+    }
+}
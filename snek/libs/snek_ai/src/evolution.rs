@@ -0,0 +1,339 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use genetic_algorithm::{
+    chromosome::Chromosome, crossover::UniformCrossover, individual::Individual,
+    mutation::GaussianMutation, selection::RouletteWheelSelection, statistics::Statistics,
+    GeneticAlgorithm,
+};
+use neural_network::{topology::LayerTopology, Network};
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use snek_core::{
+    entities::topology::TopologyKind,
+    env::{Action, Environment, SnekEnv},
+    observation::Encoder,
+    settings::Config,
+};
+
+use crate::{argmax, rebuild, topology, AiPlayer};
+
+/// How much fitness a trainee gets for surviving a whole Grid's worth of moves, on top of its score
+/// This gives the very first generations, which hardly ever eat anything, something to tell them apart
+const SURVIVAL_BONUS: f32 = 0.1;
+
+/// A brain in the population, along with how well it played
+#[derive(Clone, Debug)]
+pub struct Trainee {
+    chromosome: Chromosome,
+    fitness: f32,
+}
+
+impl Individual for Trainee {
+    fn create(chromosome: Chromosome) -> Self {
+        Self {
+            chromosome,
+            fitness: 0.0,
+        }
+    }
+
+    fn chromosome(&self) -> &Chromosome {
+        &self.chromosome
+    }
+
+    fn fitness(&self) -> f32 {
+        self.fitness
+    }
+}
+
+/// How a single generation got on
+#[derive(Debug, Clone)]
+pub struct Generation {
+    /// Counting from 1
+    pub number: usize,
+    pub statistics: Statistics,
+    /// The fittest AI in the generation
+    pub champion: AiPlayer,
+    /// The best score from any single game in the generation
+    pub best_score: usize,
+}
+
+/// Evolves a population of AIs, by playing each of them through the same handful of games and breeding the best
+pub struct Trainer {
+    config: Config,
+    layers: [LayerTopology; 3],
+    env: SnekEnv,
+    ga: GeneticAlgorithm<RouletteWheelSelection, UniformCrossover, GaussianMutation>,
+    prng: ChaCha8Rng,
+    population: Vec<Trainee>,
+    generation: usize,
+}
+
+impl Trainer {
+    /// Create a new Trainer with a randomly wired population, laid out according to the Config, that plays on Grids of the specified size
+    pub fn new(
+        config: &Config,
+        encoder: Box<dyn Encoder>,
+        width: usize,
+        height: usize,
+        seed: u64,
+    ) -> Self {
+        assert_eq!(
+            config.eye_photoreceptors,
+            encoder.size(),
+            "The Config doesn't match the encoder - use Config::for_encoder"
+        );
+
+        let mut prng = ChaCha8Rng::seed_from_u64(seed);
+        let layers = topology(config);
+        let population = (0..config.population_size)
+            .map(|_| Trainee::create(Network::random(&mut prng, &layers).weights().collect()))
+            .collect();
+
+        Self {
+            config: config.clone(),
+            layers,
+            env: SnekEnv::new(width, height, encoder),
+            ga: GeneticAlgorithm::new(
+                RouletteWheelSelection::new(),
+                UniformCrossover::new(),
+                GaussianMutation::new(0.01, 0.3),
+            ),
+            prng,
+            population,
+            generation: 0,
+        }
+    }
+
+    /// Play the games on Grids with the specified Topology, rather than the usual square ones
+    pub fn with_topology(mut self, kind: TopologyKind) -> Self {
+        self.env = self.env.with_topology(kind);
+        self
+    }
+
+    /// How many generations have been evolved so far
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    /// Play every AI in the current population through `generation_length` games, then breed the next generation from the fittest
+    pub fn evolve(&mut self) -> Generation {
+        // Everyone plays the same games, so nobody gets lucky with where the Food turns up
+        let seeds: Vec<u64> = (0..self.config.generation_length)
+            .map(|_| self.prng.next_u64())
+            .collect();
+
+        let mut best_score = 0;
+        for idx in 0..self.population.len() {
+            let brain = self.brain(&self.population[idx]);
+            let (fitness, score) = self.assess(&brain, &seeds);
+            self.population[idx].fitness = fitness;
+            best_score = best_score.max(score);
+        }
+
+        let champion = self
+            .population
+            .iter()
+            .fold(&self.population[0], |best, trainee| {
+                if trainee.fitness > best.fitness {
+                    trainee
+                } else {
+                    best
+                }
+            });
+        let champion = AiPlayer::with_encoder(self.brain(champion), rebuild(self.env.encoder()));
+
+        let (population, statistics) = self.ga.evolve(&mut self.prng, &self.population);
+        self.population = population;
+        self.generation += 1;
+
+        Generation {
+            number: self.generation,
+            statistics,
+            champion,
+            best_score,
+        }
+    }
+
+    /// Wire up a brain from a trainee's chromosome
+    fn brain(&self, trainee: &Trainee) -> Network {
+        Network::from_weights(&self.layers, trainee.chromosome.iter().copied())
+    }
+
+    /// Play the brain through a game with each of the seeds, returning its fitness and its best score
+    fn assess(&mut self, brain: &Network, seeds: &[u64]) -> (f32, usize) {
+        let squares = self.env.state().grid.width() * self.env.state().grid.height();
+        let mut fitness = 0.0;
+        let mut best_score = 0;
+
+        for &seed in seeds {
+            let mut observation = self.env.reset(seed);
+            let mut steps = 0;
+
+            let score = loop {
+                let action = Action::ALL[argmax(&brain.propagate(observation))];
+                let transition = self.env.step(action);
+                steps += 1;
+
+                if transition.is_over() {
+                    break transition.score;
+                }
+                observation = transition.observation;
+            };
+
+            fitness += score as f32 + SURVIVAL_BONUS * steps as f32 / squares as f32;
+            best_score = best_score.max(score);
+        }
+
+        (fitness, best_score)
+    }
+}
+
+/// Everything a Training has found out so far
+#[derive(Debug, Default)]
+pub struct Progress {
+    /// How fit each generation was, oldest first
+    pub history: Vec<Statistics>,
+    /// The fittest AI from the latest generation, along with its generation number
+    pub champion: Option<(usize, AiPlayer)>,
+    /// The best score from any single game so far
+    pub best_score: usize,
+}
+
+/// A Trainer evolving generation after generation on a thread of its own, until it's dropped
+/// Its Progress can be looked at from anywhere, such as a window showing how the training is going
+pub struct Training {
+    progress: Arc<Mutex<Progress>>,
+    paused: Arc<AtomicBool>,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Training {
+    /// Start evolving in the background
+    pub fn start(mut trainer: Trainer) -> Self {
+        let progress = Arc::new(Mutex::new(Progress::default()));
+        let paused = Arc::new(AtomicBool::new(false));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let handle = {
+            let progress = Arc::clone(&progress);
+            let paused = Arc::clone(&paused);
+            let stop = Arc::clone(&stop);
+
+            std::thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    if paused.load(Ordering::Relaxed) {
+                        std::thread::sleep(Duration::from_millis(50));
+                        continue;
+                    }
+
+                    // Only hold the lock long enough to hand over the results, so the window never waits on a whole generation
+                    let generation = trainer.evolve();
+                    let mut progress = progress.lock().expect("The training window panicked");
+                    progress.history.push(generation.statistics);
+                    progress.champion = Some((generation.number, generation.champion));
+                    progress.best_score = progress.best_score.max(generation.best_score);
+                }
+            })
+        };
+
+        Self {
+            progress,
+            paused,
+            stop,
+            handle: Some(handle),
+        }
+    }
+
+    /// Get a look at everything found out so far - the training can't hand over another generation until this is dropped
+    pub fn progress(&self) -> MutexGuard<'_, Progress> {
+        self.progress.lock().expect("The training thread panicked")
+    }
+
+    /// Stop evolving after the current generation if it's running, or carry on if it's stopped
+    pub fn toggle_pause(&self) {
+        self.paused.fetch_xor(true, Ordering::Relaxed);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for Training {
+    /// Finish off the current generation and wait for the thread to end
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use snek_core::observation::Senses;
+
+    use super::*;
+
+    fn trainer(seed: u64) -> Trainer {
+        let config = Config {
+            population_size: 10,
+            generation_length: 2,
+            ..Config::default()
+        };
+
+        Trainer::new(&config, Box::new(Senses), 6, 6, seed)
+    }
+
+    #[test]
+    fn evolution_is_repeatable() {
+        let mut runs = Vec::new();
+        for _ in 0..2 {
+            let mut trainer = trainer(1);
+            let generations: Vec<Generation> = (0..3).map(|_| trainer.evolve()).collect();
+            assert_eq!(trainer.generation(), 3);
+            runs.push(generations);
+        }
+
+        for (a, b) in runs[0].iter().zip(&runs[1]) {
+            assert_eq!(a.number, b.number);
+            assert_eq!(a.best_score, b.best_score);
+            assert_eq!(a.statistics.max_fitness(), b.statistics.max_fitness());
+            assert!(a
+                .champion
+                .brain()
+                .weights()
+                .eq(b.champion.brain().weights()));
+
+            // Everyone gets at least a little fitness for surviving, so there's always something to choose between
+            assert!(a.statistics.min_fitness() > 0.0);
+            assert!(a.statistics.min_fitness() <= a.statistics.avg_fitness());
+            assert!(a.statistics.avg_fitness() <= a.statistics.max_fitness());
+        }
+    }
+
+    #[test]
+    fn training_in_the_background() {
+        let training = Training::start(trainer(2));
+
+        // Wait for a couple of generations to come through
+        for _ in 0..1_000 {
+            if training.progress().history.len() >= 2 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        training.toggle_pause();
+        assert!(training.is_paused());
+
+        let progress = training.progress();
+        assert!(progress.history.len() >= 2);
+        assert!(matches!(progress.champion, Some((n, _)) if n == progress.history.len()));
+    }
+}
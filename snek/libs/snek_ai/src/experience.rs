@@ -0,0 +1,97 @@
+use rand::{Rng, RngCore};
+
+/// A single move made while training, along with what came of it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Experience {
+    pub observation: Vec<f32>,
+    pub action: usize,
+    pub reward: f32,
+    pub next_observation: Vec<f32>,
+    /// Whether the move ended the game, in which case nothing more can come of it
+    pub done: bool,
+}
+
+/// Holds on to the most recent experiences, so that training can learn from a random mix of them rather than just the last few moves in a row
+#[derive(Debug)]
+pub struct ExperienceBuffer {
+    capacity: usize,
+    experiences: Vec<Experience>,
+    /// Where the next experience goes once the buffer is full, overwriting the oldest one
+    next: usize,
+}
+
+impl ExperienceBuffer {
+    /// Create a new ExperienceBuffer that holds on to at most the specified number of experiences
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0);
+
+        Self {
+            capacity,
+            experiences: Vec::with_capacity(capacity),
+            next: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.experiences.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.experiences.is_empty()
+    }
+
+    /// Add an experience, throwing away the oldest one if the buffer is full
+    pub fn push(&mut self, experience: Experience) {
+        if self.experiences.len() < self.capacity {
+            self.experiences.push(experience);
+        } else {
+            self.experiences[self.next] = experience;
+        }
+
+        self.next = (self.next + 1) % self.capacity;
+    }
+
+    /// Pick the specified number of experiences at random (the same one may come up more than once)
+    pub fn sample(&self, prng: &mut dyn RngCore, count: usize) -> Vec<&Experience> {
+        if self.experiences.is_empty() {
+            return Vec::new();
+        }
+
+        (0..count)
+            .map(|_| &self.experiences[prng.gen_range(0..self.experiences.len())])
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+
+    fn experience(reward: f32) -> Experience {
+        Experience {
+            observation: vec![0.0],
+            action: 0,
+            reward,
+            next_observation: vec![0.0],
+            done: false,
+        }
+    }
+
+    #[test]
+    fn oldest_experiences_are_forgotten() {
+        let mut buffer = ExperienceBuffer::new(3);
+        for reward in 0..5 {
+            buffer.push(experience(reward as f32));
+        }
+        assert_eq!(buffer.len(), 3);
+
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+        let samples = buffer.sample(&mut prng, 20);
+        assert_eq!(samples.len(), 20);
+        assert!(samples.iter().all(|e| e.reward >= 2.0));
+        assert!(ExperienceBuffer::new(1).sample(&mut prng, 5).is_empty());
+    }
+}
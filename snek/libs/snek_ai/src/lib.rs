@@ -1,15 +1,331 @@
-use snek_core::{players::Move, types::Vec2};
+use std::io::ErrorKind;
+use std::path::Path;
 
-pub struct AiPlayer {}
+use neural_network::{activation::Activation, topology::LayerTopology, Network};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use snek_core::{
+    observation::{Encoder, EncoderKind, Senses},
+    players::{Move, View},
+    settings::Config,
+    types::Direction,
+};
+
+pub mod dqn;
+pub mod evolution;
+pub mod experience;
+pub mod visualiser;
+
+/// The AI produces one value for each way it can go: how much it wants to go ahead, turn left, or turn right
+#[derive(Debug)]
+pub struct AiPlayer {
+    brain: Network,
+    /// How the AI turns what it can see into inputs for its brain
+    encoder: Box<dyn Encoder>,
+}
+
+impl AiPlayer {
+    /// Create a new AiPlayer with the specified brain, which sees the game through the simple Senses encoder
+    pub fn new(brain: Network) -> Self {
+        Self::with_encoder(brain, Box::new(Senses))
+    }
+
+    /// Create a new AiPlayer with the specified brain, which sees the game through the specified encoder
+    pub fn with_encoder(brain: Network, encoder: Box<dyn Encoder>) -> Self {
+        Self { brain, encoder }
+    }
+
+    /// Create a new AiPlayer with a randomly wired brain, which sees the game through the simple Senses encoder
+    pub fn random(prng: &mut dyn RngCore, config: &Config) -> Self {
+        Self::random_with_encoder(prng, config, Box::new(Senses))
+    }
+
+    /// Create a new AiPlayer with a randomly wired brain, which sees the game through the specified encoder
+    /// The Config must have been made for the same encoder, so that the brain has the right number of inputs
+    pub fn random_with_encoder(
+        prng: &mut dyn RngCore,
+        config: &Config,
+        encoder: Box<dyn Encoder>,
+    ) -> Self {
+        assert_eq!(
+            config.eye_photoreceptors,
+            encoder.size(),
+            "The Config doesn't match the encoder - use Config::for_encoder"
+        );
+
+        let brain = Network::random(prng, &topology(config));
+
+        Self { brain, encoder }
+    }
+}
+
+/// The layout of a saved brain file
+#[derive(Serialize, Deserialize)]
+struct SavedBrain {
+    /// How many neurons are in each layer, starting with the inputs
+    layers: Vec<usize>,
+    weights: Vec<f32>,
+    /// Brains trained to estimate rewards (rather than evolved) have outputs that can go negative
+    #[serde(default)]
+    linear_output: bool,
+    /// TOML needs tables to come after plain values, so this has to go last
+    encoder: EncoderKind,
+}
+
+impl AiPlayer {
+    /// Get an immutable ref to the AI's brain
+    pub fn brain(&self) -> &Network {
+        &self.brain
+    }
+
+    /// Work out what every layer of the AI's brain puts out for what it can see right now, e.g. for drawing it
+    pub fn trace(&self, view: &View) -> Vec<Vec<f32>> {
+        self.brain.trace(self.encoder.encode(view))
+    }
+
+    /// Save the AI's brain to the specified file, along with how it sees the game
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let saved = SavedBrain {
+            layers: self
+                .brain
+                .topology()
+                .iter()
+                .map(|layer| layer.neurons)
+                .collect(),
+            weights: self.brain.weights().collect(),
+            linear_output: self.brain.output_activation() == Activation::Linear,
+            encoder: self.encoder.kind(),
+        };
+        let contents =
+            toml::to_string(&saved).map_err(|e| std::io::Error::new(ErrorKind::InvalidData, e))?;
+
+        if let Some(dir) = path.as_ref().parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, contents)
+    }
+
+    /// Load an AI from a brain file written by `save`
+    /// The file is checked over first, so that a brain that doesn't fit its encoder is an error rather than a panic
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let invalid = |msg: String| std::io::Error::new(ErrorKind::InvalidData, msg);
+
+        let contents = std::fs::read_to_string(path)?;
+        let saved: SavedBrain = toml::from_str(&contents).map_err(|e| invalid(e.to_string()))?;
+
+        let encoder = saved
+            .encoder
+            .build()
+            .ok_or_else(|| invalid(format!("{:?} isn't a valid encoder", saved.encoder)))?;
+        let layers: Vec<LayerTopology> = saved
+            .layers
+            .iter()
+            .map(|&neurons| LayerTopology { neurons })
+            .collect();
+
+        if layers.len() < 2 || layers.iter().any(|layer| layer.neurons == 0) {
+            return Err(invalid(format!("{:?} isn't a valid brain", saved.layers)));
+        }
+        if layers[0].neurons != encoder.size() {
+            return Err(invalid(format!(
+                "the brain takes {} inputs, but the encoder gives it {}",
+                layers[0].neurons,
+                encoder.size()
+            )));
+        }
+        let expected = Network::weight_count(&layers);
+        if saved.weights.len() != expected {
+            return Err(invalid(format!(
+                "the brain needs {} weights, but the file has {}",
+                expected,
+                saved.weights.len()
+            )));
+        }
+
+        let mut brain = Network::from_weights(&layers, saved.weights);
+        if saved.linear_output {
+            brain = brain.with_output_activation(Activation::Linear);
+        }
+
+        Ok(Self::with_encoder(brain, encoder))
+    }
+}
+
+impl Clone for AiPlayer {
+    fn clone(&self) -> Self {
+        Self::with_encoder(self.brain.clone(), rebuild(self.encoder.as_ref()))
+    }
+}
+
+/// How an AI's brain is laid out: one input per value the encoder produces, a hidden layer, and one output per move
+pub(crate) fn topology(config: &Config) -> [LayerTopology; 3] {
+    [
+        LayerTopology {
+            neurons: config.eye_photoreceptors,
+        },
+        LayerTopology {
+            neurons: config.brain_neurons,
+        },
+        LayerTopology {
+            neurons: config.outputs,
+        },
+    ]
+}
+
+/// The index of the biggest value
+pub(crate) fn argmax(values: &[f32]) -> usize {
+    values.iter().enumerate().fold(
+        0,
+        |best, (idx, value)| if *value > values[best] { idx } else { best },
+    )
+}
+
+/// Make a copy of an encoder - every encoder can be rebuilt from its own description
+fn rebuild(encoder: &dyn Encoder) -> Box<dyn Encoder> {
+    encoder
+        .kind()
+        .build()
+        .expect("Encoders always describe themselves validly")
+}
 
 impl Default for AiPlayer {
     fn default() -> Self {
-        todo!()
+        Self::random(&mut rand::thread_rng(), &Config::default())
     }
 }
 
 impl Move for AiPlayer {
-    fn make_move(&self, _ctx: &mut ggez::Context) -> Option<Vec2> {
-        todo!()
+    fn make_move(&mut self, view: &View) -> Option<Direction> {
+        let outputs = self.brain.propagate(self.encoder.encode(view));
+
+        // Go whichever way the brain is keenest on
+        let choice = argmax(&outputs);
+
+        let orientation = *view.snek().orientation();
+        let topology = view.grid.topology();
+        match choice {
+            1 => Some(topology.turn_left(orientation)),
+            2 => Some(topology.turn_right(orientation)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+    use snek_core::entities::{food::Food, grid::Grid, snek::Snek};
+    use snek_core::observation::Rays;
+    use snek_core::types::Vec2;
+
+    use super::*;
+
+    #[test]
+    fn save_and_load_brain() {
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+        let config = Config::for_encoder(&Rays);
+        let player = AiPlayer::random_with_encoder(&mut prng, &config, Box::new(Rays));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("brain.toml");
+        player.save(&path).unwrap();
+
+        // The loaded brain is identical, and sees the game the same way
+        let loaded = AiPlayer::load(&path).unwrap();
+        let expected: Vec<f32> = player.brain().weights().collect();
+        let actual: Vec<f32> = loaded.brain().weights().collect();
+        assert_eq!(actual, expected);
+        assert_eq!(loaded.encoder.kind(), EncoderKind::Rays);
+
+        // Brains that don't match their encoder are rejected
+        let contents = std::fs::read_to_string(&path)
+            .unwrap()
+            .replace("kind = \"rays\"", "kind = \"senses\"");
+        std::fs::write(&path, contents).unwrap();
+        assert!(AiPlayer::load(&path).is_err());
+    }
+
+    #[test]
+    fn ai_sees_danger_and_food() {
+        let grid = Grid::new(10, 10).unwrap();
+        let sneks = vec![
+            Snek::new(Vec2::new(5, 5), grid.bounds()),
+            Snek::new(Vec2::new(5, 6), grid.bounds()),
+        ];
+        let food = vec![Food::new(Vec2::new(5, 9))];
+        let keys = HashSet::new();
+
+        let view = View {
+            me: 0,
+            grid: &grid,
+            sneks: &sneks,
+            food: &food,
+            keys: &keys,
+        };
+
+        // The other Snek is directly ahead, and the Food is 4 squares further on
+        assert_eq!(Senses.encode(&view), vec![1.0, 0.0, 0.0, 0.4, 0.0]);
+    }
+
+    #[test]
+    fn ai_always_moves() {
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+        let mut player = AiPlayer::random(&mut prng, &Config::default());
+
+        let grid = Grid::default();
+        let sneks = vec![Snek::default()];
+        let food = vec![Food::new(Vec2::new(3, 3))];
+        let keys = HashSet::new();
+
+        let view = View {
+            me: 0,
+            grid: &grid,
+            sneks: &sneks,
+            food: &food,
+            keys: &keys,
+        };
+
+        // Whatever the brain decides, it should only ever ask to turn left or right (carrying on ahead is no move at all)
+        if let Some(dir) = player.make_move(&view) {
+            let ahead = *view.snek().orientation();
+            assert!(dir == ahead.turn_left() || dir == ahead.turn_right());
+        }
+    }
+
+    #[test]
+    fn ai_with_other_encoders() {
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+        let config = Config::for_encoder(&Rays);
+        assert_eq!(config.eye_photoreceptors, 24);
+
+        let mut player = AiPlayer::random_with_encoder(&mut prng, &config, Box::new(Rays));
+
+        let grid = Grid::default();
+        let sneks = vec![Snek::default()];
+        let food = vec![Food::new(Vec2::new(3, 3))];
+        let keys = HashSet::new();
+
+        let view = View {
+            me: 0,
+            grid: &grid,
+            sneks: &sneks,
+            food: &food,
+            keys: &keys,
+        };
+
+        if let Some(dir) = player.make_move(&view) {
+            let ahead = *view.snek().orientation();
+            assert!(dir == ahead.turn_left() || dir == ahead.turn_right());
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn mismatched_config() {
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+        AiPlayer::random_with_encoder(&mut prng, &Config::default(), Box::new(Rays));
     }
 }
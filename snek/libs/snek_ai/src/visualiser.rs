@@ -0,0 +1,406 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use genetic_algorithm::statistics::Statistics;
+use ggez::event::EventHandler;
+use ggez::graphics::{self, Color, DrawMode, Mesh, MeshBuilder, Rect};
+use ggez::input::keyboard::{KeyCode, KeyMods};
+use ggez::{Context, GameError, GameResult};
+use neural_network::diagram::Diagram;
+use rand_chacha::ChaCha8Rng;
+use snek_core::{
+    entities::{grid::Grid, topology::TopologyKind},
+    game::{
+        engine::{tick_progress, Renderer},
+        state::GameState,
+    },
+    players::{human::input::Input, View},
+};
+
+use crate::evolution::Training;
+use crate::AiPlayer;
+
+/// Where the fitness chart goes on the screen
+const CHART: Rect = Rect {
+    x: 530.0,
+    y: 20.0,
+    w: 250.0,
+    h: 150.0,
+};
+
+/// Where the champion's brain goes on the screen
+const BRAIN: Rect = Rect {
+    x: 530.0,
+    y: 190.0,
+    w: 250.0,
+    h: 300.0,
+};
+
+/// The colours of the min, avg and max fitness lines on the chart
+const LINE_COLOURS: [(u8, u8, u8); 3] = [(200, 50, 50), (200, 200, 50), (50, 200, 50)];
+
+/// A window onto a Training running in the background: the latest champion plays on a loop, next to a chart of fitness over the generations
+/// The window only ever samples the Training's Progress, so drawing never holds up the evolving
+pub struct TrainingView {
+    training: Training,
+    width: usize,
+    height: usize,
+    topology: TopologyKind,
+    /// The champion's current game, along with the generation it came from
+    game: Option<(usize, GameState<ChaCha8Rng, AiPlayer>)>,
+    /// The champion's brain laid out for drawing, which only needs doing once per champion
+    diagram: Option<Diagram>,
+    /// Whether to draw the champion's brain
+    show_brain: bool,
+    /// How much faster (or slower) than normal the champion plays, which carries over from game to game
+    speed: i32,
+    /// Where the champion gets saved to
+    save_path: PathBuf,
+    /// What happened the last time the champion was saved
+    message: Option<String>,
+    keys: HashSet<Input>,
+    renderer: Renderer,
+}
+
+impl TrainingView {
+    /// Watch the specified Training, with the champion playing on Grids of the specified size and saved to the specified file
+    pub fn new(
+        training: Training,
+        width: usize,
+        height: usize,
+        save_path: impl Into<PathBuf>,
+    ) -> Self {
+        Self {
+            training,
+            width,
+            height,
+            topology: TopologyKind::default(),
+            game: None,
+            diagram: None,
+            show_brain: true,
+            speed: 0,
+            save_path: save_path.into(),
+            message: None,
+            keys: HashSet::new(),
+            renderer: Renderer::default(),
+        }
+    }
+
+    /// Watch the champion play on Grids with the specified Topology, which should be the one it's training on
+    pub fn with_topology(mut self, kind: TopologyKind) -> Self {
+        self.topology = kind;
+        self
+    }
+
+    /// Start the latest champion on a new game, once the last one has finished
+    fn next_game(&mut self) {
+        if matches!(&self.game, Some((_, state)) if state.play) {
+            return;
+        }
+
+        let progress = self.training.progress();
+        if let Some((number, champion)) = &progress.champion {
+            let grid = Grid::new(self.width, self.height)
+                .expect("Grids need to be at least 1x1")
+                .with_topology(self.topology);
+            let mut state = GameState::seeded(grid, *number as u64, vec![champion.clone()]);
+            state.adjust_speed(self.speed);
+            self.diagram = Some(Diagram::new(champion.brain(), BRAIN.w, BRAIN.h));
+            self.game = Some((*number, state));
+        }
+    }
+
+    /// Save the latest champion, and remember how it went so it can be shown on screen
+    fn save_champion(&mut self) {
+        let progress = self.training.progress();
+
+        self.message = Some(match &progress.champion {
+            Some((number, champion)) => match champion.save(&self.save_path) {
+                Ok(()) => format!(
+                    "Saved the champion from generation {} to {}",
+                    number,
+                    self.save_path.display()
+                ),
+                Err(e) => format!("Couldn't save the champion: {}", e),
+            },
+            None => "There's no champion to save yet".to_owned(),
+        });
+    }
+
+    /// The lines written at the top of the screen
+    fn status(&self) -> Vec<String> {
+        let progress = self.training.progress();
+
+        let generation = match self.training.is_paused() {
+            true => format!("Generation {} (PAUSED)", progress.history.len()),
+            false => format!("Generation {}", progress.history.len()),
+        };
+        let champion = match &self.game {
+            Some((number, _)) => format!("Watching the champion from generation {}", number),
+            None => "Waiting for the first generation...".to_owned(),
+        };
+
+        let mut lines = vec![
+            generation,
+            format!("Best score so far: {}", progress.best_score),
+            champion,
+        ];
+        if let Some(stats) = progress.history.last() {
+            lines.push(format!(
+                "Fitness: min {:.2}, avg {:.2}, max {:.2}",
+                stats.min_fitness(),
+                stats.avg_fitness(),
+                stats.max_fitness()
+            ));
+        }
+        lines.push(
+            "+/- speed, P pause, S save the champion, B show/hide its brain, Esc quit".to_owned(),
+        );
+        lines.extend(self.message.clone());
+
+        lines
+    }
+}
+
+impl EventHandler<GameError> for TrainingView {
+    /// Called every frame
+    fn update(&mut self, ctx: &mut Context) -> GameResult<()> {
+        self.next_game();
+
+        if let Some((_, state)) = &mut self.game {
+            state.poll_players(&self.keys);
+
+            while ggez::timer::check_update_time(ctx, state.tick_rate()) {
+                if state.play {
+                    state.step();
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Draw the champion's game, with the chart and the training status over the top
+    fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
+        match &self.game {
+            Some((_, state)) => {
+                let alpha = tick_progress(ctx, state.tick_rate());
+                self.renderer.draw_game(ctx, state, alpha)?;
+            }
+            None => graphics::clear(ctx, Color::from_rgba(0, 0, 0, 255)),
+        }
+
+        draw_chart(ctx, &self.training.progress().history)?;
+
+        // Light up the brain with what the champion is thinking right now
+        if let (true, Some((_, state)), Some(diagram)) =
+            (self.show_brain, &self.game, &self.diagram)
+        {
+            let view = View {
+                me: 0,
+                grid: &state.grid,
+                sneks: &state.sneks,
+                food: &state.food,
+                keys: &self.keys,
+            };
+            let trace = state.players[0].trace(&view);
+            draw_brain(ctx, diagram, &trace, [BRAIN.x, BRAIN.y])?;
+        }
+
+        for (idx, line) in self.status().iter().enumerate() {
+            let text = graphics::Text::new(line.as_str());
+            let params = graphics::DrawParam::default().dest([20.0, 20.0 + 25.0 * idx as f32]);
+            graphics::draw(ctx, &text, params)?;
+        }
+
+        graphics::present(ctx)
+    }
+
+    /// Speed the champion up or slow it down, pause the training, or save the champion
+    fn key_down_event(
+        &mut self,
+        ctx: &mut Context,
+        keycode: KeyCode,
+        _keymods: KeyMods,
+        _repeat: bool,
+    ) {
+        let delta = match keycode {
+            KeyCode::Equals | KeyCode::Plus | KeyCode::NumpadAdd => 1,
+            KeyCode::Minus | KeyCode::NumpadSubtract => -1,
+            KeyCode::P | KeyCode::Space => {
+                self.training.toggle_pause();
+                0
+            }
+            KeyCode::S => {
+                self.save_champion();
+                0
+            }
+            KeyCode::B => {
+                self.show_brain = !self.show_brain;
+                0
+            }
+            KeyCode::Escape => {
+                ggez::event::quit(ctx);
+                0
+            }
+            _ => 0,
+        };
+
+        if delta != 0 {
+            self.speed += delta;
+            if let Some((_, state)) = &mut self.game {
+                state.adjust_speed(delta);
+            }
+        }
+    }
+}
+
+/// Draw the min, avg and max fitness of every generation so far as lines on a chart
+fn draw_chart(ctx: &mut Context, history: &[Statistics]) -> GameResult {
+    let panel = Mesh::new_rectangle(ctx, DrawMode::fill(), CHART, Color::from_rgba(0, 0, 0, 180))?;
+    graphics::draw(ctx, &panel, graphics::DrawParam::default())?;
+
+    // Lines need at least two points
+    if history.len() < 2 {
+        return Ok(());
+    }
+
+    let mut builder = MeshBuilder::new();
+    builder.rectangle(DrawMode::stroke(1.0), CHART, Color::from_rgb(100, 100, 100))?;
+    for (points, (r, g, b)) in chart_lines(history, CHART).iter().zip(LINE_COLOURS.iter()) {
+        builder.line(points, 2.0, Color::from_rgb(*r, *g, *b))?;
+    }
+
+    let mesh = builder.build(ctx)?;
+    graphics::draw(ctx, &mesh, graphics::DrawParam::default())
+}
+
+/// Draw a brain diagram with its top left corner at the specified point, with each neuron shaded by how hard it's firing
+fn draw_brain(
+    ctx: &mut Context,
+    diagram: &Diagram,
+    trace: &[Vec<f32>],
+    origin: [f32; 2],
+) -> GameResult {
+    let panel = Mesh::new_rectangle(
+        ctx,
+        DrawMode::fill(),
+        Rect::new(origin[0], origin[1], BRAIN.w, BRAIN.h),
+        Color::from_rgba(0, 0, 0, 180),
+    )?;
+    graphics::draw(ctx, &panel, graphics::DrawParam::default())?;
+
+    let mut builder = MeshBuilder::new();
+    for edge in diagram.edges() {
+        let (r, g, b) = edge.colour;
+        builder.line(
+            &[[edge.from.x, edge.from.y], [edge.to.x, edge.to.y]],
+            edge.thickness,
+            Color::from_rgba(r, g, b, (255.0 * edge.opacity) as u8),
+        )?;
+    }
+    for (layer, shades) in diagram.layers().iter().zip(diagram.shades(trace)) {
+        for (node, (r, g, b)) in layer.iter().zip(shades) {
+            builder.circle(
+                DrawMode::fill(),
+                [node.x, node.y],
+                diagram.radius(),
+                0.1,
+                Color::from_rgb(r, g, b),
+            )?;
+        }
+    }
+
+    let mesh = builder.build(ctx)?;
+    graphics::draw(ctx, &mesh, graphics::DrawParam::default().dest(origin))
+}
+
+/// Work out where the min, avg and max fitness of each generation go on the chart
+/// Generations are spread evenly across the chart from left to right, and the fitnesses are scaled to fill it from bottom to top
+fn chart_lines(history: &[Statistics], area: Rect) -> [Vec<[f32; 2]>; 3] {
+    let low = history
+        .iter()
+        .map(Statistics::min_fitness)
+        .fold(f32::INFINITY, f32::min);
+    let high = history
+        .iter()
+        .map(Statistics::max_fitness)
+        .fold(f32::NEG_INFINITY, f32::max);
+    let range = match high > low {
+        true => high - low,
+        false => 1.0,
+    };
+    let gap = area.w / (history.len().max(2) - 1) as f32;
+
+    let line = |fitness: fn(&Statistics) -> f32| {
+        history
+            .iter()
+            .enumerate()
+            .map(|(idx, stats)| {
+                [
+                    area.x + gap * idx as f32,
+                    area.y + area.h - area.h * (fitness(stats) - low) / range,
+                ]
+            })
+            .collect()
+    };
+
+    [
+        line(Statistics::min_fitness),
+        line(Statistics::avg_fitness),
+        line(Statistics::max_fitness),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use genetic_algorithm::{
+        chromosome::Chromosome, crossover::UniformCrossover, individual::Individual,
+        mutation::GaussianMutation, selection::RouletteWheelSelection, GeneticAlgorithm,
+    };
+    use rand::SeedableRng;
+
+    use super::*;
+
+    /// Just enough of an individual to make Statistics from
+    struct Fit(f32, Chromosome);
+
+    impl Individual for Fit {
+        fn create(chromosome: Chromosome) -> Self {
+            Self(0.0, chromosome)
+        }
+
+        fn chromosome(&self) -> &Chromosome {
+            &self.1
+        }
+
+        fn fitness(&self) -> f32 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn chart() {
+        // Statistics only come out of evolving a population
+        let ga = GeneticAlgorithm::new(
+            RouletteWheelSelection::new(),
+            UniformCrossover::new(),
+            GaussianMutation::new(0.0, 0.0),
+        );
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+        let mut generation = |fitnesses: &[f32]| {
+            let population: Vec<Fit> = fitnesses
+                .iter()
+                .map(|&f| Fit(f, std::iter::empty().collect()))
+                .collect();
+
+            ga.evolve(&mut prng, &population).1
+        };
+        let history = vec![generation(&[0.0, 1.0, 2.0]), generation(&[2.0, 3.0, 4.0])];
+
+        let [min, avg, max] = chart_lines(&history, Rect::new(0.0, 0.0, 100.0, 40.0));
+        assert_eq!(min, vec![[0.0, 40.0], [100.0, 20.0]]);
+        assert_eq!(avg, vec![[0.0, 30.0], [100.0, 10.0]]);
+        assert_eq!(max, vec![[0.0, 20.0], [100.0, 0.0]]);
+    }
+}
@@ -0,0 +1,300 @@
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use directories::ProjectDirs;
+use serde::Deserialize;
+
+use crate::observation::Encoder;
+use crate::players::human::player::Controls;
+use crate::settings::{
+    Config, FoodConfig, Rules, SpeedConfig, GAME_TITLE, SCREEN_HEIGHT, SCREEN_WIDTH,
+};
+
+const FILE_NAME: &str = "config.toml";
+
+/// What gets written to the config file when there isn't one yet - the same as the defaults, with a few notes on each setting
+const DEFAULT_CONFIG: &str = r#"# Snek settings - anything left out keeps its default value
+
+[display]
+width = 800.0
+height = 600.0
+vsync = true
+
+# One set of controls for each human player, handed out in order, with any number of inputs for each one
+# Keys are named like "Left", "W", "3", "Numpad8", "F1" or "Space", and gamepads like "Pad1:DPadUp" or "Pad1:LeftStickY+"
+# turn_left and turn_right turn from wherever the Snek is heading
+# Controls changed on the Controls screen in the game are saved separately, and take the place of these
+[[controls]]
+left = ["Left", "Pad1:DPadLeft", "Pad1:LeftStickX-"]
+right = ["Right", "Pad1:DPadRight", "Pad1:LeftStickX+"]
+up = ["Up", "Pad1:DPadUp", "Pad1:LeftStickY+"]
+down = ["Down", "Pad1:DPadDown", "Pad1:LeftStickY-"]
+turn_left = []
+turn_right = []
+
+[[controls]]
+left = ["A", "Pad2:DPadLeft", "Pad2:LeftStickX-"]
+right = ["D", "Pad2:DPadRight", "Pad2:LeftStickX+"]
+up = ["W", "Pad2:DPadUp", "Pad2:LeftStickY+"]
+down = ["S", "Pad2:DPadDown", "Pad2:LeftStickY-"]
+turn_left = []
+turn_right = []
+
+[[controls]]
+left = ["J", "Pad3:DPadLeft", "Pad3:LeftStickX-"]
+right = ["L", "Pad3:DPadRight", "Pad3:LeftStickX+"]
+up = ["I", "Pad3:DPadUp", "Pad3:LeftStickY+"]
+down = ["K", "Pad3:DPadDown", "Pad3:LeftStickY-"]
+turn_left = []
+turn_right = []
+
+# Levels bring their own [food] and [speed] tables, which take the place of these
+[food]
+max_items = 3
+special_chance = 0.05
+bonus_weight = 4
+poison_weight = 3
+speed_weight = 2
+golden_weight = 1
+golden_lifetime = 25
+boost_length = 20
+
+[speed]
+base_rate = 5
+ramp_every = 5
+max_rate = 20
+
+# How the AIs' brains are laid out, and how they're evolved
+[ai]
+brain_neurons = 15
+generation_length = 10
+population_size = 100
+"#;
+
+/// Everything that can be set in the config file
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameConfig {
+    pub display: DisplayConfig,
+    pub controls: Vec<Controls>,
+    pub food: FoodConfig,
+    pub speed: SpeedConfig,
+    pub ai: AiConfig,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        Self {
+            display: DisplayConfig::default(),
+            controls: Controls::presets(),
+            food: FoodConfig::default(),
+            speed: SpeedConfig::default(),
+            ai: AiConfig::default(),
+        }
+    }
+}
+
+/// The game window
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DisplayConfig {
+    pub width: f32,
+    pub height: f32,
+    pub vsync: bool,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        Self {
+            width: SCREEN_WIDTH,
+            height: SCREEN_HEIGHT,
+            vsync: true,
+        }
+    }
+}
+
+/// The settings for the AIs that don't depend on how they see the game
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AiConfig {
+    pub brain_neurons: usize,
+    pub generation_length: usize,
+    pub population_size: usize,
+}
+
+impl Default for AiConfig {
+    fn default() -> Self {
+        let config = Config::default();
+
+        Self {
+            brain_neurons: config.brain_neurons,
+            generation_length: config.generation_length,
+            population_size: config.population_size,
+        }
+    }
+}
+
+impl AiConfig {
+    /// Create a Config for AIs that see the game through the specified encoder
+    pub fn for_encoder(&self, encoder: &dyn Encoder) -> Config {
+        Config {
+            brain_neurons: self.brain_neurons,
+            generation_length: self.generation_length,
+            population_size: self.population_size,
+            ..Config::for_encoder(encoder)
+        }
+    }
+}
+
+impl GameConfig {
+    /// Where the config file lives, following the conventions of the current platform
+    pub fn default_path() -> Option<PathBuf> {
+        ProjectDirs::from("com", "rufus-stone", GAME_TITLE)
+            .map(|dirs| dirs.config_dir().join(FILE_NAME))
+    }
+
+    /// Load the config from the default path, creating it first if it's missing
+    /// If the platform has nowhere to put it, the defaults are used instead
+    pub fn load_default() -> std::io::Result<Self> {
+        match Self::default_path() {
+            Some(path) => Self::load_or_create(path),
+            None => {
+                log::warn!("Nowhere to keep the config file - using the default settings!");
+                Ok(Self::default())
+            }
+        }
+    }
+
+    /// Load the config from the specified file, creating it first if it's missing
+    pub fn load_or_create(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let path = path.as_ref();
+
+        if !path.exists() {
+            log::warn!(
+                "No config file found... Creating one at: {}",
+                path.display()
+            );
+
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            std::fs::File::create(path)?.write_all(DEFAULT_CONFIG.as_bytes())?;
+        }
+
+        Self::load(path)
+    }
+
+    /// Load the config from the specified file
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let path = path.as_ref();
+
+        Self::parse(&std::fs::read_to_string(path)?)
+            .map_err(|e| std::io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+    }
+
+    /// Parse the config from a TOML string, checking every setting makes sense
+    pub fn parse(contents: &str) -> std::io::Result<Self> {
+        let invalid = |msg: String| std::io::Error::new(ErrorKind::InvalidData, msg);
+
+        let config: GameConfig = toml::from_str(contents).map_err(|e| invalid(e.to_string()))?;
+
+        // The rules are checked the same way as a rules file
+        config.rules().check().map_err(invalid)?;
+
+        if config.display.width <= 0.0 || config.display.height <= 0.0 {
+            return Err(invalid(
+                "the window must have some width and height".to_owned(),
+            ));
+        }
+        if config.controls.is_empty() {
+            return Err(invalid(
+                "there must be at least one set of controls".to_owned(),
+            ));
+        }
+        if config.ai.brain_neurons == 0 || config.ai.population_size == 0 {
+            return Err(invalid(
+                "the AIs need some neurons, and some of them to evolve".to_owned(),
+            ));
+        }
+
+        Ok(config)
+    }
+
+    /// Get the rules of the game
+    pub fn rules(&self) -> Rules {
+        Rules {
+            food: self.food.clone(),
+            speed: self.speed.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ggez::input::keyboard::KeyCode;
+
+    use crate::observation::Rays;
+    use crate::players::human::{input::Input, player::Control};
+
+    use super::*;
+
+    #[test]
+    fn default_config_file() {
+        // The file written for new players says the same as the defaults
+        let config = GameConfig::parse(DEFAULT_CONFIG).unwrap();
+        let default = GameConfig::default();
+
+        assert_eq!(config.display.width, default.display.width);
+        assert_eq!(config.display.height, default.display.height);
+        assert_eq!(config.controls, default.controls);
+        assert_eq!(config.food.special_chance, default.food.special_chance);
+        assert_eq!(config.speed.max_rate, default.speed.max_rate);
+        assert_eq!(config.ai.population_size, default.ai.population_size);
+    }
+
+    #[test]
+    fn parse_config() {
+        let config = GameConfig::parse(
+            "[display]\nwidth = 1024.0\n\n[[controls]]\nleft = \"Numpad4\"\nright = \"Numpad6\"\nup = \"Numpad8\"\ndown = \"Numpad2\"\n\n[ai]\nbrain_neurons = 20\n",
+        )
+        .unwrap();
+
+        assert_eq!(config.display.width, 1024.0);
+        assert_eq!(config.display.height, SCREEN_HEIGHT);
+        assert_eq!(config.controls.len(), 1);
+        assert_eq!(
+            config.controls[0].bindings(Control::Up),
+            &[Input::Key(KeyCode::Numpad8)]
+        );
+        assert_eq!(config.ai.for_encoder(&Rays).brain_neurons, 20);
+        assert_eq!(config.ai.for_encoder(&Rays).eye_photoreceptors, Rays.size());
+
+        assert!(GameConfig::parse("").is_ok());
+        assert!(GameConfig::parse("[display]\nwidth = 0.0\n").is_err());
+        assert!(GameConfig::parse("[food]\nspecial_chance = 2.0\n").is_err());
+        assert!(GameConfig::parse("[ai]\npopulation_size = 0\n").is_err());
+        assert!(GameConfig::parse("controls = []\n").is_err());
+        assert!(GameConfig::parse("[colours]\nsnek = \"green\"\n").is_err());
+        assert!(GameConfig::parse(
+            "[[controls]]\nleft = \"Banana\"\nright = \"D\"\nup = \"W\"\ndown = \"S\"\n"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn config_file_created_if_missing() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join(FILE_NAME);
+
+        let config = GameConfig::load_or_create(&path).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), DEFAULT_CONFIG);
+        assert_eq!(config.controls.len(), 3);
+
+        // An existing file is left alone
+        std::fs::write(&path, "[speed]\nbase_rate = 12\n").unwrap();
+        assert_eq!(
+            GameConfig::load_or_create(&path).unwrap().speed.base_rate,
+            12
+        );
+    }
+}
@@ -1,42 +1,182 @@
 use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
 
+use crate::settings::FoodConfig;
 use crate::types::Vec2;
 
+use super::grid::Grid;
 use super::snek::Snek;
 
-#[derive(Debug, PartialEq)]
-pub struct Food(Vec2);
+/// The different kinds of Food, each with their own effect on the Snek that eats them
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FoodKind {
+    /// Plain old Food - there's always one of these somewhere on the Grid
+    Normal,
+    /// Worth a lot more points than Normal Food
+    Bonus,
+    /// Shrinks the Snek and costs points
+    Poison,
+    /// Speeds the whole game up for a while
+    Speed,
+    /// Worth the most points of all, but only sticks around for a limited time
+    Golden,
+}
+
+impl FoodKind {
+    /// Every kind of Food other than Normal
+    pub const SPECIALS: [FoodKind; 4] = [
+        FoodKind::Bonus,
+        FoodKind::Poison,
+        FoodKind::Speed,
+        FoodKind::Golden,
+    ];
+
+    /// How many parts the Snek grows by (or shrinks by, if negative) when it eats this kind of Food
+    pub fn growth(&self) -> i32 {
+        match self {
+            FoodKind::Poison => -2,
+            _ => 1,
+        }
+    }
+
+    /// How many points the Snek gains (or loses, if negative) when it eats this kind of Food
+    pub fn points(&self) -> i32 {
+        match self {
+            FoodKind::Normal => 1,
+            FoodKind::Bonus => 5,
+            FoodKind::Poison => -3,
+            FoodKind::Speed => 2,
+            FoodKind::Golden => 10,
+        }
+    }
+
+    /// The character used for this kind of Food when printing the game
+    pub fn symbol(&self) -> char {
+        match self {
+            FoodKind::Normal => '%',
+            FoodKind::Bonus => '$',
+            FoodKind::Poison => 'x',
+            FoodKind::Speed => '>',
+            FoodKind::Golden => '*',
+        }
+    }
+
+    /// Find the kind of Food drawn with the specified character, if there is one
+    pub fn from_symbol(symbol: char) -> Option<FoodKind> {
+        std::iter::once(FoodKind::Normal)
+            .chain(FoodKind::SPECIALS.iter().copied())
+            .find(|kind| kind.symbol() == symbol)
+    }
+
+    /// Pick one of the special kinds of Food, using the weights from the specified FoodConfig
+    pub fn random_special(config: &FoodConfig, prng: &mut dyn RngCore) -> Option<FoodKind> {
+        let weights = [
+            config.bonus_weight,
+            config.poison_weight,
+            config.speed_weight,
+            config.golden_weight,
+        ];
+
+        let total: u32 = weights.iter().sum();
+        if total == 0 {
+            return None;
+        }
+
+        // Walk along the weights until we pass the randomly chosen point
+        let mut roll = prng.gen_range(0..total);
+        for (kind, weight) in FoodKind::SPECIALS.iter().zip(weights.iter()) {
+            if roll < *weight {
+                return Some(*kind);
+            }
+            roll -= weight;
+        }
+
+        None
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Food {
+    pos: Vec2,
+    kind: FoodKind,
+    /// How many more ticks the Food will stay on the Grid, if it doesn't last forever
+    ttl: Option<u32>,
+}
 
 impl Food {
-    /// Create a new Food at the specified location
+    /// Create a new Normal Food at the specified location
     pub fn new(pos: Vec2) -> Self {
-        Self(pos)
+        Self::with_kind(pos, FoodKind::Normal)
     }
 
-    /// Create a new Food at a random location, but not on top of the Snek
-    pub fn random(bounds: &Vec2, snek: &Snek, prng: &mut dyn RngCore) -> Self {
-        let mut x = prng.gen_range(0..bounds.x);
-        let mut y = prng.gen_range(0..bounds.y);
+    /// Create a new Food of the specified kind at the specified location
+    pub fn with_kind(pos: Vec2, kind: FoodKind) -> Self {
+        Self {
+            pos,
+            kind,
+            ttl: None,
+        }
+    }
 
-        let mut proposed_food = Vec2::new(x, y);
+    /// Make the Food vanish after the specified number of ticks
+    pub fn expiring(mut self, ttl: u32) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Create a new Food of the specified kind at a random location, but not on top of a wall, any living Snek, or any other Food
+    /// Every free square is equally likely, and if there aren't any left then there's nowhere to put the Food!
+    pub fn random(
+        kind: FoodKind,
+        grid: &Grid,
+        sneks: &[Snek],
+        others: &[Food],
+        prng: &mut dyn RngCore,
+    ) -> Option<Self> {
+        // Combine the walls with the squares covered by every living Snek and every other Food
+        let mut occupied = grid.walls();
+        for snek in sneks.iter().filter(|snek| snek.is_alive()) {
+            occupied.union_with(snek.occupied());
+        }
+        for food in others {
+            occupied.insert(food.pos());
+        }
 
-        while snek.touches(&proposed_food) {
-            log::warn!(
-                "proposed_food {:?} would be inside the Snek! Try again...",
-                &proposed_food
-            );
-            x = prng.gen_range(0..bounds.x);
-            y = prng.gen_range(0..bounds.y);
-            proposed_food = Vec2::new(x, y);
+        if occupied.free() == 0 {
+            log::warn!("No free squares left for the Food!");
+            return None;
         }
 
-        log::warn!("New random Food: {:?}", &proposed_food);
+        let n = prng.gen_range(0..occupied.free());
+        let pos = occupied.nth_free(n)?;
 
-        Self(proposed_food)
+        log::warn!("New random {:?} Food: {:?}", kind, &pos);
+
+        Some(Self::with_kind(pos, kind))
     }
 
     pub fn pos(&self) -> &Vec2 {
-        &self.0
+        &self.pos
+    }
+
+    pub fn kind(&self) -> FoodKind {
+        self.kind
+    }
+
+    pub fn ttl(&self) -> Option<u32> {
+        self.ttl
+    }
+
+    /// Count down another tick, returning false once the Food has expired
+    pub fn age(&mut self) -> bool {
+        match &mut self.ttl {
+            Some(0) => false,
+            Some(ttl) => {
+                *ttl -= 1;
+                *ttl > 0
+            }
+            None => true,
+        }
     }
 }
 
@@ -46,6 +186,7 @@ mod tests {
     use rand_chacha::ChaCha8Rng;
 
     use crate::entities::food::Food;
+    use crate::types::Direction;
 
     use super::*;
 
@@ -53,17 +194,95 @@ mod tests {
     fn new_food() {
         let food = Food::new(Vec2::new(0, 0));
 
-        assert_eq!(food.0, Vec2::new(0, 0));
+        assert_eq!(food.pos, Vec2::new(0, 0));
+        assert_eq!(food.kind(), FoodKind::Normal);
     }
 
     #[test]
     fn random_food() {
         let mut prng = ChaCha8Rng::from_seed(Default::default());
 
-        let bounds = Vec2::new(10, 10);
+        let grid = Grid::default();
         let snek = Snek::default();
-        let food = Food::random(&bounds, &snek, &mut prng);
+        let food = Food::random(FoodKind::Normal, &grid, &[snek], &[], &mut prng).unwrap();
+
+        assert_eq!(food.pos, Vec2::new(3, 6));
+    }
+
+    #[test]
+    fn no_room_for_food() {
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+
+        // A Snek that fills the whole Grid leaves nowhere for the Food to go
+        let grid = Grid::new(2, 2).unwrap();
+        let bounds = *grid.bounds();
+        let parts = vec![
+            Vec2::new(0, 0),
+            Vec2::new(1, 0),
+            Vec2::new(1, 1),
+            Vec2::new(0, 1),
+        ];
+        let snek = Snek::from_parts(parts.into(), &bounds, Direction::Up, true);
+        let sneks = std::slice::from_ref(&snek);
+        assert!(Food::random(FoodKind::Normal, &grid, sneks, &[], &mut prng).is_none());
+
+        // But dead Sneks don't count
+        let mut snek = snek;
+        snek.kill();
+        assert!(Food::random(FoodKind::Normal, &grid, &[snek], &[], &mut prng).is_some());
+
+        // Nor can Food go on top of other Food
+        let others = vec![
+            Food::new(Vec2::new(0, 0)),
+            Food::new(Vec2::new(1, 0)),
+            Food::new(Vec2::new(1, 1)),
+        ];
+        let food = Food::random(FoodKind::Bonus, &grid, &[], &others, &mut prng).unwrap();
+        assert_eq!(food.pos, Vec2::new(0, 1));
+
+        // Or on top of a wall
+        let mut grid = grid;
+        grid.add_wall(&Vec2::new(0, 1));
+        assert!(Food::random(FoodKind::Bonus, &grid, &[], &others, &mut prng).is_none());
+    }
+
+    #[test]
+    fn golden_food_expires() {
+        let mut food = Food::with_kind(Vec2::new(0, 0), FoodKind::Golden).expiring(2);
+
+        assert!(food.age());
+        assert_eq!(food.ttl(), Some(1));
+        assert!(!food.age());
+
+        let mut food = Food::new(Vec2::new(0, 0));
+        assert!(food.age());
+        assert_eq!(food.ttl(), None);
+    }
+
+    #[test]
+    fn special_food_weights() {
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+
+        // Only kinds with a weight can turn up
+        let config = FoodConfig {
+            bonus_weight: 0,
+            poison_weight: 1,
+            speed_weight: 0,
+            golden_weight: 1,
+            ..FoodConfig::default()
+        };
+        for _ in 0..50 {
+            let kind = FoodKind::random_special(&config, &mut prng).unwrap();
+            assert!(kind == FoodKind::Poison || kind == FoodKind::Golden);
+        }
 
-        assert_eq!(food.0, Vec2::new(8, 9));
+        let config = FoodConfig {
+            bonus_weight: 0,
+            poison_weight: 0,
+            speed_weight: 0,
+            golden_weight: 0,
+            ..FoodConfig::default()
+        };
+        assert_eq!(FoodKind::random_special(&config, &mut prng), None);
     }
 }
@@ -1,59 +1,135 @@
-use crate::types::Vec2;
+use std::convert::TryFrom;
 
-#[derive(Debug)]
-pub struct Grid(Vec2);
+use serde::{Deserialize, Serialize};
+
+use crate::types::{Direction, Vec2};
+
+use super::occupancy::Occupancy;
+use super::topology::{Topology, TopologyKind};
+
+#[derive(Debug, Clone, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "SavedGrid", into = "SavedGrid")]
+pub struct Grid {
+    bounds: Vec2,
+    /// The squares that nothing can move into - most Grids don't have any, so this is only created when the first wall is added
+    walls: Option<Occupancy>,
+    /// How the squares are joined together - square, unless asked otherwise
+    topology: TopologyKind,
+}
+
+/// The way a Grid is saved - just its size, topology, and a list of the walls, rather than the bitset they're kept in
+#[derive(Serialize, Deserialize)]
+struct SavedGrid {
+    width: usize,
+    height: usize,
+    #[serde(default)]
+    walls: Vec<Vec2>,
+    #[serde(default)]
+    topology: TopologyKind,
+}
+
+impl TryFrom<SavedGrid> for Grid {
+    type Error = String;
+
+    fn try_from(saved: SavedGrid) -> Result<Self, Self::Error> {
+        let mut grid = Grid::new(saved.width, saved.height)
+            .ok_or_else(|| format!("can't have a {}x{} Grid", saved.width, saved.height))?
+            .with_topology(saved.topology);
+
+        for wall in &saved.walls {
+            if !grid.contains(wall) {
+                return Err(format!("the wall at {:?} is off the Grid", wall));
+            }
+            grid.add_wall(wall);
+        }
+
+        Ok(grid)
+    }
+}
+
+impl From<Grid> for SavedGrid {
+    fn from(grid: Grid) -> Self {
+        Self {
+            width: grid.width(),
+            height: grid.height(),
+            walls: grid.into_iter().filter(|pos| grid.is_wall(pos)).collect(),
+            topology: grid.topology,
+        }
+    }
+}
 
 impl Grid {
     /// Create a new Grid with the specified width and height
     /// This must have a width and height of at least 1 - no empty or 1-dimensional grids allowed!
+    /// The width and height must also fit in an i32, and the area must fit in a usize
     pub fn new(width: usize, height: usize) -> Option<Self> {
         if width == 0 || height == 0 {
-            None
-        } else {
-            log::warn!("New Grid of width: {} / height: {}", width, height);
-            Some(Self(Vec2::new(width as i32, height as i32)))
+            return None;
         }
+
+        // Make sure every square can be described by both a Vec2 and an index
+        let x = i32::try_from(width).ok()?;
+        let y = i32::try_from(height).ok()?;
+        width.checked_mul(height)?;
+
+        log::warn!("New Grid of width: {} / height: {}", width, height);
+        Some(Self {
+            bounds: Vec2::new(x, y),
+            walls: None,
+            topology: TopologyKind::default(),
+        })
     }
 
-    /// Get an immutable ref to the internal Vec2 that describes the boundsof the Grid
+    /// Join the squares of the Grid together using the specified Topology
+    pub fn with_topology(mut self, kind: TopologyKind) -> Self {
+        self.topology = kind;
+        self
+    }
+
+    /// Get the Topology that decides how the squares of the Grid are joined together
+    pub fn topology(&self) -> &'static dyn Topology {
+        self.topology.build()
+    }
+
+    /// Get an immutable ref to the internal Vec2 that describes the bounds of the Grid
     pub fn bounds(&self) -> &Vec2 {
-        &self.0
+        &self.bounds
     }
 
     /// Get the area the Grid
     pub fn len(&self) -> usize {
-        self.0.x as usize * self.0.y as usize
+        self.bounds.x as usize * self.bounds.y as usize
     }
 
     /// This should never be true
     /// Just including to silence Clippy warning about a len() function without a corresponding is_empty() function!
     pub fn is_empty(&self) -> bool {
-        self.0 == Vec2::default()
+        self.bounds == Vec2::default()
     }
 
     /// Get the width of the Grid
     pub fn width(&self) -> usize {
-        self.0.x as usize
+        self.bounds.x as usize
     }
 
     /// Get the height of the Grid
     pub fn height(&self) -> usize {
-        self.0.y as usize
+        self.bounds.y as usize
     }
 
     /// Get the number of columns in the Grid
     pub fn columns(&self) -> usize {
-        self.0.x as usize
+        self.bounds.x as usize
     }
 
     /// Get the number of rows in the Grid
     pub fn rows(&self) -> usize {
-        self.0.y as usize
+        self.bounds.y as usize
     }
 
     /// Get a Vec2 describing the centre position in the Grid
     pub fn centre(&self) -> Vec2 {
-        Vec2::new(self.0.x / 2, self.0.y / 2)
+        Vec2::new(self.bounds.x / 2, self.bounds.y / 2)
     }
 
     /// Wrap the specified position so that it fits within the specified bounds
@@ -71,34 +147,113 @@ impl Grid {
         Vec2::new(x, y)
     }
 
-    /// Get the
+    /// Turn the specified position into a wall, returning false if it was already a wall or lies outside the Grid
+    pub fn add_wall(&mut self, xy: &Vec2) -> bool {
+        if !self.contains(xy) {
+            return false;
+        }
+
+        let bounds = self.bounds;
+        self.walls
+            .get_or_insert_with(|| Occupancy::new(&bounds))
+            .insert(xy)
+    }
+
+    /// Check if the specified position is a wall
+    pub fn is_wall(&self, xy: &Vec2) -> bool {
+        matches!(&self.walls, Some(walls) if walls.contains(xy))
+    }
+
+    /// Get an Occupancy map with every wall already filled in
+    pub fn walls(&self) -> Occupancy {
+        match &self.walls {
+            Some(walls) => walls.clone(),
+            None => Occupancy::new(&self.bounds),
+        }
+    }
+
+    /// Get the number of walls in the Grid
+    pub fn wall_count(&self) -> usize {
+        self.walls.as_ref().map_or(0, |walls| walls.count())
+    }
+
+    /// Check if the specified position lies within the Grid
+    pub fn contains(&self, xy: &Vec2) -> bool {
+        (0..self.bounds.x).contains(&xy.x) && (0..self.bounds.y).contains(&xy.y)
+    }
+
+    /// Get the position of the square at the specified index, counting along each row in turn
     pub fn xy_at_index(&self, idx: usize) -> Option<Vec2> {
         // Ignore index values that are outside the grid
-        if idx >= self.width() * self.height() {
+        if idx >= self.len() {
             None
         } else {
             let x = idx % self.width();
-            let y = idx / self.height();
+            let y = idx / self.width();
 
             Some(Vec2::new(x as i32, y as i32))
         }
     }
 
+    /// Get the index of the square at the specified position
     pub fn index_at_xy(&self, xy: Vec2) -> Option<usize> {
-        // Ignore x and y positions that are outside the grid
-        if xy.x >= self.width() as i32 || xy.y >= self.height() as i32 {
+        // Ignore x and y positions that are outside the grid, including negative ones
+        if !self.contains(&xy) {
             None
         } else {
-            let idx = (xy.x + (self.width() as i32 * xy.y)) as usize;
+            let idx = xy.x as usize + (self.width() * xy.y as usize);
             Some(idx)
         }
     }
+
+    /// Get the square reached by taking one step from the specified position in the specified Direction, wrapping around the edges of the Grid
+    pub fn step(&self, xy: &Vec2, dir: Direction) -> Vec2 {
+        self.topology().step(&self.bounds, xy, dir)
+    }
+
+    /// Get every square next to the specified position, in the same order as the Topology's directions, wrapping around the edges of the Grid
+    pub fn neighbours<'a>(&'a self, xy: &'a Vec2) -> impl Iterator<Item = Vec2> + 'a {
+        let topology = self.topology();
+        topology
+            .directions()
+            .iter()
+            .map(move |&dir| topology.step(&self.bounds, xy, dir))
+    }
+
+    /// Get the number of moves needed to get from a to b, taking the shortest route around the edges of the Grid
+    pub fn distance(&self, a: &Vec2, b: &Vec2) -> usize {
+        self.topology().distance(&self.bounds, a, b)
+    }
+
+    /// Get an iterator over every square in the specified row, from left to right
+    pub fn row(&self, y: usize) -> Option<impl Iterator<Item = Vec2>> {
+        if y >= self.rows() {
+            None
+        } else {
+            let y = y as i32;
+            Some((0..self.bounds.x).map(move |x| Vec2::new(x, y)))
+        }
+    }
+
+    /// Get an iterator over every square in the specified column, from bottom to top
+    pub fn column(&self, x: usize) -> Option<impl Iterator<Item = Vec2>> {
+        if x >= self.columns() {
+            None
+        } else {
+            let x = x as i32;
+            Some((0..self.bounds.y).map(move |y| Vec2::new(x, y)))
+        }
+    }
 }
 
 impl Default for Grid {
     fn default() -> Self {
         log::warn!("New default Grid");
-        Self(Vec2::new(10, 10))
+        Self {
+            bounds: Vec2::new(10, 10),
+            walls: None,
+            topology: TopologyKind::default(),
+        }
     }
 }
 
@@ -111,7 +266,7 @@ impl<'a> Iterator for GridIter<'a> {
     type Item = Vec2;
 
     fn next(&mut self) -> Option<Self::Item> {
-        match self.pos < self.grid.len() as usize {
+        match self.pos < self.grid.len() {
             true => {
                 let xy = self.grid.xy_at_index(self.pos).unwrap();
                 self.pos += 1;
@@ -127,15 +282,16 @@ impl<'a> IntoIterator for &'a Grid {
     type IntoIter = GridIter<'a>;
 
     fn into_iter(self) -> Self::IntoIter {
-        GridIter {
-            grid: &self,
-            pos: 0,
-        }
+        GridIter { grid: self, pos: 0 }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashSet;
+
+    use proptest::prelude::*;
+
     use super::*;
 
     #[test]
@@ -160,6 +316,108 @@ mod tests {
         assert_eq!(grid.xy_at_index(26), None);
     }
 
+    #[test]
+    fn non_square_grid() {
+        // Create a new 4x3 Grid
+        let grid = Grid::new(4, 3).unwrap();
+
+        assert_eq!(grid.xy_at_index(3), Some(Vec2::new(3, 0)));
+        assert_eq!(grid.xy_at_index(4), Some(Vec2::new(0, 1)));
+        assert_eq!(grid.xy_at_index(11), Some(Vec2::new(3, 2)));
+        assert_eq!(grid.xy_at_index(12), None);
+
+        assert_eq!(grid.index_at_xy(Vec2::new(3, 2)), Some(11));
+        assert_eq!(grid.index_at_xy(Vec2::new(-1, 0)), None);
+        assert_eq!(grid.index_at_xy(Vec2::new(0, -1)), None);
+        assert_eq!(grid.index_at_xy(Vec2::new(4, 0)), None);
+        assert_eq!(grid.index_at_xy(Vec2::new(0, 3)), None);
+    }
+
+    #[test]
+    fn bad_grid_sizes() {
+        assert_eq!(Grid::new(0, 5), None);
+        assert_eq!(Grid::new(5, 0), None);
+        assert_eq!(Grid::new(i32::MAX as usize + 1, 1), None);
+        assert_eq!(
+            Grid::new(i32::MAX as usize, i32::MAX as usize).is_some(),
+            usize::BITS > 32
+        );
+    }
+
+    #[test]
+    fn neighbours_and_distance() {
+        let grid = Grid::new(6, 4).unwrap();
+
+        let corner = Vec2::new(0, 0);
+        let neighbours: Vec<Vec2> = grid.neighbours(&corner).collect();
+        assert_eq!(neighbours.len(), 4);
+        assert!(neighbours.contains(&Vec2::new(0, 1)));
+        assert!(neighbours.contains(&Vec2::new(0, 3)));
+        assert!(neighbours.contains(&Vec2::new(5, 0)));
+        assert!(neighbours.contains(&Vec2::new(1, 0)));
+
+        assert_eq!(grid.distance(&corner, &corner), 0);
+        assert_eq!(grid.distance(&corner, &Vec2::new(5, 3)), 2);
+        assert_eq!(grid.distance(&corner, &Vec2::new(3, 2)), 5);
+        assert_eq!(grid.distance(&Vec2::new(1, 1), &Vec2::new(4, 1)), 3);
+
+        // Heading diagonally cuts the corners off
+        let grid = grid.with_topology(TopologyKind::Diagonal);
+        assert_eq!(grid.neighbours(&corner).count(), 8);
+        assert!(grid.neighbours(&corner).any(|xy| xy == Vec2::new(5, 3)));
+        assert_eq!(grid.distance(&corner, &Vec2::new(5, 3)), 1);
+        assert_eq!(grid.distance(&corner, &Vec2::new(3, 2)), 3);
+
+        // Hexagons only have two of the four corners as neighbours
+        let grid = grid.with_topology(TopologyKind::Hex);
+        assert_eq!(grid.neighbours(&corner).count(), 6);
+        assert!(grid.neighbours(&corner).any(|xy| xy == Vec2::new(5, 1)));
+        assert!(!grid.neighbours(&corner).any(|xy| xy == Vec2::new(1, 1)));
+        assert_eq!(grid.distance(&corner, &Vec2::new(1, 1)), 2);
+        assert_eq!(grid.step(&corner, Direction::DownRight), Vec2::new(1, 3));
+    }
+
+    #[test]
+    fn saved_topology() {
+        let grid = Grid::new(5, 4).unwrap().with_topology(TopologyKind::Hex);
+        let json = serde_json::to_string(&grid).unwrap();
+        assert!(json.contains(r#""topology":"hex""#));
+        assert_eq!(serde_json::from_str::<Grid>(&json).unwrap(), grid);
+
+        // Grids saved before there was a choice are square
+        let old: Grid = serde_json::from_str(r#"{ "width": 5, "height": 4 }"#).unwrap();
+        assert_eq!(old.topology().kind(), TopologyKind::Square);
+    }
+
+    #[test]
+    fn rows_and_columns() {
+        let grid = Grid::new(3, 2).unwrap();
+
+        let row: Vec<Vec2> = grid.row(1).unwrap().collect();
+        assert_eq!(row, vec![Vec2::new(0, 1), Vec2::new(1, 1), Vec2::new(2, 1)]);
+
+        let column: Vec<Vec2> = grid.column(2).unwrap().collect();
+        assert_eq!(column, vec![Vec2::new(2, 0), Vec2::new(2, 1)]);
+
+        assert!(grid.row(2).is_none());
+        assert!(grid.column(3).is_none());
+    }
+
+    #[test]
+    fn walls() {
+        let mut grid = Grid::new(4, 3).unwrap();
+        assert_eq!(grid.wall_count(), 0);
+        assert!(!grid.is_wall(&Vec2::new(1, 1)));
+
+        assert!(grid.add_wall(&Vec2::new(1, 1)));
+        assert!(!grid.add_wall(&Vec2::new(1, 1)));
+        assert!(!grid.add_wall(&Vec2::new(4, 1)));
+
+        assert!(grid.is_wall(&Vec2::new(1, 1)));
+        assert_eq!(grid.wall_count(), 1);
+        assert_eq!(grid.walls().free(), 11);
+    }
+
     #[test]
     fn wrap_test() {
         let bounds = Vec2::new(5, 5);
@@ -189,4 +447,96 @@ mod tests {
             log::warn!("{:?}", pos);
         }
     }
+
+    /// Grids of any shape and topology, up to a reasonable size
+    fn any_grid() -> impl Strategy<Value = Grid> {
+        (1usize..40, 1usize..40, 0usize..TopologyKind::ALL.len()).prop_map(
+            |(width, height, kind)| {
+                Grid::new(width, height)
+                    .unwrap()
+                    .with_topology(TopologyKind::ALL[kind])
+            },
+        )
+    }
+
+    /// A Grid along with a position that may or may not lie inside it
+    fn grid_and_pos() -> impl Strategy<Value = (Grid, Vec2)> {
+        any_grid().prop_flat_map(|grid| {
+            let (w, h) = (grid.bounds().x, grid.bounds().y);
+            (Just(grid), (-2 * w..3 * w, -2 * h..3 * h))
+                .prop_map(|(grid, (x, y))| (grid, Vec2::new(x, y)))
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn index_round_trip(grid in any_grid(), idx in 0usize..2000) {
+            match grid.xy_at_index(idx) {
+                Some(xy) => {
+                    prop_assert!(grid.contains(&xy));
+                    prop_assert_eq!(grid.index_at_xy(xy), Some(idx));
+                }
+                None => prop_assert!(idx >= grid.len()),
+            }
+        }
+
+        #[test]
+        fn xy_round_trip((grid, xy) in grid_and_pos()) {
+            match grid.index_at_xy(xy) {
+                Some(idx) => {
+                    prop_assert!(idx < grid.len());
+                    prop_assert_eq!(grid.xy_at_index(idx), Some(xy));
+                }
+                None => prop_assert!(!grid.contains(&xy)),
+            }
+        }
+
+        #[test]
+        fn iterator_visits_every_square_once(grid in any_grid()) {
+            let squares: HashSet<Vec2> = grid.into_iter().collect();
+
+            prop_assert_eq!(grid.into_iter().count(), grid.len());
+            prop_assert_eq!(squares.len(), grid.len());
+            prop_assert!(squares.iter().all(|xy| grid.contains(xy)));
+        }
+
+        #[test]
+        fn rows_and_columns_cover_grid(grid in any_grid()) {
+            let by_rows: Vec<Vec2> = (0..grid.rows()).flat_map(|y| grid.row(y).unwrap()).collect();
+            let by_iter: Vec<Vec2> = grid.into_iter().collect();
+            prop_assert_eq!(by_rows, by_iter);
+
+            let by_columns: HashSet<Vec2> = (0..grid.columns()).flat_map(|x| grid.column(x).unwrap()).collect();
+            prop_assert_eq!(by_columns.len(), grid.len());
+        }
+
+        #[test]
+        fn wrap_stays_inside((grid, xy) in grid_and_pos()) {
+            let wrapped = Grid::wrap(grid.bounds(), &xy);
+
+            prop_assert!(grid.contains(&wrapped));
+            prop_assert_eq!(grid.distance(&xy, &wrapped), 0);
+            if grid.contains(&xy) {
+                prop_assert_eq!(wrapped, xy);
+            }
+        }
+
+        #[test]
+        fn neighbours_are_one_step_away((grid, xy) in grid_and_pos()) {
+            for neighbour in grid.neighbours(&xy) {
+                prop_assert!(grid.contains(&neighbour));
+                prop_assert!(grid.distance(&xy, &neighbour) <= 1);
+            }
+        }
+
+        #[test]
+        fn distance_is_a_metric((grid, a) in grid_and_pos(), bx in -50i32..50, by in -50i32..50, cx in -50i32..50, cy in -50i32..50) {
+            let b = Vec2::new(bx, by);
+            let c = Vec2::new(cx, cy);
+
+            prop_assert_eq!(grid.distance(&a, &b), grid.distance(&b, &a));
+            prop_assert!(grid.distance(&a, &c) <= grid.distance(&a, &b) + grid.distance(&b, &c));
+            prop_assert!(grid.distance(&a, &b) <= grid.width() / 2 + grid.height() / 2);
+        }
+    }
 }
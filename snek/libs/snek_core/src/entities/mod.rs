@@ -1,3 +1,5 @@
 pub mod food;
 pub mod grid;
+pub mod occupancy;
 pub mod snek;
+pub mod topology;
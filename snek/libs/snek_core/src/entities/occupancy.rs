@@ -0,0 +1,201 @@
+use crate::types::Vec2;
+
+/// How many squares are tracked by each word of the bitset
+const BITS: usize = u64::BITS as usize;
+
+/// A bitset with one bit for each square of a Grid, used to check whether a square is occupied without scanning every Snek part
+#[derive(Debug, Clone, PartialEq, Hash)]
+pub struct Occupancy {
+    bounds: Vec2,
+    words: Vec<u64>,
+    count: usize,
+}
+
+impl Occupancy {
+    /// Create a new empty Occupancy map for a Grid with the specified bounds
+    pub fn new(bounds: &Vec2) -> Self {
+        let squares = bounds.x.max(0) as usize * bounds.y.max(0) as usize;
+
+        Self {
+            bounds: *bounds,
+            words: vec![0; squares.div_ceil(BITS)],
+            count: 0,
+        }
+    }
+
+    /// Get an immutable ref to the bounds of the Grid being tracked
+    pub fn bounds(&self) -> &Vec2 {
+        &self.bounds
+    }
+
+    /// Get the number of occupied squares
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Get the number of squares that are still free
+    pub fn free(&self) -> usize {
+        self.squares() - self.count
+    }
+
+    /// Check if the specified position is occupied - positions outside the Grid never are
+    pub fn contains(&self, pos: &Vec2) -> bool {
+        match self.index(pos) {
+            Some(idx) => self.words[idx / BITS] & (1 << (idx % BITS)) != 0,
+            None => false,
+        }
+    }
+
+    /// Mark the specified position as occupied, returning false if it already was
+    pub fn insert(&mut self, pos: &Vec2) -> bool {
+        let idx = self
+            .index(pos)
+            .expect("Can't occupy a square outside the Grid!");
+        let mask = 1 << (idx % BITS);
+
+        if self.words[idx / BITS] & mask != 0 {
+            return false;
+        }
+
+        self.words[idx / BITS] |= mask;
+        self.count += 1;
+        true
+    }
+
+    /// Mark the specified position as free, returning false if it already was
+    pub fn remove(&mut self, pos: &Vec2) -> bool {
+        let idx = match self.index(pos) {
+            Some(idx) => idx,
+            None => return false,
+        };
+        let mask = 1 << (idx % BITS);
+
+        if self.words[idx / BITS] & mask == 0 {
+            return false;
+        }
+
+        self.words[idx / BITS] &= !mask;
+        self.count -= 1;
+        true
+    }
+
+    /// Mark every square occupied in other as occupied here too
+    pub fn union_with(&mut self, other: &Occupancy) {
+        assert_eq!(self.bounds, other.bounds);
+
+        for (word, other) in self.words.iter_mut().zip(&other.words) {
+            *word |= other;
+        }
+
+        self.count = self
+            .words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum();
+    }
+
+    /// Find the nth free square, counting along each row in turn
+    pub fn nth_free(&self, mut n: usize) -> Option<Vec2> {
+        if n >= self.free() {
+            return None;
+        }
+
+        for (w, word) in self.words.iter().enumerate() {
+            // The last word may have more bits than there are squares left
+            let valid = (self.squares() - w * BITS).min(BITS);
+            let free = valid - (word.count_ones() as usize);
+
+            if n >= free {
+                n -= free;
+                continue;
+            }
+
+            // The answer is somewhere in this word, so check each bit in turn
+            for bit in 0..valid {
+                if word & (1 << bit) == 0 {
+                    if n == 0 {
+                        let idx = w * BITS + bit;
+                        let width = self.bounds.x as usize;
+                        return Some(Vec2::new((idx % width) as i32, (idx / width) as i32));
+                    }
+                    n -= 1;
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Get the total number of squares being tracked
+    fn squares(&self) -> usize {
+        self.bounds.x as usize * self.bounds.y as usize
+    }
+
+    /// Get the bit index for the specified position
+    fn index(&self, pos: &Vec2) -> Option<usize> {
+        if (0..self.bounds.x).contains(&pos.x) && (0..self.bounds.y).contains(&pos.y) {
+            Some(pos.x as usize + pos.y as usize * self.bounds.x as usize)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_and_remove() {
+        let mut occupancy = Occupancy::new(&Vec2::new(9, 9));
+
+        assert!(occupancy.insert(&Vec2::new(8, 8)));
+        assert!(!occupancy.insert(&Vec2::new(8, 8)));
+        assert!(occupancy.contains(&Vec2::new(8, 8)));
+        assert!(!occupancy.contains(&Vec2::new(-1, 8)));
+        assert_eq!(occupancy.count(), 1);
+        assert_eq!(occupancy.free(), 80);
+
+        assert!(occupancy.remove(&Vec2::new(8, 8)));
+        assert!(!occupancy.remove(&Vec2::new(8, 8)));
+        assert_eq!(occupancy.count(), 0);
+    }
+
+    #[test]
+    fn free_squares() {
+        let bounds = Vec2::new(10, 7);
+        let mut occupancy = Occupancy::new(&bounds);
+
+        // Fill every square but two, either side of a word boundary
+        for y in 0..bounds.y {
+            for x in 0..bounds.x {
+                occupancy.insert(&Vec2::new(x, y));
+            }
+        }
+        occupancy.remove(&Vec2::new(3, 6));
+        occupancy.remove(&Vec2::new(2, 1));
+
+        assert_eq!(occupancy.nth_free(0), Some(Vec2::new(2, 1)));
+        assert_eq!(occupancy.nth_free(1), Some(Vec2::new(3, 6)));
+        assert_eq!(occupancy.nth_free(2), None);
+
+        occupancy.insert(&Vec2::new(2, 1));
+        occupancy.insert(&Vec2::new(3, 6));
+        assert_eq!(occupancy.nth_free(0), None);
+    }
+
+    #[test]
+    fn union() {
+        let bounds = Vec2::new(4, 4);
+        let mut a = Occupancy::new(&bounds);
+        let mut b = Occupancy::new(&bounds);
+
+        a.insert(&Vec2::new(0, 0));
+        b.insert(&Vec2::new(0, 0));
+        b.insert(&Vec2::new(3, 3));
+        a.union_with(&b);
+
+        assert_eq!(a.count(), 2);
+        assert!(a.contains(&Vec2::new(3, 3)));
+    }
+}
@@ -1,41 +1,128 @@
 use std::collections::VecDeque;
+use std::convert::TryFrom;
 
-use crate::types::{direction, Vec2};
+use serde::{Deserialize, Serialize};
 
-use super::grid::Grid;
+use crate::types::{Direction, Vec2};
 
-pub const FACING_UP: Vec2 = Vec2 { x: 0, y: -1 };
-pub const FACING_DOWN: Vec2 = Vec2 { x: 0, y: 1 };
-pub const FACING_LEFT: Vec2 = Vec2 { x: -1, y: 0 };
-pub const FACING_RIGHT: Vec2 = Vec2 { x: 1, y: 0 };
+use super::food::Food;
+use super::grid::Grid;
+use super::occupancy::Occupancy;
+use super::topology::Topology;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Hash, Serialize, Deserialize)]
+#[serde(try_from = "SavedSnek", into = "SavedSnek")]
 pub struct Snek {
     parts: VecDeque<Vec2>,
-    orientation: Vec2,
+    /// Every square covered by parts, so collision checks don't need to scan the whole Snek
+    occupied: Occupancy,
+    /// How many parts are sharing a square with another part
+    overlaps: usize,
+    orientation: Direction,
+    alive: bool,
+    score: usize,
+}
+
+/// The way a Snek is saved - the squares it covers are worked out again when it's loaded, rather than trusted
+#[derive(Serialize, Deserialize)]
+struct SavedSnek {
+    /// Tail first, head last
+    parts: Vec<Vec2>,
+    /// The bounds of the Grid the Snek is on
+    bounds: Vec2,
+    orientation: Direction,
+    alive: bool,
+    score: usize,
+}
+
+impl TryFrom<SavedSnek> for Snek {
+    type Error = String;
+
+    fn try_from(saved: SavedSnek) -> Result<Self, Self::Error> {
+        if saved.parts.is_empty() {
+            return Err("a Snek needs at least a head".to_owned());
+        }
+        if let Some(part) = saved.parts.iter().find(|part| {
+            !(0..saved.bounds.x).contains(&part.x) || !(0..saved.bounds.y).contains(&part.y)
+        }) {
+            return Err(format!("the Snek part at {:?} is off the Grid", part));
+        }
+
+        let snek = Snek::from_parts(
+            saved.parts.into(),
+            &saved.bounds,
+            saved.orientation,
+            saved.alive,
+        );
+        Ok(snek.with_score(saved.score))
+    }
+}
+
+impl From<Snek> for SavedSnek {
+    fn from(snek: Snek) -> Self {
+        Self {
+            bounds: *snek.occupied.bounds(),
+            parts: snek.parts.into(),
+            orientation: snek.orientation,
+            alive: snek.alive,
+            score: snek.score,
+        }
+    }
 }
 
 impl Default for Snek {
-    /// A default Snek starts are grid position 0,0 facing up
+    /// A default Snek starts are grid position 0,0 facing up, on a Grid the same size as a default Grid
     fn default() -> Self {
         log::warn!("New default Snek");
-        Self {
-            parts: VecDeque::from(vec![Vec2::new(0, 0)]),
-            orientation: FACING_UP, // Sneks start facing straight up
-        }
+        Self::from_parts(
+            VecDeque::from(vec![Vec2::new(0, 0)]),
+            Grid::default().bounds(),
+            Direction::Up, // Sneks start facing straight up
+            true,
+        )
     }
 }
 
 impl Snek {
-    /// Create a new Snek at the specified position
-    pub fn new(pos: Vec2) -> Self {
+    /// Create a new Snek at the specified position, on a Grid with the specified bounds
+    pub fn new(pos: Vec2, bounds: &Vec2) -> Self {
         log::warn!("New custom Snek");
+        Self::from_parts(
+            VecDeque::from(vec![pos]),
+            bounds,
+            Direction::Up, // Sneks start facing straight up
+            true,
+        )
+    }
+
+    /// Rebuild a Snek from its raw parts (tail first, head last)
+    pub(crate) fn from_parts(
+        parts: VecDeque<Vec2>,
+        bounds: &Vec2,
+        orientation: Direction,
+        alive: bool,
+    ) -> Self {
+        assert!(!parts.is_empty());
+
+        let mut occupied = Occupancy::new(bounds);
+        let overlaps = parts.iter().filter(|part| !occupied.insert(part)).count();
+
         Self {
-            parts: VecDeque::from(vec![pos]),
-            orientation: FACING_UP, // Sneks start facing straight up
+            parts,
+            occupied,
+            overlaps,
+            orientation,
+            alive,
+            score: 0,
         }
     }
 
+    /// Give a rebuilt Snek the score it had before
+    pub(crate) fn with_score(mut self, score: usize) -> Self {
+        self.score = score;
+        self
+    }
+
     /// How long is the Snek (not counting its head)
     pub fn len(&self) -> usize {
         self.parts.len() - 1
@@ -46,9 +133,14 @@ impl Snek {
         self.parts.len() == 1
     }
 
-    /// Get an immutable ref to the parts of the Snek
-    pub fn parts(&self) -> Vec<Vec2> {
-        self.parts.iter().copied().collect::<Vec<Vec2>>()
+    /// Get an immutable ref to the parts of the Snek (tail first, head last)
+    pub fn parts(&self) -> &VecDeque<Vec2> {
+        &self.parts
+    }
+
+    /// Get an immutable ref to the squares covered by the Snek
+    pub fn occupied(&self) -> &Occupancy {
+        &self.occupied
     }
 
     /// Get an immutable ref to the current position of the Snek head
@@ -57,33 +149,43 @@ impl Snek {
     }
 
     /// Get an immutable ref to the current direction of the Snek
-    pub fn orientation(&self) -> &Vec2 {
+    pub fn orientation(&self) -> &Direction {
         &self.orientation
     }
 
     /// Set the direction of the Snek
-    pub fn set_orientation(&mut self, new_orientation: Vec2) {
+    pub fn set_orientation(&mut self, new_orientation: Direction) {
         self.orientation = new_orientation;
     }
 
-    /// Turn the Snek to the left
-    pub fn turn_left(&mut self) {
-        self.orientation = self.orientation.rotate(direction::LEFT);
+    /// How many points has the Snek earned from eating Food
+    pub fn score(&self) -> usize {
+        self.score
+    }
+
+    /// Is the Snek still in the game
+    pub fn is_alive(&self) -> bool {
+        self.alive
+    }
+
+    /// Take the Snek out of the game
+    pub fn kill(&mut self) {
+        self.alive = false;
     }
 
-    /// Turn the Snek to the right
-    pub fn turn_right(&mut self) {
-        self.orientation = self.orientation.rotate(direction::RIGHT);
+    /// Turn the Snek to the left, to the next way the Topology of its Grid allows
+    pub fn turn_left(&mut self, topology: &dyn Topology) {
+        self.orientation = topology.turn_left(self.orientation);
     }
 
-    /// Turn the Snek
-    pub fn turn(&mut self, radians: f64) {
-        self.orientation = self.orientation.rotate(radians);
+    /// Turn the Snek to the right, to the next way the Topology of its Grid allows
+    pub fn turn_right(&mut self, topology: &dyn Topology) {
+        self.orientation = topology.turn_right(self.orientation);
     }
 
     /// Check if any part of the Snek is touching the specified position
     pub fn touches(&self, pos: &Vec2) -> bool {
-        self.parts.contains(pos)
+        self.occupied.contains(pos)
     }
 
     /// Check if the Snek head is touching the specified position
@@ -93,43 +195,64 @@ impl Snek {
 
     /// Check if the Snek head has hit the Snek body
     pub fn hit_self(&self) -> bool {
-        // If the Snek is only a head then it can't have hit itself!
-        if self.is_empty() {
-            return false;
-        }
-
-        // Find the first position in the VecDeque where the body_part is in the same place as the head
-        // This will always find a match, as the head is part of the body, so eventually the head will be compared with the head...
-        // But, as the head is always the last element in the VecDeque, this is fine
-        let p = self
-            .parts
-            .iter()
-            .position(|body_part| body_part == self.head())
-            .unwrap();
-
-        // If the head is NOT touching the body, then p will be the position of the head, i.e. the last element (or self.parts.len() - 1)
-        // So, if p is less than the last element, then it must be hitting the body
-        p < self.parts.len() - 1
+        // The head can only share a square with another part if it has run into the body
+        self.overlaps > 0
     }
 
-    /// Move the Snek
-    pub fn advance(&mut self, bounds: &Vec2, food: &Vec2) -> bool {
+    /// Move the Snek, eating any Food it lands on
+    /// Returns the index of the Food that was eaten, if any
+    pub fn advance(&mut self, food: &[Food]) -> Option<usize> {
         // Check where the Snek wants to go, wrapping around if it crosses the bounds of the grid
-        let new_head = Grid::wrap(bounds, &(self.head() + &self.orientation));
+        let new_head = Grid::wrap(self.occupied.bounds(), &(self.head() + self.orientation));
+
+        // Did the Snek eat any food? Poison counts as food, but doesn't make the Snek grow
+        let eaten = food.iter().position(|food| food.pos() == &new_head);
+        let growth = eaten.map_or(0, |idx| food[idx].kind().growth());
+
+        // If the Snek isn't growing, pop off the last bit of the tail first, so that the head can follow right behind it
+        if growth <= 0 {
+            self.drop_tail();
+        }
 
         // Add the new position
+        if !self.occupied.insert(&new_head) {
+            self.overlaps += 1;
+        }
         self.parts.push_back(new_head);
 
-        // Did the Snek eat the food?
-        if self.head_is_touching(food) {
-            log::warn!("Ate the food!");
-            return true;
-        } else {
-            // If not, pop off the last bit of the tail
-            self.parts.pop_front();
+        if let Some(idx) = eaten {
+            let kind = food[idx].kind();
+            log::warn!("Ate the {:?} food!", kind);
+
+            // Poison shrinks the Snek, although it can never take away the head
+            for _ in growth..0 {
+                if self.is_empty() {
+                    break;
+                }
+                self.drop_tail();
+            }
+
+            let points = kind.points();
+            self.score = if points < 0 {
+                self.score.saturating_sub(points.unsigned_abs() as usize)
+            } else {
+                self.score + points as usize
+            };
         }
 
-        false
+        eaten
+    }
+
+    /// Remove the last bit of the tail
+    fn drop_tail(&mut self) {
+        let tail = self.parts.pop_front().unwrap();
+
+        // Only free up the square if no other part is still in it
+        if self.overlaps > 0 && self.parts.contains(&tail) {
+            self.overlaps -= 1;
+        } else {
+            self.occupied.remove(&tail);
+        }
     }
 }
 
@@ -138,7 +261,8 @@ mod tests {
     use rand::SeedableRng;
     use rand_chacha::ChaCha8Rng;
 
-    use crate::entities::food::Food;
+    use crate::entities::food::FoodKind;
+    use crate::entities::topology::{Hex, Square};
 
     use super::*;
 
@@ -152,7 +276,7 @@ mod tests {
 
     #[test]
     fn new_snek() {
-        let snek = Snek::new(Vec2::new(5, 5));
+        let snek = Snek::new(Vec2::new(5, 5), &Vec2::new(10, 10));
 
         assert_eq!(snek.head(), &Vec2::new(5, 5));
         assert_eq!(snek.orientation(), &Vec2::new(0, 1));
@@ -162,23 +286,102 @@ mod tests {
     fn snek_advance() {
         let mut prng = ChaCha8Rng::from_seed(Default::default());
 
-        let bounds = Vec2::new(10, 10);
+        let grid = Grid::default();
         let mut snek = Snek::default();
-        let food = Food::random(&bounds, &snek, &mut prng);
+        let sneks = std::slice::from_ref(&snek);
+        let food = vec![Food::random(FoodKind::Normal, &grid, sneks, &[], &mut prng).unwrap()];
 
-        snek.advance(&bounds, food.pos());
+        snek.advance(&food);
 
         assert_eq!(snek.head(), &Vec2::new(0, 1));
 
-        snek.set_orientation(FACING_LEFT);
-        snek.advance(&bounds, food.pos());
+        snek.set_orientation(Direction::Left);
+        snek.advance(&food);
 
         assert_eq!(snek.head(), &Vec2::new(9, 1));
 
-        snek.advance(&bounds, food.pos());
+        snek.advance(&food);
         assert_eq!(snek.head(), &Vec2::new(8, 1));
     }
 
+    #[test]
+    fn snek_collisions() {
+        let bounds = Vec2::new(10, 10);
+
+        // A Snek curled up in a 2x2 square, with its head right behind its tail
+        let parts = vec![
+            Vec2::new(1, 1),
+            Vec2::new(2, 1),
+            Vec2::new(2, 2),
+            Vec2::new(1, 2),
+        ];
+        let mut snek = Snek::from_parts(parts.into(), &bounds, Direction::Down, true);
+        assert!(snek.touches(&Vec2::new(2, 2)));
+        assert!(!snek.touches(&Vec2::new(3, 3)));
+
+        // Chasing its own tail is fine, as the tail moves out of the way in time
+        snek.advance(&[]);
+        assert_eq!(snek.head(), &Vec2::new(1, 1));
+        assert!(!snek.hit_self());
+
+        // But not if the Snek grows at the same time
+        snek.set_orientation(Direction::Right);
+        snek.advance(&[]);
+        snek.set_orientation(Direction::Up);
+        snek.advance(&[Food::new(Vec2::new(2, 2))]);
+        snek.set_orientation(Direction::Left);
+        snek.advance(&[]);
+        snek.set_orientation(Direction::Down);
+        snek.advance(&[]);
+        assert!(snek.hit_self());
+
+        // The squares stay occupied for as long as any part is in them
+        assert_eq!(snek.occupied().count(), 4);
+        snek.advance(&[]);
+        assert!(!snek.hit_self());
+        assert!(snek.touches(&Vec2::new(1, 1)));
+        assert_eq!(snek.occupied().count(), 5);
+    }
+
+    #[test]
+    fn snek_eating() {
+        let bounds = Vec2::new(10, 10);
+        let mut snek = Snek::new(Vec2::new(5, 5), &bounds);
+        snek.set_orientation(Direction::Right);
+
+        // Only Food right in front of the Snek gets eaten
+        let food = vec![
+            Food::new(Vec2::new(8, 5)),
+            Food::with_kind(Vec2::new(6, 5), FoodKind::Bonus),
+        ];
+        assert_eq!(snek.advance(&food), Some(1));
+        assert_eq!(snek.len(), 1);
+        assert_eq!(snek.score(), 5);
+
+        let food = vec![
+            Food::with_kind(Vec2::new(7, 5), FoodKind::Golden),
+            Food::new(Vec2::new(8, 5)),
+        ];
+        assert_eq!(snek.advance(&food), Some(0));
+        assert_eq!(snek.advance(&food), Some(1));
+        assert_eq!(snek.len(), 3);
+        assert_eq!(snek.score(), 16);
+
+        // Poison shrinks the Snek and takes away points
+        let food = vec![Food::with_kind(Vec2::new(9, 5), FoodKind::Poison)];
+        assert_eq!(snek.advance(&food), Some(0));
+        assert_eq!(snek.len(), 1);
+        assert_eq!(snek.score(), 13);
+        assert_eq!(snek.occupied().count(), 2);
+
+        // But it can never take away the head
+        let food = vec![Food::with_kind(Vec2::new(0, 5), FoodKind::Poison)];
+        assert_eq!(snek.advance(&food), Some(0));
+        assert!(snek.is_empty());
+        assert_eq!(snek.head(), &Vec2::new(0, 5));
+        assert_eq!(snek.occupied().count(), 1);
+    }
+
     #[test]
     fn snek_turning() {
         // Create a new default Snek
@@ -187,28 +390,35 @@ mod tests {
         // Default Sneks start facing up
         assert_eq!(snek.orientation(), &Vec2::new(0, 1)); // Up
 
-        snek.turn_left();
+        snek.turn_left(&Square);
         assert_eq!(snek.orientation(), &Vec2::new(-1, 0)); // Left
 
-        snek.turn_left();
+        snek.turn_left(&Square);
         assert_eq!(snek.orientation(), &Vec2::new(0, -1)); // Down
 
-        snek.turn_left();
+        snek.turn_left(&Square);
         assert_eq!(snek.orientation(), &Vec2::new(1, 0)); // Right
 
-        snek.turn_left();
+        snek.turn_left(&Square);
         assert_eq!(snek.orientation(), &Vec2::new(0, 1)); // Up
 
-        snek.turn_right();
+        snek.turn_right(&Square);
         assert_eq!(snek.orientation(), &Vec2::new(1, 0)); // Right
 
-        snek.turn_right();
+        snek.turn_right(&Square);
         assert_eq!(snek.orientation(), &Vec2::new(0, -1)); // Down
 
-        snek.turn_right();
+        snek.turn_right(&Square);
         assert_eq!(snek.orientation(), &Vec2::new(-1, 0)); // Left
 
-        snek.turn_right();
+        snek.turn_right(&Square);
         assert_eq!(snek.orientation(), &Vec2::new(0, 1)); // Up
+
+        // Hexes have no corner between DownRight and Down, so the Snek turns straight from one to the other
+        snek.set_orientation(Direction::DownRight);
+        snek.turn_right(&Hex);
+        assert_eq!(snek.orientation(), &Direction::Down);
+        snek.turn_left(&Hex);
+        assert_eq!(snek.orientation(), &Direction::DownRight);
     }
 }
@@ -0,0 +1,333 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::types::{Direction, Vec2};
+
+use super::grid::Grid;
+
+/// The way the squares of a Grid are joined together - which ways a Snek can head, which squares are next to each other, and where each square is drawn
+/// Every Topology wraps around the edges of the Grid in the same way, so heading off one side brings a Snek back on the other
+pub trait Topology: std::fmt::Debug + Send + Sync {
+    /// Describe the Topology, so it can be saved along with the Grid
+    fn kind(&self) -> TopologyKind;
+
+    /// Every way a Snek can head, going clockwise from Up
+    fn directions(&self) -> &'static [Direction];
+
+    /// Get the number of moves needed to get from a to b, taking the shortest route around the edges of the Grid
+    fn distance(&self, bounds: &Vec2, a: &Vec2, b: &Vec2) -> usize;
+
+    /// Check if a Snek can head the specified way
+    fn allows(&self, dir: Direction) -> bool {
+        self.directions().contains(&dir)
+    }
+
+    /// Get the Direction the specified number of steps clockwise through `directions()` from dir (or anticlockwise, if negative)
+    /// Directions the Topology doesn't allow are left as they are
+    fn rotate(&self, dir: Direction, steps: i32) -> Direction {
+        let dirs = self.directions();
+        match dirs.iter().position(|&d| d == dir) {
+            Some(idx) => dirs[(idx as i32 + steps).rem_euclid(dirs.len() as i32) as usize],
+            None => dir,
+        }
+    }
+
+    /// Get the next Direction anticlockwise from dir
+    fn turn_left(&self, dir: Direction) -> Direction {
+        self.rotate(dir, -1)
+    }
+
+    /// Get the next Direction clockwise from dir
+    fn turn_right(&self, dir: Direction) -> Direction {
+        self.rotate(dir, 1)
+    }
+
+    /// Get the square reached by taking one step from pos in the specified Direction, wrapping around the edges of the Grid
+    fn step(&self, bounds: &Vec2, pos: &Vec2, dir: Direction) -> Vec2 {
+        Grid::wrap(bounds, &(pos + dir))
+    }
+
+    /// Get where the bottom left corner of the square at pos is drawn, in squares from the bottom left corner of the Grid
+    fn position(&self, pos: &Vec2) -> (f32, f32) {
+        (pos.x as f32, pos.y as f32)
+    }
+
+    /// Get how many squares across and up the whole Grid takes up when it's drawn
+    fn extent(&self, bounds: &Vec2) -> (f32, f32) {
+        (bounds.x as f32, bounds.y as f32)
+    }
+}
+
+/// Get the gaps between a and b along each axis, once they've both been wrapped onto the Grid
+fn gaps(bounds: &Vec2, a: &Vec2, b: &Vec2) -> (i32, i32) {
+    let a = Grid::wrap(bounds, a);
+    let b = Grid::wrap(bounds, b);
+
+    ((a.x - b.x).abs(), (a.y - b.y).abs())
+}
+
+/// The usual Grid of squares, where a Snek can only head up, down, left or right
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Square;
+
+impl Topology for Square {
+    fn kind(&self) -> TopologyKind {
+        TopologyKind::Square
+    }
+
+    fn directions(&self) -> &'static [Direction] {
+        &[
+            Direction::Up,
+            Direction::Right,
+            Direction::Down,
+            Direction::Left,
+        ]
+    }
+
+    fn distance(&self, bounds: &Vec2, a: &Vec2, b: &Vec2) -> usize {
+        // Going the other way around might be quicker
+        let (dx, dy) = gaps(bounds, a, b);
+        (dx.min(bounds.x - dx) + dy.min(bounds.y - dy)) as usize
+    }
+}
+
+/// A Grid of squares where a Snek can head diagonally too, so each square has eight neighbours
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Diagonal;
+
+impl Topology for Diagonal {
+    fn kind(&self) -> TopologyKind {
+        TopologyKind::Diagonal
+    }
+
+    fn directions(&self) -> &'static [Direction] {
+        &Direction::ALL
+    }
+
+    fn distance(&self, bounds: &Vec2, a: &Vec2, b: &Vec2) -> usize {
+        // A diagonal step covers both axes at once
+        let (dx, dy) = gaps(bounds, a, b);
+        dx.min(bounds.x - dx).max(dy.min(bounds.y - dy)) as usize
+    }
+}
+
+/// A Grid of hexagons, each with six neighbours, using axial coordinates
+/// x runs along each row as usual, but y runs up and to the right, so each row is drawn half a square further right than the one below it
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Hex;
+
+impl Topology for Hex {
+    fn kind(&self) -> TopologyKind {
+        TopologyKind::Hex
+    }
+
+    fn directions(&self) -> &'static [Direction] {
+        &[
+            Direction::Up,
+            Direction::Right,
+            Direction::DownRight,
+            Direction::Down,
+            Direction::Left,
+            Direction::UpLeft,
+        ]
+    }
+
+    fn distance(&self, bounds: &Vec2, a: &Vec2, b: &Vec2) -> usize {
+        let a = Grid::wrap(bounds, a);
+        let b = Grid::wrap(bounds, b);
+        let (dx, dy) = (a.x - b.x, a.y - b.y);
+
+        // The axes aren't independent here, so try every way around the edges of the Grid and keep the shortest
+        let mut best = i32::MAX;
+        for wx in -1..=1 {
+            for wy in -1..=1 {
+                let x = dx + wx * bounds.x;
+                let y = dy + wy * bounds.y;
+                best = best.min(x.abs().max(y.abs()).max((x + y).abs()));
+            }
+        }
+
+        best as usize
+    }
+
+    fn position(&self, pos: &Vec2) -> (f32, f32) {
+        (pos.x as f32 + pos.y as f32 / 2.0, pos.y as f32)
+    }
+
+    fn extent(&self, bounds: &Vec2) -> (f32, f32) {
+        (
+            bounds.x as f32 + (bounds.y - 1) as f32 / 2.0,
+            bounds.y as f32,
+        )
+    }
+}
+
+/// The Topologies a Grid can have, for picking one from the command line, a level file, or a saved Grid
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TopologyKind {
+    #[default]
+    Square,
+    Diagonal,
+    Hex,
+}
+
+impl TopologyKind {
+    /// Every kind of Topology
+    pub const ALL: [TopologyKind; 3] = [
+        TopologyKind::Square,
+        TopologyKind::Diagonal,
+        TopologyKind::Hex,
+    ];
+
+    /// Get the Topology this describes
+    pub fn build(self) -> &'static dyn Topology {
+        match self {
+            TopologyKind::Square => &Square,
+            TopologyKind::Diagonal => &Diagonal,
+            TopologyKind::Hex => &Hex,
+        }
+    }
+}
+
+impl FromStr for TopologyKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "square" => Ok(TopologyKind::Square),
+            "diagonal" => Ok(TopologyKind::Diagonal),
+            "hex" => Ok(TopologyKind::Hex),
+            _ => Err(format!(
+                "'{}' isn't a topology - try square, diagonal or hex",
+                s
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::*;
+
+    /// Count the moves from start to every square by walking out through the neighbours, to check the distances against
+    fn walk(grid: &Grid, start: &Vec2) -> Vec<usize> {
+        let mut steps = vec![usize::MAX; grid.len()];
+        steps[grid.index_at_xy(*start).unwrap()] = 0;
+        let mut queue = VecDeque::from(vec![*start]);
+
+        while let Some(pos) = queue.pop_front() {
+            let next_steps = steps[grid.index_at_xy(pos).unwrap()] + 1;
+            for next in grid.neighbours(&pos) {
+                let idx = grid.index_at_xy(next).unwrap();
+                if steps[idx] == usize::MAX {
+                    steps[idx] = next_steps;
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        steps
+    }
+
+    #[test]
+    fn directions_and_turns() {
+        for kind in TopologyKind::ALL.iter().copied() {
+            let topology = kind.build();
+            assert_eq!(topology.kind(), kind);
+
+            let dirs = topology.directions();
+            assert_eq!(dirs[0], Direction::Up);
+            for (idx, &dir) in dirs.iter().enumerate() {
+                assert!(topology.allows(dir));
+                assert_eq!(topology.turn_right(dir), dirs[(idx + 1) % dirs.len()]);
+                assert_eq!(topology.turn_left(topology.turn_right(dir)), dir);
+
+                // A Snek can always head straight back the way it came
+                assert!(topology.allows(dir.opposite()));
+            }
+        }
+
+        assert_eq!(Square.turn_right(Direction::Up), Direction::Right);
+        assert_eq!(Diagonal.turn_right(Direction::Up), Direction::UpRight);
+        assert_eq!(Hex.turn_left(Direction::Up), Direction::UpLeft);
+        assert_eq!(Hex.turn_right(Direction::Right), Direction::DownRight);
+
+        // Squares don't have corners to head through
+        assert!(!Square.allows(Direction::UpLeft));
+        assert!(!Hex.allows(Direction::UpRight));
+        assert_eq!(Square.turn_left(Direction::DownLeft), Direction::DownLeft);
+    }
+
+    #[test]
+    fn distances_match_walking() {
+        for kind in TopologyKind::ALL.iter().copied() {
+            for width in 1..8 {
+                for height in 1..8 {
+                    let grid = Grid::new(width, height).unwrap().with_topology(kind);
+                    for start in &grid {
+                        let steps = walk(&grid, &start);
+                        for end in &grid {
+                            assert_eq!(
+                                grid.distance(&start, &end),
+                                steps[grid.index_at_xy(end).unwrap()],
+                                "{:?} {}x{} from {:?} to {:?}",
+                                kind,
+                                width,
+                                height,
+                                start,
+                                end
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn hex_layout() {
+        let bounds = Vec2::new(4, 3);
+        let pos = Vec2::new(1, 1);
+
+        // Each row is shifted half a square right, so the squares above and below overlap two of the row's squares each
+        assert_eq!(Hex.position(&pos), (1.5, 1.0));
+        assert_eq!(Hex.position(&Vec2::new(0, 2)), (1.0, 2.0));
+        assert_eq!(Hex.extent(&bounds), (5.0, 3.0));
+        assert_eq!(Square.extent(&bounds), (4.0, 3.0));
+
+        let (x, _) = Hex.position(&pos);
+        for dir in [
+            Direction::Up,
+            Direction::UpLeft,
+            Direction::Down,
+            Direction::DownRight,
+        ]
+        .iter()
+        {
+            let (nx, _) = Hex.position(&(pos + *dir));
+            assert!((nx - x).abs() < 1.0, "{:?}", dir);
+        }
+
+        // Heading off the top brings a Snek back on at the bottom
+        assert_eq!(
+            Hex.step(&bounds, &Vec2::new(3, 2), Direction::Up),
+            Vec2::new(3, 0)
+        );
+        assert_eq!(
+            Hex.step(&bounds, &Vec2::new(0, 0), Direction::DownRight),
+            Vec2::new(1, 2)
+        );
+    }
+
+    #[test]
+    fn parse_kinds() {
+        assert_eq!("hex".parse(), Ok(TopologyKind::Hex));
+        assert_eq!("Diagonal".parse(), Ok(TopologyKind::Diagonal));
+        assert!("triangle".parse::<TopologyKind>().is_err());
+        assert_eq!(TopologyKind::default(), TopologyKind::Square);
+    }
+}
@@ -0,0 +1,509 @@
+use std::collections::HashSet;
+
+use rand_chacha::ChaCha8Rng;
+
+use crate::{
+    entities::{
+        grid::Grid,
+        topology::{Topology, TopologyKind},
+    },
+    game::state::GameState,
+    observation::Encoder,
+    players::{human::input::Input, Move, View},
+    types::Direction,
+};
+
+/// The moves an agent can make each step, relative to the way its Snek is facing
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Action {
+    Ahead,
+    Left,
+    Right,
+}
+
+impl Action {
+    /// Every Action, in the same order as their indices (and an AiPlayer's outputs)
+    pub const ALL: [Action; 3] = [Action::Ahead, Action::Left, Action::Right];
+
+    /// Get the index of this Action in `ALL`
+    pub fn index(self) -> usize {
+        match self {
+            Action::Ahead => 0,
+            Action::Left => 1,
+            Action::Right => 2,
+        }
+    }
+
+    /// Get the Action with the specified index, if there is one
+    pub fn from_index(idx: usize) -> Option<Self> {
+        Self::ALL.get(idx).copied()
+    }
+
+    /// Get the way a Snek facing in the specified direction would face after taking this Action, turning as far as the Topology's next direction
+    pub fn apply(self, topology: &dyn Topology, orientation: Direction) -> Direction {
+        match self {
+            Action::Ahead => orientation,
+            Action::Left => topology.turn_left(orientation),
+            Action::Right => topology.turn_right(orientation),
+        }
+    }
+}
+
+/// Describes the values an Environment takes or gives out
+#[derive(Debug, Clone, PartialEq)]
+pub enum Space {
+    /// One of `n` choices, numbered from 0
+    Discrete(usize),
+    /// A block of floats with the specified shape, each between `low` and `high`
+    Box {
+        shape: Vec<usize>,
+        low: f32,
+        high: f32,
+    },
+}
+
+impl Space {
+    /// How many values are in the Space - the number of choices for a Discrete Space, or the number of floats in a Box
+    pub fn size(&self) -> usize {
+        match self {
+            Space::Discrete(n) => *n,
+            Space::Box { shape, .. } => shape.iter().product(),
+        }
+    }
+}
+
+/// What came of a single step
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transition {
+    pub observation: Vec<f32>,
+    pub reward: f32,
+    /// Whether the game ended by itself, because the Snek died or won
+    pub done: bool,
+    /// Whether the game was cut short before it ended by itself
+    pub truncated: bool,
+    /// The score so far this game
+    pub score: usize,
+}
+
+impl Transition {
+    /// Whether `reset` needs calling before the next step
+    pub fn is_over(&self) -> bool {
+        self.done || self.truncated
+    }
+}
+
+/// A game that can be played one step at a time, for training agents with reinforcement learning
+pub trait Environment {
+    /// Start a new game using the specified seed, returning the first observation
+    /// The same seed always leads to the same game, given the same Actions
+    fn reset(&mut self, seed: u64) -> Vec<f32>;
+
+    /// Take an Action, and find out what came of it
+    fn step(&mut self, action: Action) -> Transition;
+
+    /// The Actions that can be taken each step
+    fn action_space(&self) -> Space;
+
+    /// The observations given out by `reset` and `step`
+    fn observation_space(&self) -> Space;
+}
+
+/// A player that never makes a move by itself, for games where the Snek is steered from outside
+#[derive(Debug, Default)]
+pub struct Puppet;
+
+impl Move for Puppet {
+    fn make_move(&mut self, _view: &View) -> Option<Direction> {
+        None
+    }
+}
+
+/// How much each thing that can happen in a game is worth to an agent that's learning to play
+#[derive(Debug, Clone)]
+pub struct Rewards {
+    pub points: f32,
+    pub death: f32,
+    pub win: f32,
+    pub step: f32,
+}
+
+impl Default for Rewards {
+    fn default() -> Self {
+        Self {
+            points: 1.0,  // For each point scored (or lost, for Poison)
+            death: -1.0,  // For crashing
+            win: 10.0,    // For filling the whole Grid
+            step: -0.001, // For every move, to discourage wandering around aimlessly
+        }
+    }
+}
+
+/// A single player game of Snek on an empty Grid, seen through an observation Encoder
+pub struct SnekEnv {
+    width: usize,
+    height: usize,
+    topology: TopologyKind,
+    encoder: Box<dyn Encoder>,
+    rewards: Rewards,
+    /// Games are cut short if the Snek goes this long without eating, so it can't go round in circles forever
+    patience: usize,
+    state: GameState<ChaCha8Rng, Puppet>,
+    hungry: usize,
+    keys: HashSet<Input>,
+}
+
+impl SnekEnv {
+    /// Create a new environment that plays on Grids of the specified size, and sees the game through the specified encoder
+    pub fn new(width: usize, height: usize, encoder: Box<dyn Encoder>) -> Self {
+        Self {
+            width,
+            height,
+            topology: TopologyKind::default(),
+            encoder,
+            rewards: Rewards::default(),
+            patience: 2 * width * height,
+            state: new_game(width, height, TopologyKind::default(), 0),
+            hungry: 0,
+            keys: HashSet::new(),
+        }
+    }
+
+    /// Use the specified rewards rather than the default ones
+    pub fn with_rewards(mut self, rewards: Rewards) -> Self {
+        self.rewards = rewards;
+        self
+    }
+
+    /// Play on Grids with the specified Topology, rather than the usual square ones
+    pub fn with_topology(mut self, kind: TopologyKind) -> Self {
+        self.topology = kind;
+        self.state = new_game(self.width, self.height, kind, 0);
+        self
+    }
+
+    /// Cut games short after the specified number of steps without eating, rather than twice the number of squares
+    pub fn with_patience(mut self, patience: usize) -> Self {
+        self.patience = patience;
+        self
+    }
+
+    /// Get an immutable ref to the encoder used to make the observations
+    pub fn encoder(&self) -> &dyn Encoder {
+        self.encoder.as_ref()
+    }
+
+    /// Get an immutable ref to the game currently being played
+    pub fn state(&self) -> &GameState<ChaCha8Rng, Puppet> {
+        &self.state
+    }
+
+    /// Encode what the Snek can see right now
+    fn observe(&self) -> Vec<f32> {
+        let view = View {
+            me: 0,
+            grid: &self.state.grid,
+            sneks: &self.state.sneks,
+            food: &self.state.food,
+            keys: &self.keys,
+        };
+
+        self.encoder.encode(&view)
+    }
+}
+
+impl Environment for SnekEnv {
+    fn reset(&mut self, seed: u64) -> Vec<f32> {
+        self.state = new_game(self.width, self.height, self.topology, seed);
+        self.hungry = 0;
+
+        self.observe()
+    }
+
+    fn step(&mut self, action: Action) -> Transition {
+        // Nothing more happens once the game is over
+        if !self.state.play || self.hungry >= self.patience {
+            return Transition {
+                observation: self.observe(),
+                reward: 0.0,
+                done: !self.state.play,
+                truncated: self.state.play,
+                score: self.state.sneks[0].score(),
+            };
+        }
+
+        let topology = self.state.grid.topology();
+        let snek = &mut self.state.sneks[0];
+        let orientation = action.apply(topology, *snek.orientation());
+        snek.set_orientation(orientation);
+
+        let score = self.state.sneks[0].score() as f32;
+        let length = self.state.sneks[0].len();
+        self.state.step();
+
+        let snek = &self.state.sneks[0];
+        let mut reward = self.rewards.step + (snek.score() as f32 - score) * self.rewards.points;
+        if self.state.won {
+            reward += self.rewards.win;
+        } else if !snek.is_alive() {
+            reward += self.rewards.death;
+        }
+
+        // Eating anything, even Poison, counts as not going hungry
+        if snek.score() as f32 != score || snek.len() != length {
+            self.hungry = 0;
+        } else {
+            self.hungry += 1;
+        }
+
+        Transition {
+            observation: self.observe(),
+            reward,
+            done: !self.state.play,
+            truncated: self.state.play && self.hungry >= self.patience,
+            score: snek.score(),
+        }
+    }
+
+    fn action_space(&self) -> Space {
+        Space::Discrete(Action::ALL.len())
+    }
+
+    fn observation_space(&self) -> Space {
+        let (low, high) = self.encoder.range();
+
+        Space::Box {
+            shape: self.encoder.shape(),
+            low,
+            high,
+        }
+    }
+}
+
+/// Set up a fresh game with the specified seed
+fn new_game(
+    width: usize,
+    height: usize,
+    topology: TopologyKind,
+    seed: u64,
+) -> GameState<ChaCha8Rng, Puppet> {
+    let grid = Grid::new(width, height)
+        .expect("Grids need to be at least 1x1")
+        .with_topology(topology);
+
+    GameState::seeded(grid, seed, vec![Puppet])
+}
+
+/// Many Environments played side by side, with each batch of steps spread across several threads
+/// Games that are over stay over, giving out the same final Transition, until the next `reset`
+pub struct VecEnv<E> {
+    envs: Vec<E>,
+    threads: usize,
+}
+
+impl<E: Environment + Send> VecEnv<E> {
+    /// Create a new VecEnv from the specified Environments, which should all have the same spaces
+    pub fn new(envs: Vec<E>) -> Self {
+        let threads = num_cpus::get();
+
+        Self { envs, threads }
+    }
+
+    /// Spread the work across at most the specified number of threads
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// How many Environments there are
+    pub fn len(&self) -> usize {
+        self.envs.len()
+    }
+
+    /// Whether there are no Environments at all
+    pub fn is_empty(&self) -> bool {
+        self.envs.is_empty()
+    }
+
+    /// Get an immutable ref to the Environments
+    pub fn envs(&self) -> &[E] {
+        &self.envs
+    }
+
+    /// Start a new game in every Environment, with the nth one using `seed + n`, returning the first observations
+    pub fn reset(&mut self, seed: u64) -> Vec<Vec<f32>> {
+        let seeds: Vec<u64> = (0..self.envs.len() as u64)
+            .map(|n| seed.wrapping_add(n))
+            .collect();
+
+        self.map(&seeds, |env, &seed| env.reset(seed))
+    }
+
+    /// Take one Action in each Environment, in order
+    pub fn step(&mut self, actions: &[Action]) -> Vec<Transition> {
+        assert_eq!(
+            actions.len(),
+            self.envs.len(),
+            "need one Action per Environment"
+        );
+
+        self.map(actions, |env, &action| env.step(action))
+    }
+
+    /// The Actions that can be taken each step, in each Environment
+    pub fn action_space(&self) -> Option<Space> {
+        self.envs.first().map(Environment::action_space)
+    }
+
+    /// The observations given out by each Environment
+    pub fn observation_space(&self) -> Option<Space> {
+        self.envs.first().map(Environment::observation_space)
+    }
+
+    /// Call `f` on each Environment along with its input, splitting them into one chunk per thread
+    fn map<T, R, F>(&mut self, inputs: &[T], f: F) -> Vec<R>
+    where
+        T: Sync,
+        R: Send,
+        F: Fn(&mut E, &T) -> R + Sync,
+    {
+        let mut outputs: Vec<Option<R>> = self.envs.iter().map(|_| None).collect();
+        if self.envs.is_empty() {
+            return Vec::new();
+        }
+
+        // Round up, so nothing gets left over when the number of Environments isn't a multiple of the thread count (there's always at least one)
+        let chunk = (self.envs.len() - 1) / self.threads + 1;
+        let f = &f;
+
+        crossbeam::scope(|scope| {
+            for ((envs, inputs), outputs) in self
+                .envs
+                .chunks_mut(chunk)
+                .zip(inputs.chunks(chunk))
+                .zip(outputs.chunks_mut(chunk))
+            {
+                scope.spawn(move |_| {
+                    for ((env, input), output) in envs.iter_mut().zip(inputs).zip(outputs) {
+                        *output = Some(f(env, input));
+                    }
+                });
+            }
+        })
+        .expect("an Environment panicked");
+
+        outputs.into_iter().flatten().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::entities::food::Food;
+    use crate::observation::{Board, Senses};
+    use crate::types::Vec2;
+
+    use super::*;
+
+    #[test]
+    fn actions() {
+        for (idx, action) in Action::ALL.iter().enumerate() {
+            assert_eq!(action.index(), idx);
+            assert_eq!(Action::from_index(idx), Some(*action));
+        }
+        assert_eq!(Action::from_index(3), None);
+
+        let up = Direction::Up;
+        let square = TopologyKind::Square.build();
+        assert_eq!(Action::Ahead.apply(square, up), up);
+        assert_eq!(Action::Left.apply(square, up), Vec2::new(-1, 0));
+        assert_eq!(Action::Right.apply(square, up), Vec2::new(1, 0));
+
+        // Turns only go as far as the next way the Topology allows
+        let hex = TopologyKind::Hex.build();
+        assert_eq!(Action::Left.apply(hex, up), Direction::UpLeft);
+        assert_eq!(Action::Right.apply(hex, up), Direction::Right);
+    }
+
+    #[test]
+    fn spaces() {
+        let env = SnekEnv::new(6, 4, Box::new(Board::new(&Grid::new(6, 4).unwrap())));
+        assert_eq!(env.action_space(), Space::Discrete(3));
+
+        let space = env.observation_space();
+        assert_eq!(space.size(), env.encoder().size());
+        assert!(matches!(space, Space::Box { ref shape, .. } if shape[1..] == [4, 6]));
+    }
+
+    #[test]
+    fn rewards() {
+        let mut env = SnekEnv::new(5, 5, Box::new(Senses));
+        assert_eq!(env.reset(0).len(), env.observation_space().size());
+
+        // Put some Food right in front of the Snek, and a wall just after it
+        let head = *env.state.sneks[0].head();
+        let ahead = *env.state.sneks[0].orientation();
+        let food = Grid::wrap(env.state.grid.bounds(), &(head + ahead));
+        let wall = Grid::wrap(env.state.grid.bounds(), &(food + ahead));
+        env.state.food = vec![Food::new(food)];
+        env.state.food_config.special_chance = 0.0;
+        env.state.grid.add_wall(&wall);
+
+        let step = env.step(Action::Ahead);
+        assert!((step.reward - 0.999).abs() < 1e-6);
+        assert!(!step.is_over());
+        assert_eq!(step.score, 1);
+
+        let step = env.step(Action::Ahead);
+        assert!((step.reward + 1.001).abs() < 1e-6);
+        assert!(step.done && !step.truncated);
+
+        // Nothing more happens until the next game
+        let step = env.step(Action::Left);
+        assert_eq!(step.reward, 0.0);
+        assert!(step.done);
+
+        env.reset(1);
+        assert!(env.state().play);
+        assert_eq!(env.state().sneks[0].len(), 0);
+    }
+
+    #[test]
+    fn hungry_sneks_give_up() {
+        let mut env = SnekEnv::new(4, 4, Box::new(Senses)).with_patience(10);
+        env.reset(0);
+        env.state.food = vec![Food::new(Vec2::new(2, 2))];
+        env.state.food_config.special_chance = 0.0;
+
+        // Turning the same way every move, the Snek goes round in a tight circle that never reaches the Food, until it runs out of patience
+        let steps = (0..100)
+            .take_while(|_| !env.step(Action::Left).is_over())
+            .count();
+        assert_eq!(steps + 1, 10);
+
+        let step = env.step(Action::Left);
+        assert!(step.truncated && !step.done);
+    }
+
+    #[test]
+    fn vec_env_matches_sequential_games() {
+        let make = || SnekEnv::new(6, 6, Box::new(Senses));
+        let mut vec_env = VecEnv::new((0..5).map(|_| make()).collect()).with_threads(2);
+        let mut envs: Vec<SnekEnv> = (0..5).map(|_| make()).collect();
+
+        let observations = vec_env.reset(7);
+        for (n, env) in envs.iter_mut().enumerate() {
+            assert_eq!(env.reset(7 + n as u64), observations[n]);
+        }
+
+        for step in 0..50 {
+            let actions: Vec<Action> = (0..5).map(|n| Action::ALL[(n + step) % 3]).collect();
+            let transitions = vec_env.step(&actions);
+
+            for ((env, action), transition) in envs.iter_mut().zip(&actions).zip(&transitions) {
+                assert_eq!(&env.step(*action), transition);
+            }
+        }
+
+        assert_eq!(vec_env.len(), 5);
+        assert_eq!(vec_env.action_space(), Some(Space::Discrete(3)));
+    }
+}
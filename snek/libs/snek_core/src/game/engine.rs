@@ -1,15 +1,26 @@
-use ggez::event::EventHandler;
-use ggez::graphics::{Color, Mesh, Rect};
+use std::convert::TryFrom;
+
+use ggez::event::{Axis, Button, EventHandler, GamepadId};
+use ggez::graphics::{Color, Mesh, MeshBuilder, Rect};
+use ggez::input::keyboard::{self, KeyCode, KeyMods};
 use ggez::{graphics, Context, GameError, GameResult};
 use rand::RngCore;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 
-use crate::entities::food::Food;
+use crate::entities::food::{Food, FoodKind};
 use crate::entities::grid::Grid;
 use crate::entities::snek::Snek;
+use crate::net::NetGame;
+use crate::players::human::input::Input;
+use crate::players::human::player::{Control, Controls};
 use crate::players::Move;
-use crate::settings::SCREEN_HEIGHT;
-use crate::types::Vec2;
+use crate::scores::HighScores;
+use crate::settings::MENU;
+use crate::types::{Direction, Vec2};
 
+use super::mode::Mode;
+use super::scene::{Rebinding, Scene, Scenes};
 use super::state::GameState;
 
 impl<R, M> EventHandler<GameError> for GameState<R, M>
@@ -19,17 +30,12 @@ where
 {
     /// Called every frame
     fn update(&mut self, ctx: &mut Context) -> GameResult<()> {
-        const DESIRED_FPS: u32 = 5;
-
-        // Check for new input without waiting
-        let players_move = self.player.make_move(ctx);
+        // Check for new input without waiting - a bare GameState only listens to the keyboard, so use Scenes for gamepads
+        let keys = Input::keys(keyboard::pressed_keys(ctx));
+        self.poll_players(&keys);
 
-        // Check to see if the player has made a new move, otherwise continue in the current direction
-        if let Some(new_orientation) = players_move {
-            self.snek.set_orientation(new_orientation);
-        }
-
-        while ggez::timer::check_update_time(ctx, DESIRED_FPS) {
+        // The tick rate can change from one step to the next, so check it every time
+        while ggez::timer::check_update_time(ctx, self.tick_rate()) {
             self.step();
 
             // Finally, check if the the game has ended, and quit if so
@@ -43,154 +49,664 @@ where
     }
 
     /// Draw the game screen
+    /// A bare GameState has nowhere to keep a Renderer between frames, so nothing gets cached - use Scenes for that
+    fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let alpha = tick_progress(ctx, self.tick_rate());
+        Renderer::default().draw_game(ctx, self, alpha)?;
+        graphics::present(ctx)
+    }
+
+    /// Speed the game up or slow it down with the + and - keys
+    fn key_down_event(
+        &mut self,
+        ctx: &mut Context,
+        keycode: KeyCode,
+        _keymods: KeyMods,
+        _repeat: bool,
+    ) {
+        match keycode {
+            KeyCode::Equals | KeyCode::Plus | KeyCode::NumpadAdd => self.adjust_speed(1),
+            KeyCode::Minus | KeyCode::NumpadSubtract => self.adjust_speed(-1),
+            KeyCode::Escape => ggez::event::quit(ctx),
+            _ => {}
+        }
+    }
+}
+
+impl EventHandler<GameError> for NetGame {
+    /// Called every frame
+    fn update(&mut self, ctx: &mut Context) -> GameResult<()> {
+        // Check for new input without waiting
+        let keys = keyboard::pressed_keys(ctx).clone();
+        self.poll_local(&keys);
+
+        while ggez::timer::check_update_time(ctx, self.state.tick_rate()) {
+            self.tick_due();
+        }
+
+        // Only move the game on as far as everyone's moves allow
+        self.catch_up()?;
+
+        // Finally, check if the the game has ended, and quit if so
+        if !self.state.play {
+            log::warn!("{}", self.state);
+            ggez::event::quit(ctx);
+        }
+
+        Ok(())
+    }
+
+    /// Draw the game screen
+    fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let alpha = tick_progress(ctx, self.state.tick_rate());
+        self.renderer.draw_game(ctx, &self.state, alpha)?;
+        graphics::present(ctx)
+    }
+
+    fn gamepad_button_down_event(&mut self, _ctx: &mut Context, btn: Button, id: GamepadId) {
+        let pad = self.inputs.pad(id);
+        self.inputs.button_down(pad, btn);
+    }
+
+    fn gamepad_button_up_event(&mut self, _ctx: &mut Context, btn: Button, id: GamepadId) {
+        let pad = self.inputs.pad(id);
+        self.inputs.button_up(pad, btn);
+    }
+
+    fn gamepad_axis_event(&mut self, _ctx: &mut Context, axis: Axis, value: f32, id: GamepadId) {
+        let pad = self.inputs.pad(id);
+        self.inputs.axis(pad, axis, value);
+    }
+}
+
+impl<R, M, F> EventHandler<GameError> for Scenes<R, M, F>
+where
+    R: RngCore + Clone + Serialize + DeserializeOwned,
+    M: Move,
+    F: FnMut(&Mode, &[Controls]) -> GameResult<GameState<R, M>>,
+{
+    /// Called every frame
+    fn update(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let tick_rate = self.state().map_or(1, |state| state.tick_rate());
+
+        // Only the game itself moves - everywhere else, just let the time pass so the game doesn't race to catch up when it resumes
+        if self.scene() != Scene::Playing {
+            while ggez::timer::check_update_time(ctx, tick_rate) {}
+            return Ok(());
+        }
+
+        // Check for new input without waiting
+        let keys = keyboard::pressed_keys(ctx).clone();
+        self.poll_players(&keys);
+
+        // The tick rate can change from one step to the next, so check it every time
+        while ggez::timer::check_update_time(ctx, self.state().map_or(1, |state| state.tick_rate()))
+        {
+            self.step();
+        }
+
+        Ok(())
+    }
+
+    /// Draw whichever screen we're on
     fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
+        // Work out what to write over the top of the game before borrowing the Renderer
+        let scene = self.scene();
+        if scene == Scene::Controls {
+            graphics::clear(ctx, Color::from_rgba(0, 0, 0, 255));
+            draw_centred_text(ctx, &controls_text(self.controls(), self.rebinding()));
+            return graphics::present(ctx);
+        }
+
+        let overlay = overlay_text(self);
+
+        match self.state_and_renderer() {
+            (Some(state), renderer) if scene != Scene::Menu => {
+                // Keep the Sneks where they were if the game isn't running
+                let alpha = match scene {
+                    Scene::Playing => tick_progress(ctx, state.tick_rate()),
+                    _ => 1.0,
+                };
+                renderer.draw_game(ctx, state, alpha)?;
+            }
+            _ => draw_menu(ctx, self.selected()),
+        }
+
+        if let Some(lines) = overlay {
+            draw_overlay(ctx, &lines);
+        }
+
+        graphics::present(ctx)
+    }
+
+    /// Navigate between the screens using the keyboard
+    fn key_down_event(
+        &mut self,
+        ctx: &mut Context,
+        keycode: KeyCode,
+        _keymods: KeyMods,
+        _repeat: bool,
+    ) {
+        match self.key_pressed(keycode) {
+            Ok(true) => ggez::event::quit(ctx),
+            Ok(false) => {}
+            Err(e) => {
+                log::warn!("Couldn't start a new game: {}", e);
+                self.to_menu();
+            }
+        }
+    }
+
+    fn gamepad_button_down_event(&mut self, _ctx: &mut Context, btn: Button, id: GamepadId) {
+        let inputs = self.inputs_mut();
+        let pad = inputs.pad(id);
+        let input = inputs.button_down(pad, btn);
+        self.input_pressed(input);
+    }
+
+    fn gamepad_button_up_event(&mut self, _ctx: &mut Context, btn: Button, id: GamepadId) {
+        let inputs = self.inputs_mut();
+        let pad = inputs.pad(id);
+        inputs.button_up(pad, btn);
+    }
+
+    fn gamepad_axis_event(&mut self, _ctx: &mut Context, axis: Axis, value: f32, id: GamepadId) {
+        let inputs = self.inputs_mut();
+        let pad = inputs.pad(id);
+        if let Some(input) = inputs.axis(pad, axis, value) {
+            self.input_pressed(input);
+        }
+    }
+}
+
+/// How far through the current tick we are, from 0.0 (just stepped) to 1.0 (about to step again)
+/// Rendering happens far more often than stepping, so this lets the Sneks slide smoothly from one square to the next
+pub fn tick_progress(ctx: &mut Context, tick_rate: u32) -> f32 {
+    let waited = ggez::timer::remaining_update_time(ctx).as_secs_f32();
+
+    (waited * tick_rate as f32).min(1.0)
+}
+
+/// Draws games, holding on to anything that doesn't change from one frame to the next
+#[derive(Default)]
+pub struct Renderer {
+    /// The Grid lines and walls, along with the Grid and screen size they were built for
+    background: Option<(Grid, (f32, f32), Mesh)>,
+}
+
+impl Renderer {
+    /// Render the whole game, with the Sneks the specified fraction of the way through their next move
+    /// Everything that moves is batched up into a single mesh, so the cost of drawing hardly depends on the size of the Grid
+    pub fn draw_game<R, M>(
+        &mut self,
+        ctx: &mut Context,
+        state: &GameState<R, M>,
+        alpha: f32,
+    ) -> GameResult
+    where
+        R: RngCore,
+        M: Move,
+    {
         // Clear the screen to black
         graphics::clear(ctx, Color::from_rgba(0, 0, 0, 255));
 
-        // Draw the Grid outline
-        draw_grid(ctx, &self.grid);
+        // Draw the Grid outline, and any walls
+        let background = self.background(ctx, &state.grid)?;
+        graphics::draw(ctx, background, graphics::DrawParam::default())?;
 
-        // Draw the Food
-        draw_food(ctx, &self.food, &self.grid);
+        // Add each item of Food, and the living Sneks each in their own colours
+        let mut builder = MeshBuilder::new();
+        for food in &state.food {
+            add_food(ctx, &mut builder, food, &state.grid)?;
+        }
+
+        let mut living = 0;
+        for (idx, snek) in state.sneks.iter().enumerate() {
+            if snek.is_alive() {
+                add_snek(
+                    ctx,
+                    &mut builder,
+                    snek,
+                    state.previous_ends[idx],
+                    alpha,
+                    &state.grid,
+                    SNEK_COLOURS[idx % SNEK_COLOURS.len()],
+                )?;
+                living += 1;
+            }
+        }
 
-        // Draw the Snek
-        draw_snek(ctx, &self.snek, &self.grid);
+        // Meshes can't be empty, so there might be nothing to draw
+        if living > 0 || !state.food.is_empty() {
+            let mesh = builder.build(ctx)?;
+            graphics::draw(ctx, &mesh, graphics::DrawParam::default())?;
+        }
 
         // Draw the stats
-        draw_stats(ctx, self.snek.len());
-
-        // Update the screen
-        graphics::present(ctx).expect("Error presenting graphics!");
+        draw_stats(ctx, &state.scores(), state.tick_rate());
 
         Ok(())
     }
+
+    /// Get the mesh for the Grid lines and walls, only building it again if the Grid or the screen size have changed
+    fn background(&mut self, ctx: &mut Context, grid: &Grid) -> GameResult<&Mesh> {
+        let size = graphics::drawable_size(ctx);
+
+        let stale = match &self.background {
+            Some((cached, cached_size, _)) => cached != grid || *cached_size != size,
+            None => true,
+        };
+
+        if stale {
+            log::trace!(
+                "Building the background for a {}x{} Grid",
+                grid.width(),
+                grid.height()
+            );
+            let mesh = build_background(ctx, grid)?;
+            self.background = Some((grid.clone(), size, mesh));
+        }
+
+        Ok(&self.background.as_ref().unwrap().2)
+    }
+}
+
+/// Render the title menu, with the selected game mode highlighted
+fn draw_menu(ctx: &mut Context, selected: usize) {
+    graphics::clear(ctx, Color::from_rgba(0, 0, 0, 255));
+    draw_centred_text(ctx, &menu_text(selected));
+}
+
+/// The lines shown on the title menu, with the selected game mode highlighted
+pub(crate) fn menu_text(selected: usize) -> Vec<String> {
+    let mut lines = vec![crate::settings::GAME_TITLE.to_uppercase(), String::new()];
+    lines.extend(
+        MENU.iter()
+            .enumerate()
+            .map(|(idx, (name, _))| match idx == selected {
+                true => format!("> {} <", name),
+                false => name.to_string(),
+            }),
+    );
+    lines.push(String::new());
+    lines.push("Up/Down to choose, Enter to start, C for controls, Esc to quit".to_owned());
+
+    lines
+}
+
+/// The lines written over the top of the game when it's paused or over, if it is
+pub(crate) fn overlay_text<R, M, F>(scenes: &Scenes<R, M, F>) -> Option<Vec<String>>
+where
+    R: RngCore,
+    M: Move,
+    F: FnMut(&Mode, &[Controls]) -> GameResult<GameState<R, M>>,
+{
+    match (scenes.scene(), scenes.state()) {
+        (Scene::Paused, Some(_)) => {
+            let mut lines = vec![
+                "PAUSED".to_owned(),
+                String::new(),
+                "P to carry on, R to restart, M for the menu, Q to quit".to_owned(),
+            ];
+            if scenes.quick_save_path().is_some() {
+                lines.push("F5 to quick save, F9 to quick load".to_owned());
+            }
+            Some(lines)
+        }
+        (Scene::GameOver, Some(state)) => Some(game_over_text(
+            state,
+            scenes.high_scores(),
+            scenes.new_high_scores(),
+        )),
+        _ => None,
+    }
+}
+
+/// The lines shown on the Controls screen - everything bound to each of one player's Controls, with the chosen one highlighted
+pub(crate) fn controls_text(controls: &[Controls], rebinding: Rebinding) -> Vec<String> {
+    let mut lines = vec![
+        format!("CONTROLS - P{} of {}", rebinding.player + 1, controls.len()),
+        String::new(),
+    ];
+
+    for (idx, control) in Control::ALL.iter().enumerate() {
+        let bindings: Vec<String> = controls[rebinding.player]
+            .bindings(*control)
+            .iter()
+            .map(Input::to_string)
+            .collect();
+        let bindings = match (
+            rebinding.listening && idx == rebinding.control,
+            bindings.is_empty(),
+        ) {
+            (true, _) => "press a key or button...".to_owned(),
+            (false, true) => "-".to_owned(),
+            (false, false) => bindings.join(", "),
+        };
+
+        lines.push(match idx == rebinding.control {
+            true => format!("> {}: {} <", control.name(), bindings),
+            false => format!("{}: {}", control.name(), bindings),
+        });
+    }
+
+    lines.push(String::new());
+    lines.push(match rebinding.listening {
+        true => "Press the key, button or stick to add, or Esc to stop".to_owned(),
+        false => "Up/Down to choose, Left/Right for other players, Enter to add, Backspace to clear, Esc to save".to_owned(),
+    });
+
+    lines
+}
+
+/// How many of the high scores fit on the game over screen
+const HIGH_SCORES_SHOWN: usize = 5;
+
+/// The lines shown once a game has ended - who won, how each Snek got on, and the best scores so far (with any new ones marked)
+fn game_over_text<R, M>(
+    state: &GameState<R, M>,
+    high_scores: &HighScores,
+    new_high_scores: &[usize],
+) -> Vec<String>
+where
+    R: RngCore,
+    M: Move,
+{
+    let title = match state.won {
+        true => "YOU WIN - THE GRID IS FULL!",
+        false => "GAME OVER",
+    };
+    let mut lines = vec![title.to_owned(), String::new()];
+
+    for (idx, snek) in state.sneks.iter().enumerate() {
+        let fate = match state.deaths[idx] {
+            Some(death) => death.to_string(),
+            None => "survived".to_owned(),
+        };
+
+        lines.push(format!("P{}: {} points - {}", idx + 1, snek.score(), fate));
+    }
+
+    if !high_scores.entries().is_empty() {
+        lines.push(String::new());
+        lines.push("HIGH SCORES".to_owned());
+    }
+    for (rank, entry) in high_scores
+        .entries()
+        .iter()
+        .enumerate()
+        .take(HIGH_SCORES_SHOWN)
+    {
+        let marker = match new_high_scores.contains(&rank) {
+            true => "NEW! ",
+            false => "",
+        };
+
+        lines.push(format!(
+            "{}{}. {} - {} ({}x{}, {})",
+            marker,
+            rank + 1,
+            entry.name,
+            entry.score,
+            entry.width,
+            entry.height,
+            entry.date.format("%Y-%m-%d")
+        ));
+    }
+
+    lines.push(String::new());
+    lines.push("R to play again, M for the menu, Q to quit".to_owned());
+
+    lines
+}
+
+/// Darken whatever is already on the screen, and write the specified lines over the top
+fn draw_overlay(ctx: &mut Context, lines: &[String]) {
+    let (w, h) = graphics::drawable_size(ctx);
+    let shade = Mesh::new_rectangle(
+        ctx,
+        graphics::DrawMode::fill(),
+        Rect::new(0.0, 0.0, w, h),
+        Color::from_rgba(0, 0, 0, 180),
+    )
+    .expect("Error creating the overlay mesh!");
+
+    graphics::draw(ctx, &shade, graphics::DrawParam::default()).expect("Error drawing mesh!");
+
+    draw_centred_text(ctx, lines);
+}
+
+/// Write the specified lines in the middle of the screen, one under the other
+fn draw_centred_text(ctx: &mut Context, lines: &[String]) {
+    const LINE_HEIGHT: f32 = 30.0;
+
+    let (screen_width, screen_height) = graphics::drawable_size(ctx);
+    let top = (screen_height - LINE_HEIGHT * lines.len() as f32) / 2.0;
+
+    for (idx, line) in lines.iter().enumerate() {
+        let text = graphics::Text::new(line.as_str());
+        let x = (screen_width - text.width(ctx)) / 2.0;
+        let y = top + LINE_HEIGHT * idx as f32;
+
+        graphics::draw(ctx, &text, graphics::DrawParam::default().dest([x, y]))
+            .expect("Error drawing text!");
+    }
 }
 
+/// An RGB colour
+pub(crate) type Rgb = (u8, u8, u8);
+
+/// The colour of the walls
+pub(crate) const WALL_COLOUR: Rgb = (120, 120, 120);
+
+/// The (body, head) colours used for each player's Snek, in player order
+pub(crate) const SNEK_COLOURS: [(Rgb, Rgb); 4] = [
+    ((255, 50, 50), (50, 50, 200)),
+    ((255, 200, 50), (150, 50, 150)),
+    ((50, 200, 255), (200, 50, 50)),
+    ((200, 200, 200), (50, 150, 50)),
+];
+
 /// How many pixels make up each Grid square?
 fn pixels_per_grid_square(ctx: &mut Context, grid: &Grid) -> (f32, f32) {
     let (screen_width, screen_height) = graphics::drawable_size(ctx);
+    let (width, height) = grid.topology().extent(grid.bounds());
 
-    (
-        screen_width / grid.width() as f32,
-        screen_height / grid.height() as f32,
-    )
+    (screen_width / width, screen_height / height)
 }
 
-/// Convert grid positions into pixel positions
+/// Convert grid positions into pixel positions, laid out however the Grid's Topology draws them
+/// The Grid counts rows from the bottom, but the screen counts them from the top, so the rows are flipped over
 fn grid_position_to_pixels(ctx: &mut Context, pos: &Vec2, grid: &Grid) -> (f32, f32) {
     let (px, py) = pixels_per_grid_square(ctx, grid);
+    let (x, y) = grid.topology().position(pos);
+
+    (px * x, py * flip_row(grid, y))
+}
 
-    (px * pos.x as f32, py * pos.y as f32)
+/// Get the row counting down from the top of the screen, for the specified row counting up from the bottom of the Grid
+fn flip_row(grid: &Grid, y: f32) -> f32 {
+    (grid.height() - 1) as f32 - y
 }
 
-/// Render the Grid outline
-fn draw_grid(ctx: &mut Context, grid: &Grid) {
+/// Find the pixel position part way between two neighbouring grid positions
+/// Positions that aren't next to each other (e.g. when a Snek wraps around the edge of the Grid) aren't blended - it just jumps straight there
+fn interpolated_position_to_pixels(
+    ctx: &mut Context,
+    from: &Vec2,
+    to: &Vec2,
+    alpha: f32,
+    grid: &Grid,
+) -> (f32, f32) {
+    let (px, py) = pixels_per_grid_square(ctx, grid);
+    let topology = grid.topology();
+
+    let alpha = match Direction::try_from(to - from) {
+        Ok(dir) if topology.allows(dir) => alpha,
+        _ => 1.0,
+    };
+
+    let (from_x, from_y) = topology.position(from);
+    let (to_x, to_y) = topology.position(to);
+    (
+        px * (from_x + (to_x - from_x) * alpha),
+        py * flip_row(grid, from_y + (to_y - from_y) * alpha),
+    )
+}
+
+/// Build a single mesh with the Grid outline and the walls as solid grey squares
+fn build_background(ctx: &mut Context, grid: &Grid) -> GameResult<Mesh> {
     // How big should each grid square appear
     let (px, py) = pixels_per_grid_square(ctx, grid);
+    let (width, height) = (px * grid.width() as f32, py * grid.height() as f32);
+    let white = Color::from_rgba(255, 255, 255, 255);
+
+    let mut builder = MeshBuilder::new();
+
+    // One line along each edge of every column and row, rather than a box around every square
+    // That only works where the rows line up though - where they're shifted sideways, each row gets its own lines
+    let topology = grid.topology();
+    match topology.position(&Vec2::new(0, 1)).0 == 0.0 {
+        true => {
+            for x in 0..=grid.width() {
+                let x = px * x as f32;
+                builder.line(&[[x, 0.0], [x, height]], 2.0, white)?;
+            }
+            for y in 0..=grid.height() {
+                let y = py * y as f32;
+                builder.line(&[[0.0, y], [width, y]], 2.0, white)?;
+            }
+        }
+        false => {
+            for y in 0..grid.height() {
+                let (left, row) = topology.position(&Vec2::new(0, y as i32));
+                let (left, top) = (px * left, py * flip_row(grid, row));
+                for edge in [top, top + py].iter() {
+                    builder.line(&[[left, *edge], [left + width, *edge]], 2.0, white)?;
+                }
+                for x in 0..=grid.width() {
+                    let x = left + px * x as f32;
+                    builder.line(&[[x, top], [x, top + py]], 2.0, white)?;
+                }
+            }
+        }
+    }
 
-    // Generate a vector of meshes
-    let meshes: Vec<Mesh> = grid
-        .into_iter()
-        .map(|square| {
-            let (x, y) = grid_position_to_pixels(ctx, &square, grid);
-            let rect = Rect::new(x, y, px, py);
-            Mesh::new_rectangle(
-                ctx,
-                graphics::DrawMode::stroke(2.0),
-                rect,
-                Color::from_rgba(255, 255, 255, 255),
-            )
-            .expect("Error creating one of the meshes for the Grid!")
-        })
-        .collect();
-
-    // Draw the meshes
-    for mesh in meshes {
-        graphics::draw(ctx, &mesh, graphics::DrawParam::default()).expect("Error drawing mesh!");
-    }
-}
-
-/// Render the Food
-fn draw_food(ctx: &mut Context, food: &Food, grid: &Grid) {
+    for square in grid.into_iter().filter(|square| grid.is_wall(square)) {
+        let (x, y) = grid_position_to_pixels(ctx, &square, grid);
+        builder.rectangle(
+            graphics::DrawMode::fill(),
+            Rect::new(x, y, px, py),
+            Color::from(WALL_COLOUR),
+        )?;
+    }
+
+    builder.build(ctx)
+}
+
+/// Add a single item of Food to the mesh, coloured according to its kind
+fn add_food(ctx: &mut Context, builder: &mut MeshBuilder, food: &Food, grid: &Grid) -> GameResult {
     // How big should the Food appear
     let (w, h) = pixels_per_grid_square(ctx, grid);
 
-    // Where is the Food currently on the Grid?
-    let pos = food.pos();
+    // Where is the Food currently on the Grid, and what is this in pixel-space?
+    let (x, y) = grid_position_to_pixels(ctx, food.pos(), grid);
 
-    // What is this in pixel-space?
-    let (x, y) = grid_position_to_pixels(ctx, pos, grid);
+    // Pick the colour - Golden Food fades away as it gets closer to vanishing
+    let (r, g, b) = food_colour(food.kind());
+    let alpha = match food.ttl() {
+        Some(ttl) => ttl.saturating_mul(15).saturating_add(100).min(255) as u8,
+        None => 255,
+    };
 
-    // Create the food mesh
-    let food_rect = Rect::new(x, y, w, h);
-    let food_mesh = Mesh::new_rectangle(
-        ctx,
+    builder.rectangle(
         graphics::DrawMode::fill(),
-        food_rect,
-        Color::from_rgba(100, 255, 100, 255),
-    )
-    .expect("Error creating food_mesh!");
+        Rect::new(x, y, w, h),
+        Color::from_rgba(r, g, b, alpha),
+    )?;
 
-    graphics::draw(ctx, &food_mesh, graphics::DrawParam::default())
-        .expect("Error drawing food_mesh!");
+    Ok(())
 }
 
-/// Render the Snek
-fn draw_snek(ctx: &mut Context, snek: &Snek, grid: &Grid) {
+/// Get the colour each kind of Food is drawn in
+pub(crate) fn food_colour(kind: FoodKind) -> Rgb {
+    match kind {
+        FoodKind::Normal => (100, 255, 100),
+        FoodKind::Bonus => (50, 150, 255),
+        FoodKind::Poison => (150, 0, 200),
+        FoodKind::Speed => (255, 255, 255),
+        FoodKind::Golden => (255, 215, 0),
+    }
+}
+
+/// Add the Snek to the mesh using the specified (body, head) colours
+/// The head and tail are drawn the specified fraction of the way along from where they were before the last step, so the Snek slides between squares rather than jumping
+fn add_snek(
+    ctx: &mut Context,
+    builder: &mut MeshBuilder,
+    snek: &Snek,
+    (old_tail, old_head): (Vec2, Vec2),
+    alpha: f32,
+    grid: &Grid,
+    (body, head): (Rgb, Rgb),
+) -> GameResult {
     // How big should the each Snek part appear
     let (w, h) = pixels_per_grid_square(ctx, grid);
 
-    // Generate a vector of meshes
-    let mut meshes: Vec<Mesh> = snek
-        .parts()
-        .iter()
-        .map(|square| {
-            let (x, y) = grid_position_to_pixels(ctx, &square, grid);
-            let rect = Rect::new(x, y, w, h);
-            Mesh::new_rectangle(
-                ctx,
-                graphics::DrawMode::fill(),
-                rect,
-                Color::from_rgba(255, 50, 50, 255),
-            )
-            .expect("Error creating one of the meshes for the Snek!")
-        })
-        .collect();
-
-    // Change the colour of the head
-    let head = snek.head();
-    let (x, y) = grid_position_to_pixels(ctx, head, grid);
-    let head_rect = Rect::new(x, y, w, h);
-    let head_mesh = Mesh::new_rectangle(
+    // The tail slides out of the square it just left, and the head slides into the square it just entered
+    let parts = snek.parts();
+    let mut squares = vec![interpolated_position_to_pixels(
+        ctx, &old_tail, &parts[0], alpha, grid,
+    )];
+    for square in parts.iter().take(parts.len() - 1) {
+        squares.push(grid_position_to_pixels(ctx, square, grid));
+    }
+    squares.push(interpolated_position_to_pixels(
         ctx,
-        graphics::DrawMode::fill(),
-        head_rect,
-        Color::from_rgba(50, 50, 200, 255),
-    )
-    .expect("Error creating one of the meshes for the Snek!");
-    *meshes.last_mut().unwrap() = head_mesh;
-
-    // Draw the meshes
-    for mesh in meshes {
-        graphics::draw(ctx, &mesh, graphics::DrawParam::default()).expect("Error drawing mesh!");
+        &old_head,
+        snek.head(),
+        alpha,
+        grid,
+    ));
+
+    // The head is drawn last, in a different colour, so it always ends up on top
+    let last = squares.len() - 1;
+    for (idx, (x, y)) in squares.into_iter().enumerate() {
+        let (r, g, b) = if idx == last { head } else { body };
+        builder.rectangle(
+            graphics::DrawMode::fill(),
+            Rect::new(x, y, w, h),
+            Color::from_rgba(r, g, b, 255),
+        )?;
     }
 
-    println!("Snek: {:?}", snek);
+    log::trace!("Snek: {:?}", snek);
+
+    Ok(())
 }
 
-/// Render the current frames per second, elapsed time, and score
-fn draw_stats(ctx: &mut Context, score: usize) {
+/// Render the current frames per second, elapsed time, game speed, and the score of each player
+fn draw_stats(ctx: &mut Context, scores: &[usize], tick_rate: u32) {
     // Show the stats
     let fps = format!("[fps: {}]", ggez::timer::fps(ctx) as i64);
     let time = format!(
         "[t: {:.1}]",
         ggez::timer::duration_to_f64(ggez::timer::time_since_start(ctx))
     );
-    let score_text = format!("[score: {}]", score);
-    let debug_text = graphics::Text::new(format!("{}{}{}", fps, time, score_text));
-
-    let params = graphics::DrawParam::default()
-        .dest([20.0, SCREEN_HEIGHT - 20.0 - debug_text.height(ctx) as f32]);
+    let score_text = match scores {
+        [score] => format!("[score: {}]", score),
+        _ => scores
+            .iter()
+            .enumerate()
+            .map(|(idx, score)| format!("[P{}: {}]", idx + 1, score))
+            .collect(),
+    };
+    let speed = format!("[speed: {}]", tick_rate);
+    let debug_text = graphics::Text::new(format!("{}{}{}{}", fps, time, speed, score_text));
+
+    let (_, screen_height) = graphics::drawable_size(ctx);
+    let params =
+        graphics::DrawParam::default().dest([20.0, screen_height - 20.0 - debug_text.height(ctx)]);
 
     graphics::draw(ctx, &debug_text, params).expect("Error drawing debug text!");
 }
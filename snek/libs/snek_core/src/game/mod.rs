@@ -1,3 +1,6 @@
 pub mod engine;
 pub mod mode;
+pub mod scene;
+pub mod snapshot;
 pub mod state;
+pub mod terminal;
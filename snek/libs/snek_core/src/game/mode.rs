@@ -3,5 +3,16 @@ use crate::players::*;
 #[derive(Debug)]
 pub enum Mode {
     OnePlayer(Player),
+    MultiPlayer(&'static [Player]),
     TrainAi(Player),
 }
+
+impl Mode {
+    /// Get the players taking part in this mode, in order
+    pub fn players(&self) -> &[Player] {
+        match self {
+            Mode::OnePlayer(player) | Mode::TrainAi(player) => std::slice::from_ref(player),
+            Mode::MultiPlayer(players) => players,
+        }
+    }
+}
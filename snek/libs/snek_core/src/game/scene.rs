@@ -0,0 +1,684 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use ggez::input::keyboard::KeyCode;
+use ggez::GameResult;
+use rand::RngCore;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::players::human::input::{Input, InputState};
+use crate::players::human::player::{Control, Controls};
+use crate::players::{Move, Player};
+use crate::scores::{self, HighScore, HighScores};
+use crate::settings::MENU;
+
+use super::engine::Renderer;
+use super::mode::Mode;
+use super::snapshot::Snapshot;
+use super::state::GameState;
+
+/// The different screens the game can be showing
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Scene {
+    /// The title menu, where the game mode is picked
+    Menu,
+    /// A game in progress
+    Playing,
+    /// A game in progress, frozen until it's resumed
+    Paused,
+    /// A finished game, showing how everyone got on
+    GameOver,
+    /// Changing which keys and buttons each player uses
+    Controls,
+}
+
+/// Where the Controls screen is up to
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Rebinding {
+    /// Whose controls are being shown
+    pub player: usize,
+    /// The index of the highlighted Control in `Control::ALL`
+    pub control: usize,
+    /// Whether the next key or button pressed gets bound to the highlighted Control
+    pub listening: bool,
+}
+
+/// Moves between the title menu, the game itself, the pause screen and the game over screen
+/// New games are made by the specified function, so that the app gets to decide who the players are (and hand out the Controls)
+pub struct Scenes<R, M, F>
+where
+    R: RngCore,
+    M: Move,
+    F: FnMut(&Mode, &[Controls]) -> GameResult<GameState<R, M>>,
+{
+    scene: Scene,
+    /// The index of the highlighted entry in the title menu
+    selected: usize,
+    new_game: F,
+    state: Option<GameState<R, M>>,
+    high_scores: HighScores,
+    /// Where the scores from the last game ended up in the high score table
+    new_high_scores: Vec<usize>,
+    /// Where to save a replay of each game the first player plays by hand, if anywhere
+    replay_path: Option<PathBuf>,
+    inputs: InputState,
+    /// The Controls for each human player, in the order they're handed out
+    controls: Vec<Controls>,
+    /// Where to save the Controls whenever they're changed, if anywhere
+    controls_path: Option<PathBuf>,
+    /// Where F5 saves the game in progress and F9 loads it back from, if anywhere
+    quick_save_path: Option<PathBuf>,
+    rebinding: Rebinding,
+    renderer: Renderer,
+}
+
+impl<R, M, F> Scenes<R, M, F>
+where
+    R: RngCore,
+    M: Move,
+    F: FnMut(&Mode, &[Controls]) -> GameResult<GameState<R, M>>,
+{
+    /// Start off at the title menu
+    pub fn new(new_game: F) -> Self {
+        Self {
+            scene: Scene::Menu,
+            selected: 0,
+            new_game,
+            state: None,
+            high_scores: HighScores::default(),
+            new_high_scores: Vec::new(),
+            replay_path: None,
+            inputs: InputState::default(),
+            controls: Controls::presets(),
+            controls_path: None,
+            quick_save_path: None,
+            rebinding: Rebinding::default(),
+            renderer: Renderer::default(),
+        }
+    }
+
+    /// Keep track of the best scores in the specified table, rather than one that's forgotten when the game closes
+    pub fn with_high_scores(mut self, high_scores: HighScores) -> Self {
+        self.high_scores = high_scores;
+        self
+    }
+
+    /// Save a replay of each game the first player plays by hand to the specified file, replacing the last one
+    pub fn with_replay_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.replay_path = Some(path.into());
+        self
+    }
+
+    /// Hand out the specified Controls to the human players, rather than the built-in ones
+    pub fn with_controls(mut self, controls: Vec<Controls>) -> Self {
+        assert!(
+            !controls.is_empty(),
+            "There must be at least one set of controls"
+        );
+        self.controls = controls;
+        self
+    }
+
+    /// Save the Controls to the specified file whenever they're changed
+    pub fn with_controls_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.controls_path = Some(path.into());
+        self
+    }
+
+    /// Let the game in progress be saved to (and loaded back from) the specified file with F5 and F9
+    pub fn with_quick_save_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.quick_save_path = Some(path.into());
+        self
+    }
+
+    pub fn scene(&self) -> Scene {
+        self.scene
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// Get an immutable ref to the current game, if there is one
+    pub fn state(&self) -> Option<&GameState<R, M>> {
+        self.state.as_ref()
+    }
+
+    /// Get an immutable ref to the high score table
+    pub fn high_scores(&self) -> &HighScores {
+        &self.high_scores
+    }
+
+    /// Get where the scores from the last game ended up in the high score table
+    pub fn new_high_scores(&self) -> &[usize] {
+        &self.new_high_scores
+    }
+
+    /// Get the Controls for each human player
+    pub fn controls(&self) -> &[Controls] {
+        &self.controls
+    }
+
+    /// Get where F5 saves the game in progress and F9 loads it from, if anywhere
+    pub fn quick_save_path(&self) -> Option<&Path> {
+        self.quick_save_path.as_deref()
+    }
+
+    /// Get where the Controls screen is up to
+    pub fn rebinding(&self) -> Rebinding {
+        self.rebinding
+    }
+
+    /// Get a mutable ref to the keys, buttons and sticks being held down, so that gamepad events can be passed on
+    pub fn inputs_mut(&mut self) -> &mut InputState {
+        &mut self.inputs
+    }
+
+    /// Get a mutable ref to the current game, if there is one
+    pub fn state_mut(&mut self) -> Option<&mut GameState<R, M>> {
+        self.state.as_mut()
+    }
+
+    /// Get the current game along with the Renderer, so the game can be drawn
+    pub(crate) fn state_and_renderer(&mut self) -> (Option<&GameState<R, M>>, &mut Renderer) {
+        (self.state.as_ref(), &mut self.renderer)
+    }
+
+    /// Move the highlight up (negative) or down (positive) the title menu, wrapping around at either end
+    pub fn move_selection(&mut self, delta: i32) {
+        let len = MENU.len() as i32;
+        self.selected = (self.selected as i32 + delta).rem_euclid(len) as usize;
+    }
+
+    /// Start a new game in the mode highlighted in the title menu
+    pub fn start(&mut self) -> GameResult {
+        let (name, mode) = &MENU[self.selected];
+        log::warn!("Starting a new game: {}", name);
+
+        self.state = Some((self.new_game)(mode, &self.controls)?);
+        self.scene = Scene::Playing;
+        self.new_high_scores.clear();
+
+        Ok(())
+    }
+
+    /// Freeze the game if it's running, or carry on if it's frozen
+    pub fn toggle_pause(&mut self) {
+        self.scene = match self.scene {
+            Scene::Playing => Scene::Paused,
+            Scene::Paused => Scene::Playing,
+            scene => scene,
+        };
+    }
+
+    /// Throw away the current game and go back to the title menu
+    pub fn to_menu(&mut self) {
+        self.state = None;
+        self.scene = Scene::Menu;
+    }
+
+    /// Ask the players for their next moves, if a game is running, with the specified keys held down (along with any gamepad buttons)
+    pub fn poll_players(&mut self, keys: &HashSet<KeyCode>) {
+        self.inputs.set_keys(keys);
+
+        if let (Scene::Playing, Some(state)) = (self.scene, &mut self.state) {
+            state.poll_players(self.inputs.held());
+        }
+    }
+
+    /// Move the game forward one frame, if it's running, and show the game over screen once it ends
+    pub fn step(&mut self) {
+        if let (Scene::Playing, Some(state)) = (self.scene, &mut self.state) {
+            state.step();
+
+            if !state.play {
+                log::warn!("{}", state);
+                self.scene = Scene::GameOver;
+                self.record_high_scores();
+                self.save_replay();
+            }
+        }
+    }
+
+    /// Show the Controls screen, starting with the first player's
+    pub fn open_controls(&mut self) {
+        self.rebinding = Rebinding::default();
+        self.scene = Scene::Controls;
+    }
+
+    /// Go back to the title menu from the Controls screen, saving any changes
+    pub fn close_controls(&mut self) {
+        if let Some(path) = &self.controls_path {
+            if let Err(e) = Controls::save_all(path, &self.controls) {
+                log::warn!("Couldn't save the controls: {}", e);
+            }
+        }
+
+        self.scene = Scene::Menu;
+    }
+
+    /// React to a key, button or stick being pressed - on the Controls screen, this might be the new binding it's waiting for
+    /// Keys that can't be written down by name are ignored, so that the saved Controls can always be read back in
+    pub fn input_pressed(&mut self, input: Input) {
+        if self.scene != Scene::Controls || !self.rebinding.listening {
+            return;
+        }
+
+        if input.to_string().parse() == Ok(input) {
+            let control = Control::ALL[self.rebinding.control];
+            self.controls[self.rebinding.player].bind(control, input);
+            self.rebinding.listening = false;
+        }
+    }
+
+    /// Find your way around the Controls screen
+    fn controls_key_pressed(&mut self, key: KeyCode) {
+        let Rebinding {
+            player,
+            control,
+            listening,
+        } = self.rebinding;
+        let wrap = |idx: usize, delta: i32, len: usize| {
+            (idx as i32 + delta).rem_euclid(len as i32) as usize
+        };
+
+        match (listening, key) {
+            (true, KeyCode::Escape) => self.rebinding.listening = false,
+            (true, key) => self.input_pressed(Input::Key(key)),
+
+            (false, KeyCode::Up) | (false, KeyCode::W) => {
+                self.rebinding.control = wrap(control, -1, Control::ALL.len())
+            }
+            (false, KeyCode::Down) | (false, KeyCode::S) => {
+                self.rebinding.control = wrap(control, 1, Control::ALL.len())
+            }
+            (false, KeyCode::Left) | (false, KeyCode::A) => {
+                self.rebinding.player = wrap(player, -1, self.controls.len())
+            }
+            (false, KeyCode::Right) | (false, KeyCode::D) => {
+                self.rebinding.player = wrap(player, 1, self.controls.len())
+            }
+            (false, KeyCode::Return) | (false, KeyCode::Space) => self.rebinding.listening = true,
+            (false, KeyCode::Back) => self.controls[player].clear(Control::ALL[control]),
+            (false, KeyCode::Escape) => self.close_controls(),
+            _ => {}
+        }
+    }
+
+    /// Put the score of every player from the game that just ended into the high score table, if they're good enough, and save it
+    fn record_high_scores(&mut self) {
+        let state = match &self.state {
+            Some(state) => state,
+            None => return,
+        };
+
+        let (_, mode) = &MENU[self.selected];
+        let players = mode.players();
+        let date = chrono::Utc::now();
+        let rules = state.rules();
+
+        let mut entries: Vec<HighScore> = state
+            .sneks
+            .iter()
+            .enumerate()
+            .filter(|(_, snek)| snek.score() > 0)
+            .map(|(idx, snek)| {
+                let name = match players.get(idx) {
+                    Some(Player::Computer) => "Computer".to_owned(),
+                    _ => scores::player_name(),
+                };
+                let name = match players.len() {
+                    1 => name,
+                    _ => format!("{} (P{})", name, idx + 1),
+                };
+
+                HighScore {
+                    name,
+                    score: snek.score(),
+                    width: state.grid.width(),
+                    height: state.grid.height(),
+                    rules: rules.clone(),
+                    date,
+                }
+            })
+            .collect();
+
+        // Put the best scores in first, so that the later ones can't push them down the table
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.score));
+        self.new_high_scores = entries
+            .into_iter()
+            .filter_map(|entry| self.high_scores.insert(entry))
+            .collect();
+
+        if !self.new_high_scores.is_empty() {
+            if let Err(e) = self.high_scores.save() {
+                log::warn!("Couldn't save the high scores: {}", e);
+            }
+        }
+    }
+
+    /// Save a replay of the game that just ended, as long as the first player was a human
+    fn save_replay(&self) {
+        let (path, state) = match (&self.replay_path, &self.state) {
+            (Some(path), Some(state)) => (path, state),
+            _ => return,
+        };
+
+        let (_, mode) = &MENU[self.selected];
+        if !matches!(mode.players().first(), Some(Player::Human)) {
+            return;
+        }
+
+        if let Err(e) = state.replays[0].save(path) {
+            log::warn!("Couldn't save the replay: {}", e);
+        }
+    }
+
+    /// Speed the current game up (or slow it down, if negative)
+    fn adjust_speed(&mut self, delta: i32) {
+        if let Some(state) = &mut self.state {
+            state.adjust_speed(delta);
+        }
+    }
+}
+
+impl<R, M, F> Scenes<R, M, F>
+where
+    R: RngCore + Clone + Serialize + DeserializeOwned,
+    M: Move,
+    F: FnMut(&Mode, &[Controls]) -> GameResult<GameState<R, M>>,
+{
+    /// Save a Snapshot of the game in progress, replacing the last one
+    pub fn quick_save(&self) {
+        let (path, state) = match (&self.quick_save_path, &self.state) {
+            (Some(path), Some(state)) => (path, state),
+            _ => return,
+        };
+
+        if let Err(e) = state.snapshot().save(path) {
+            log::warn!("Couldn't quick save the game: {}", e);
+        }
+    }
+
+    /// Carry on from the last quick save, with the current players taking over the Sneks
+    pub fn quick_load(&mut self) {
+        let (path, state) = match (&self.quick_save_path, &mut self.state) {
+            (Some(path), Some(state)) => (path, state),
+            _ => return,
+        };
+
+        let snapshot = match Snapshot::load(path) {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                log::warn!("Couldn't load the quick save: {}", e);
+                return;
+            }
+        };
+
+        // The players can only take over a game with the same number of Sneks
+        if snapshot.sneks.len() != state.players.len() {
+            log::warn!(
+                "The quick save has {} Sneks, but this game has {} players",
+                snapshot.sneks.len(),
+                state.players.len()
+            );
+            return;
+        }
+
+        let players = std::mem::take(&mut state.players);
+        match snapshot.restore(players) {
+            Ok(loaded) => *state = loaded,
+            Err(e) => log::warn!("Couldn't load the quick save: {}", e),
+        }
+    }
+
+    /// React to a key being pressed, returning true if the player has asked to quit
+    pub fn key_pressed(&mut self, key: KeyCode) -> GameResult<bool> {
+        if self.scene == Scene::Controls {
+            self.controls_key_pressed(key);
+            return Ok(false);
+        }
+
+        match (self.scene, key) {
+            (Scene::Menu, KeyCode::Up) | (Scene::Menu, KeyCode::W) => self.move_selection(-1),
+            (Scene::Menu, KeyCode::Down) | (Scene::Menu, KeyCode::S) => self.move_selection(1),
+            (Scene::Menu, KeyCode::Return) | (Scene::Menu, KeyCode::Space) => self.start()?,
+            (Scene::Menu, KeyCode::C) => self.open_controls(),
+            (Scene::Menu, KeyCode::Escape) | (Scene::Menu, KeyCode::Q) => return Ok(true),
+
+            (Scene::Playing, KeyCode::P) | (Scene::Playing, KeyCode::Escape) => self.toggle_pause(),
+            (Scene::Playing, KeyCode::Equals)
+            | (Scene::Playing, KeyCode::Plus)
+            | (Scene::Playing, KeyCode::NumpadAdd) => self.adjust_speed(1),
+            (Scene::Playing, KeyCode::Minus) | (Scene::Playing, KeyCode::NumpadSubtract) => {
+                self.adjust_speed(-1)
+            }
+            (Scene::Playing, KeyCode::F5) | (Scene::Paused, KeyCode::F5) => self.quick_save(),
+            (Scene::Playing, KeyCode::F9) | (Scene::Paused, KeyCode::F9) => self.quick_load(),
+
+            (Scene::Paused, KeyCode::P) | (Scene::Paused, KeyCode::Escape) => self.toggle_pause(),
+            (Scene::Paused, KeyCode::R) => self.start()?,
+            (Scene::Paused, KeyCode::M) => self.to_menu(),
+            (Scene::Paused, KeyCode::Q) => return Ok(true),
+
+            (Scene::GameOver, KeyCode::R) | (Scene::GameOver, KeyCode::Return) => self.start()?,
+            (Scene::GameOver, KeyCode::M) | (Scene::GameOver, KeyCode::Escape) => self.to_menu(),
+            (Scene::GameOver, KeyCode::Q) => return Ok(true),
+
+            _ => {}
+        }
+
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ggez::input::gamepad::gilrs::Button;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::entities::{food::Food, grid::Grid};
+    use crate::game::state::Death;
+    use crate::players::{human::player::HumanPlayer, replay::Replay};
+    use crate::types::{Direction, Vec2};
+
+    use super::*;
+
+    type NewGame = fn(&Mode, &[Controls]) -> GameResult<GameState<ChaCha8Rng, HumanPlayer>>;
+
+    /// Every game is a single Snek on a small Grid, whatever the mode
+    fn scenes() -> Scenes<ChaCha8Rng, HumanPlayer, NewGame> {
+        Scenes::new(|_: &Mode, controls: &[Controls]| {
+            let prng = ChaCha8Rng::from_seed(Default::default());
+            let grid = Grid::new(5, 5).unwrap();
+            let player = HumanPlayer::with_controls(controls[0].clone());
+            Ok(GameState::new(grid, prng, player))
+        })
+    }
+
+    #[test]
+    fn menu_selection() {
+        let mut scenes = scenes();
+        assert_eq!(scenes.scene(), Scene::Menu);
+        assert!(scenes.state().is_none());
+
+        // The highlight wraps around at either end
+        scenes.key_pressed(KeyCode::Up).unwrap();
+        assert_eq!(scenes.selected(), MENU.len() - 1);
+        scenes.key_pressed(KeyCode::Down).unwrap();
+        assert_eq!(scenes.selected(), 0);
+
+        assert!(scenes.key_pressed(KeyCode::Escape).unwrap());
+    }
+
+    #[test]
+    fn pause_and_resume() {
+        let mut scenes = scenes();
+        scenes.key_pressed(KeyCode::Return).unwrap();
+        assert_eq!(scenes.scene(), Scene::Playing);
+
+        // Nothing moves while the game is paused
+        scenes.key_pressed(KeyCode::P).unwrap();
+        assert_eq!(scenes.scene(), Scene::Paused);
+        scenes.step();
+        assert_eq!(scenes.state().unwrap().sneks[0].head(), &Vec2::new(0, 0));
+
+        scenes.key_pressed(KeyCode::Escape).unwrap();
+        assert_eq!(scenes.scene(), Scene::Playing);
+        scenes.step();
+        assert_ne!(scenes.state().unwrap().sneks[0].head(), &Vec2::new(0, 0));
+
+        // Back to the menu throws the game away
+        scenes.toggle_pause();
+        scenes.key_pressed(KeyCode::M).unwrap();
+        assert_eq!(scenes.scene(), Scene::Menu);
+        assert!(scenes.state().is_none());
+    }
+
+    #[test]
+    fn game_over_and_restart() {
+        let mut scenes = scenes();
+        scenes.start().unwrap();
+
+        // Drive the Snek into a wall
+        let state = scenes.state_mut().unwrap();
+        state.grid.add_wall(&Vec2::new(1, 0));
+        state.sneks[0].set_orientation(Direction::Right);
+        scenes.step();
+
+        assert_eq!(scenes.scene(), Scene::GameOver);
+        assert_eq!(scenes.state().unwrap().deaths, vec![Some(Death::Wall)]);
+
+        // A game without any points doesn't make the high score table
+        assert!(scenes.high_scores().entries().is_empty());
+        assert!(scenes.new_high_scores().is_empty());
+
+        // Restarting brings back a fresh game, without the wall
+        scenes.key_pressed(KeyCode::R).unwrap();
+        assert_eq!(scenes.scene(), Scene::Playing);
+        let state = scenes.state().unwrap();
+        assert!(state.play);
+        assert_eq!(state.deaths, vec![None]);
+        assert_eq!(state.grid.wall_count(), 0);
+    }
+
+    #[test]
+    fn high_scores() {
+        let mut scenes = scenes();
+        scenes.start().unwrap();
+
+        // Eat some Food, then drive into a wall
+        let state = scenes.state_mut().unwrap();
+        state.food_config.special_chance = 0.0;
+        state.food = vec![Food::new(Vec2::new(1, 0))];
+        state.grid.add_wall(&Vec2::new(2, 0));
+        state.sneks[0].set_orientation(Direction::Right);
+        scenes.step();
+        scenes.step();
+
+        assert_eq!(scenes.scene(), Scene::GameOver);
+        assert_eq!(scenes.new_high_scores(), &[0]);
+        let entry = &scenes.high_scores().entries()[0];
+        assert_eq!(entry.score, 1);
+        assert_eq!((entry.width, entry.height), (5, 5));
+        assert!(entry.rules.contains("walls: 1"));
+
+        // Starting again clears the highlight, but keeps the table
+        scenes.start().unwrap();
+        assert!(scenes.new_high_scores().is_empty());
+        assert_eq!(scenes.high_scores().entries().len(), 1);
+    }
+
+    #[test]
+    fn saves_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("last_game.toml");
+        let mut scenes = scenes().with_replay_path(&path);
+        scenes.start().unwrap();
+
+        // Drive into a wall, and the way there is saved once the game is over
+        let state = scenes.state_mut().unwrap();
+        state.grid.add_wall(&Vec2::new(2, 0));
+        state.sneks[0].set_orientation(Direction::Right);
+        scenes.step();
+        assert!(!path.exists());
+        scenes.step();
+
+        assert_eq!(scenes.scene(), Scene::GameOver);
+        let replay = Replay::load(&path).unwrap();
+        assert_eq!(replay.moves(), &[Direction::Right, Direction::Right]);
+    }
+
+    #[test]
+    fn quick_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("quick_save.json");
+        let mut scenes = scenes().with_quick_save_path(&path);
+        scenes.start().unwrap();
+        scenes.step();
+
+        let saved = scenes.state().unwrap().hash();
+        scenes.key_pressed(KeyCode::F5).unwrap();
+        assert!(path.exists());
+
+        // Play on a bit, then go back to where the game was saved
+        scenes.step();
+        assert_ne!(scenes.state().unwrap().hash(), saved);
+        scenes.key_pressed(KeyCode::P).unwrap();
+        scenes.key_pressed(KeyCode::F9).unwrap();
+        assert_eq!(scenes.scene(), Scene::Paused);
+        assert_eq!(scenes.state().unwrap().hash(), saved);
+        assert_eq!(scenes.state().unwrap().players.len(), 1);
+
+        // A quick save with the wrong number of Sneks is left alone
+        let mut snapshot = scenes.state().unwrap().snapshot();
+        snapshot.sneks.push(snapshot.sneks[0].clone());
+        snapshot.previous_ends.push(snapshot.previous_ends[0]);
+        snapshot.deaths.push(None);
+        snapshot.replays.push(Replay::default());
+        snapshot.save(&path).unwrap();
+        scenes.key_pressed(KeyCode::F9).unwrap();
+        assert_eq!(scenes.state().unwrap().hash(), saved);
+    }
+
+    #[test]
+    fn rebinding_controls() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("controls.toml");
+        let mut scenes = scenes().with_controls_path(&path);
+
+        scenes.key_pressed(KeyCode::C).unwrap();
+        assert_eq!(scenes.scene(), Scene::Controls);
+
+        // Go down to Right, clear it, then bind a key and a gamepad button to it
+        scenes.key_pressed(KeyCode::Down).unwrap();
+        scenes.key_pressed(KeyCode::Back).unwrap();
+        assert!(scenes.controls()[0].bindings(Control::Right).is_empty());
+
+        scenes.key_pressed(KeyCode::Return).unwrap();
+        assert!(scenes.rebinding().listening);
+        scenes.key_pressed(KeyCode::L).unwrap();
+        scenes.input_pressed(Input::Button(0, Button::East));
+        scenes.key_pressed(KeyCode::Return).unwrap();
+        scenes.input_pressed(Input::Button(0, Button::East));
+
+        let expected = [Input::Key(KeyCode::L), Input::Button(0, Button::East)];
+        assert_eq!(scenes.controls()[0].bindings(Control::Right), &expected);
+
+        // Flip through to the next player's controls and back, then leave and save
+        scenes.key_pressed(KeyCode::Right).unwrap();
+        assert_eq!(scenes.rebinding().player, 1);
+        scenes.key_pressed(KeyCode::Left).unwrap();
+        scenes.key_pressed(KeyCode::Escape).unwrap();
+        assert_eq!(scenes.scene(), Scene::Menu);
+        assert_eq!(Controls::load_all(&path).unwrap(), scenes.controls());
+
+        // New games get the new controls
+        scenes.start().unwrap();
+        scenes.poll_players(&vec![KeyCode::L].into_iter().collect());
+        scenes.step();
+        assert_eq!(scenes.state().unwrap().sneks[0].head(), &Vec2::new(1, 0));
+    }
+}
@@ -0,0 +1,298 @@
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use rand::RngCore;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::entities::{food::Food, grid::Grid, snek::Snek};
+use crate::players::{replay::Replay, Move};
+use crate::scores;
+use crate::settings::{FoodConfig, Rules, SpeedConfig};
+use crate::types::Vec2;
+
+use super::state::{Death, GameState};
+
+/// The name of the file quick saves are kept in, inside the game's data directory
+const FILE_NAME: &str = "quick_save.json";
+
+/// Everything about a game apart from the players, so that it can be saved part way through and carried on later, sent to another peer, or used to set up a test
+/// The prng is saved too, so a game carried on from a Snapshot plays out exactly the same as the original would have
+/// Snapshots are written as JSON, as the prng's position doesn't fit in a TOML number
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot<R> {
+    pub grid: Grid,
+    pub sneks: Vec<Snek>,
+    pub food: Vec<Food>,
+    pub food_config: FoodConfig,
+    pub speed: SpeedConfig,
+    pub prng: R,
+    pub seed: Option<u64>,
+    pub play: bool,
+    pub won: bool,
+    pub boost: u32,
+    pub previous_ends: Vec<(Vec2, Vec2)>,
+    pub deaths: Vec<Option<Death>>,
+    pub replays: Vec<Replay>,
+}
+
+impl<R, M> GameState<R, M>
+where
+    R: RngCore + Clone,
+    M: Move,
+{
+    /// Take a copy of everything in the game apart from the players
+    pub fn snapshot(&self) -> Snapshot<R> {
+        Snapshot {
+            grid: self.grid.clone(),
+            sneks: self.sneks.clone(),
+            food: self.food.clone(),
+            food_config: self.food_config.clone(),
+            speed: self.speed.clone(),
+            prng: self.prng.clone(),
+            seed: self.seed,
+            play: self.play,
+            won: self.won,
+            boost: self.boost,
+            previous_ends: self.previous_ends.clone(),
+            deaths: self.deaths.clone(),
+            replays: self.replays.clone(),
+        }
+    }
+}
+
+impl<R> Snapshot<R> {
+    /// Where the quick save lives by default, following the conventions of the current platform
+    pub fn default_path() -> Option<PathBuf> {
+        scores::data_dir().map(|dir| dir.join(FILE_NAME))
+    }
+
+    /// Make sure everything in the Snapshot fits together - one of everything for each Snek, nothing off the Grid, and rules that make sense
+    fn check(&self) -> Result<(), String> {
+        let count = self.sneks.len();
+        if count == 0 {
+            return Err("there aren't any Sneks".to_owned());
+        }
+        if self.previous_ends.len() != count
+            || self.deaths.len() != count
+            || self.replays.len() != count
+        {
+            return Err(format!(
+                "there should be one of everything for each of the {} Sneks",
+                count
+            ));
+        }
+
+        if let Some(snek) = self
+            .sneks
+            .iter()
+            .find(|snek| snek.occupied().bounds() != self.grid.bounds())
+        {
+            return Err(format!(
+                "the Snek at {:?} is on a different sized Grid",
+                snek.head()
+            ));
+        }
+        if let Some(food) = self
+            .food
+            .iter()
+            .find(|food| !self.grid.contains(food.pos()))
+        {
+            return Err(format!("the Food at {:?} is off the Grid", food.pos()));
+        }
+
+        // The rules have to make as much sense as ones loaded from a config file or a level
+        Rules {
+            food: self.food_config.clone(),
+            speed: self.speed.clone(),
+        }
+        .check()
+    }
+}
+
+impl<R> Snapshot<R>
+where
+    R: RngCore,
+{
+    /// Carry on the game from where the Snapshot left off, with the specified players taking over the Sneks in order
+    pub fn restore<M>(self, players: Vec<M>) -> std::io::Result<GameState<R, M>>
+    where
+        M: Move,
+    {
+        let invalid = |msg: String| std::io::Error::new(ErrorKind::InvalidData, msg);
+
+        self.check().map_err(invalid)?;
+        if players.len() != self.sneks.len() {
+            return Err(invalid(format!(
+                "there are {} Sneks but {} players to take them over",
+                self.sneks.len(),
+                players.len()
+            )));
+        }
+
+        Ok(GameState {
+            sneks: self.sneks,
+            food: self.food,
+            food_config: self.food_config,
+            grid: self.grid,
+            prng: self.prng,
+            seed: self.seed,
+            play: self.play,
+            won: self.won,
+            boost: self.boost,
+            speed: self.speed,
+            previous_ends: self.previous_ends,
+            deaths: self.deaths,
+            replays: self.replays,
+            players,
+        })
+    }
+}
+
+impl<R> Snapshot<R>
+where
+    R: Serialize + DeserializeOwned,
+{
+    /// Write the Snapshot out as JSON
+    pub fn to_json(&self) -> std::io::Result<String> {
+        serde_json::to_string(self).map_err(|e| std::io::Error::new(ErrorKind::InvalidData, e))
+    }
+
+    /// Read a Snapshot from JSON, checking everything in it fits together
+    pub fn parse(contents: &str) -> std::io::Result<Self> {
+        let invalid = |msg: String| std::io::Error::new(ErrorKind::InvalidData, msg);
+
+        let snapshot: Self = serde_json::from_str(contents).map_err(|e| invalid(e.to_string()))?;
+        snapshot.check().map_err(invalid)?;
+
+        Ok(snapshot)
+    }
+
+    /// Load a Snapshot from the specified file
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Write the Snapshot out to the specified file
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        if let Some(dir) = path.as_ref().parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, self.to_json()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::entities::food::FoodKind;
+    use crate::players::bots::GreedyBot;
+
+    use super::*;
+
+    #[test]
+    fn carry_on_from_snapshot() {
+        let mut grid = Grid::new(12, 10).unwrap();
+        grid.add_wall(&Vec2::new(6, 5));
+        let players = vec![GreedyBot::new(), GreedyBot::new()];
+        let mut original = GameState::seeded(grid, 11, players);
+        let keys = HashSet::new();
+        for _ in 0..20 {
+            original.poll_players(&keys);
+            original.step();
+        }
+
+        // A game carried on from a Snapshot (even one that's been through JSON) keeps in step with the original
+        let json = original.snapshot().to_json().unwrap();
+        let players = vec![GreedyBot::new(), GreedyBot::new()];
+        let mut copy = Snapshot::<ChaCha8Rng>::parse(&json)
+            .unwrap()
+            .restore(players)
+            .unwrap();
+        assert_eq!(copy.hash(), original.hash());
+        assert_eq!(copy.grid, original.grid);
+        assert_eq!(copy.replays, original.replays);
+
+        for _ in 0..50 {
+            for state in [&mut original, &mut copy].iter_mut() {
+                state.poll_players(&keys);
+                state.step();
+            }
+            assert_eq!(copy.hash(), original.hash());
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(FILE_NAME);
+        copy.snapshot().save(&path).unwrap();
+        let loaded = Snapshot::<ChaCha8Rng>::load(&path).unwrap();
+        assert_eq!(
+            loaded
+                .restore(vec![GreedyBot::new(), GreedyBot::new()])
+                .unwrap()
+                .hash(),
+            copy.hash()
+        );
+    }
+
+    #[test]
+    fn snapshot_fixture() {
+        // A position written out by hand - a Snek heading right, just about to eat some Bonus Food
+        let json = r#"{
+            "grid": { "width": 5, "height": 5, "walls": [{ "x": 4, "y": 4 }] },
+            "sneks": [{
+                "parts": [{ "x": 0, "y": 2 }, { "x": 1, "y": 2 }, { "x": 2, "y": 2 }],
+                "bounds": { "x": 5, "y": 5 },
+                "orientation": "Right",
+                "alive": true,
+                "score": 4
+            }],
+            "food": [{ "pos": { "x": 3, "y": 2 }, "kind": "Bonus", "ttl": null }],
+            "food_config": {},
+            "speed": {},
+            "prng": { "seed": [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], "stream": 0, "word_pos": 0 },
+            "seed": null,
+            "play": true,
+            "won": false,
+            "boost": 0,
+            "previous_ends": [[{ "x": 0, "y": 2 }, { "x": 2, "y": 2 }]],
+            "deaths": [null],
+            "replays": [{ "moves": [], "seed": null }]
+        }"#;
+
+        let snapshot = Snapshot::<ChaCha8Rng>::parse(json).unwrap();
+        let mut state = snapshot.restore(vec![GreedyBot::new()]).unwrap();
+        assert!(state.grid.is_wall(&Vec2::new(4, 4)));
+        assert_eq!(state.food[0].kind(), FoodKind::Bonus);
+
+        state.step();
+        assert_eq!(state.sneks[0].head(), &Vec2::new(3, 2));
+        assert!(state.sneks[0].score() > 4);
+        assert!(state.sneks[0].len() > 2);
+
+        // Anything that doesn't fit together is turned away
+        let off_grid = json.replace(
+            r#""pos": { "x": 3, "y": 2 }"#,
+            r#""pos": { "x": 9, "y": 2 }"#,
+        );
+        assert!(Snapshot::<ChaCha8Rng>::parse(&off_grid).is_err());
+        let headless = json.replace(
+            r#"[{ "x": 0, "y": 2 }, { "x": 1, "y": 2 }, { "x": 2, "y": 2 }]"#,
+            "[]",
+        );
+        assert!(Snapshot::<ChaCha8Rng>::parse(&headless).is_err());
+        let no_deaths = json.replace(r#""deaths": [null]"#, r#""deaths": []"#);
+        assert!(Snapshot::<ChaCha8Rng>::parse(&no_deaths).is_err());
+        let bad_rules = json.replace(
+            r#""food_config": {}"#,
+            r#""food_config": { "special_chance": 2.0 }"#,
+        );
+        assert!(Snapshot::<ChaCha8Rng>::parse(&bad_rules).is_err());
+        assert!(Snapshot::<ChaCha8Rng>::parse(json)
+            .unwrap()
+            .restore(vec![GreedyBot::new(), GreedyBot::new()])
+            .is_err());
+    }
+}
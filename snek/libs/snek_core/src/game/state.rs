@@ -1,43 +1,158 @@
-use rand::{RngCore, SeedableRng};
+use std::collections::HashSet;
+
+use rand::{Rng, RngCore, SeedableRng};
 use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    entities::{food::Food, grid::Grid, snek::Snek},
-    players::{human::player::HumanPlayer, Move},
-    types::Vec2,
+    entities::{
+        food::{Food, FoodKind},
+        grid::Grid,
+        snek::Snek,
+    },
+    level::{Level, LevelError, Spawn, WALL},
+    players::{
+        human::{input::Input, player::HumanPlayer},
+        replay::Replay,
+        Move, View,
+    },
+    settings::{FoodConfig, SpeedConfig},
+    types::{Direction, Vec2},
 };
 
+/// The seed games are played on when nobody picks one
+pub const DEFAULT_SEED: u64 = 0;
+
+/// What took a Snek out of the game
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Death {
+    /// Ran into a wall
+    Wall,
+    /// Ran into its own body
+    Itself,
+    /// Ran into the body of the Snek with the specified index
+    Snek(usize),
+    /// Met the Snek with the specified index head-on
+    HeadOn(usize),
+}
+
+impl std::fmt::Display for Death {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Death::Wall => write!(f, "hit a wall"),
+            Death::Itself => write!(f, "ran into itself"),
+            Death::Snek(idx) => write!(f, "ran into P{}", idx + 1),
+            Death::HeadOn(idx) => write!(f, "crashed head-on into P{}", idx + 1),
+        }
+    }
+}
+
 pub struct GameState<R, M>
 where
     R: RngCore,
     M: Move,
 {
-    pub snek: Snek,
-    pub food: Food,
+    pub sneks: Vec<Snek>,
+    pub food: Vec<Food>,
+    pub food_config: FoodConfig,
     pub grid: Grid,
     pub prng: R,
+    /// The seed the prng was made from, if the game was set up with one, so that it can be played again
+    pub seed: Option<u64>,
     pub play: bool,
-    pub player: M,
+    /// Set when the Sneks have filled every square of the Grid, leaving nowhere for any more Food
+    pub won: bool,
+    /// How many more ticks the game will run at double speed, after someone ate Speed Food
+    pub boost: u32,
+    pub speed: SpeedConfig,
+    /// The (tail, head) of each Snek before the last step, so that renderers can slide the Sneks smoothly between steps
+    pub previous_ends: Vec<(Vec2, Vec2)>,
+    /// What took each Snek out of the game, if anything has yet
+    pub deaths: Vec<Option<Death>>,
+    /// The way each Snek went on every tick it was alive, so the game can be played back later
+    pub replays: Vec<Replay>,
+    pub players: Vec<M>,
 }
 
 impl Default for GameState<ChaCha8Rng, HumanPlayer> {
     fn default() -> Self {
-        let mut prng = ChaCha8Rng::from_seed(Default::default());
-
         let grid = Grid::default();
-        let snek = Snek::default();
-        let food = Food::random(grid.bounds(), &snek, &mut prng);
-
         let player = HumanPlayer::default();
 
-        Self {
-            snek,
-            food,
-            grid,
-            prng,
-            play: true,
-            player,
+        Self::seeded(grid, DEFAULT_SEED, vec![player])
+    }
+}
+
+impl<M> GameState<ChaCha8Rng, M>
+where
+    M: Move,
+{
+    /// Create a new GameState with a Snek for each of the specified players, with everything random drawn from the specified seed
+    pub fn seeded(grid: Grid, seed: u64, players: Vec<M>) -> Self {
+        let prng = ChaCha8Rng::seed_from_u64(seed);
+
+        Self::with_players(grid, prng, players).with_seed(seed)
+    }
+
+    /// Create a new GameState on the specified Level, with everything random drawn from the specified seed
+    pub fn seeded_level(level: &Level, seed: u64, players: Vec<M>) -> Result<Self, LevelError> {
+        let prng = ChaCha8Rng::seed_from_u64(seed);
+
+        Ok(Self::from_level(level, prng, players)?.with_seed(seed))
+    }
+
+    /// Remember the seed the prng was made from, in the game and in each of its Replays
+    fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        for replay in &mut self.replays {
+            replay.set_seed(seed);
         }
+        self
+    }
+
+    /// Boil everything the rest of the game depends on down to a single number, so that two copies of the same game can cheaply check they still agree
+    /// This covers the Grid, the Sneks, the Food and how far through its random numbers the prng has got, but not the record of how the game got there (deaths and replays)
+    pub fn hash(&self) -> u64 {
+        let mut fnv = Fnv::default();
+
+        let grid = &self.grid;
+        fnv.u32(grid.width() as u32)
+            .u32(grid.height() as u32)
+            .u8(grid.topology().kind() as u8)
+            .u32(grid.wall_count() as u32);
+        if grid.wall_count() > 0 {
+            for pos in grid.into_iter().filter(|pos| grid.is_wall(pos)) {
+                fnv.vec2(&pos);
+            }
+        }
+
+        fnv.u32(self.sneks.len() as u32);
+        for snek in &self.sneks {
+            fnv.u8(snek.is_alive() as u8)
+                .u64(snek.score() as u64)
+                .u8(*snek.orientation() as u8)
+                .u32(snek.parts().len() as u32);
+            for part in snek.parts() {
+                fnv.vec2(part);
+            }
+        }
+
+        fnv.u32(self.food.len() as u32);
+        for food in &self.food {
+            fnv.vec2(food.pos())
+                .u8(food.kind() as u8)
+                .u8(food.ttl().is_some() as u8)
+                .u32(food.ttl().unwrap_or(0));
+        }
+
+        fnv.u8(self.play as u8)
+            .u8(self.won as u8)
+            .u32(self.boost)
+            .bytes(&self.prng.get_seed())
+            .u64(self.prng.get_stream())
+            .u128(self.prng.get_word_pos());
+
+        fnv.0
     }
 }
 
@@ -52,23 +167,32 @@ where
             .grid
             .into_iter()
             .map(|pos| {
-                // Check if pos is part of the snake
-                if self.snek.head() == &pos {
-                    "#".to_owned()
-                } else if self.snek.parts().contains(&pos) {
-                    "0".to_owned()
-                } else if self.food.pos() == &pos {
-                    "%".to_owned()
-                } else {
-                    ".".to_owned()
+                // Check if pos is part of one of the living sneks - heads are drawn as a #, and bodies as the number of the player they belong to
+                let living = self.sneks.iter().enumerate().filter(|(_, s)| s.is_alive());
+
+                for (idx, snek) in living {
+                    if snek.head() == &pos {
+                        return "#".to_owned();
+                    } else if snek.touches(&pos) {
+                        return (idx % 10).to_string();
+                    }
+                }
+
+                if self.grid.is_wall(&pos) {
+                    return WALL.to_string();
+                }
+
+                match self.food.iter().find(|food| food.pos() == &pos) {
+                    Some(food) => food.kind().symbol().to_string(),
+                    None => ".".to_owned(),
                 }
             })
             .collect();
 
-        let mut output = String::with_capacity((self.grid.len() + self.grid.rows()) as usize);
+        let mut output = String::with_capacity(self.grid.len() + self.grid.rows());
         output.push('\n');
 
-        for chunk in tmp.chunks(self.grid.columns() as usize).rev() {
+        for chunk in tmp.chunks(self.grid.columns()).rev() {
             output = output + &chunk.concat() + "\n";
         }
 
@@ -81,51 +205,387 @@ where
     R: RngCore,
     M: Move,
 {
-    /// Create a new GameState
-    pub fn new(grid: Grid, mut prng: R, player: M) -> Self {
-        let snek = Snek::default();
-        let food = Food::random(grid.bounds(), &snek, &mut prng);
+    /// Create a new single player GameState
+    pub fn new(grid: Grid, prng: R, player: M) -> Self {
+        Self::with_players(grid, prng, vec![player])
+    }
+
+    /// Create a new GameState with a Snek for each of the specified players
+    pub fn with_players(grid: Grid, prng: R, players: Vec<M>) -> Self {
+        assert!(!players.is_empty());
+
+        let spawns = spawn_points(&grid, players.len());
+
+        Self::build(
+            grid,
+            prng,
+            players,
+            &spawns,
+            Vec::new(),
+            FoodConfig::default(),
+            SpeedConfig::default(),
+        )
+    }
+
+    /// Create a new GameState on the specified Level, with a Snek for each of the specified players
+    /// Fails if the Level doesn't have enough spawn points to go around
+    pub fn from_level(level: &Level, prng: R, players: Vec<M>) -> Result<Self, LevelError> {
+        assert!(!players.is_empty());
+
+        if players.len() > level.spawns.len() {
+            return Err(LevelError::NotEnoughSpawns {
+                spawns: level.spawns.len(),
+                players: players.len(),
+            });
+        }
+
+        let spawns = &level.spawns[..players.len()];
+
+        Ok(Self::build(
+            level.grid.clone(),
+            prng,
+            players,
+            spawns,
+            level.food.clone(),
+            level.food_config.clone(),
+            level.speed.clone(),
+        ))
+    }
+
+    /// Put a Snek at each of the spawn points, and make sure there's some Food to go after
+    fn build(
+        grid: Grid,
+        mut prng: R,
+        players: Vec<M>,
+        spawns: &[Spawn],
+        mut food: Vec<Food>,
+        food_config: FoodConfig,
+        speed: SpeedConfig,
+    ) -> Self {
+        let sneks = spawns
+            .iter()
+            .map(|spawn| {
+                let mut snek = Snek::new(spawn.pos, grid.bounds());
+                snek.set_orientation(spawn.orientation);
+                snek
+            })
+            .collect::<Vec<Snek>>();
+
+        // On a tiny enough Grid the Sneks might already fill every square
+        if !food.iter().any(|food| food.kind() == FoodKind::Normal) {
+            food.extend(Food::random(
+                FoodKind::Normal,
+                &grid,
+                &sneks,
+                &food,
+                &mut prng,
+            ));
+        }
+        let won = food.is_empty();
+        let previous_ends = ends(&sneks);
+        let deaths = vec![None; sneks.len()];
+        let replays = vec![Replay::default(); sneks.len()];
 
         Self {
-            snek,
+            sneks,
             food,
+            food_config,
             grid,
             prng,
-            play: true,
-            player,
+            seed: None,
+            play: !won,
+            won,
+            boost: 0,
+            speed,
+            previous_ends,
+            deaths,
+            replays,
+            players,
+        }
+    }
+
+    /// Is this a game with more than one Snek
+    pub fn is_multiplayer(&self) -> bool {
+        self.sneks.len() > 1
+    }
+
+    /// Get the score of each player - this is the number of points their Snek has earned from eating Food
+    pub fn scores(&self) -> Vec<usize> {
+        self.sneks.iter().map(|snek| snek.score()).collect()
+    }
+
+    /// Sum up the rules of the game in a few words, so that scores from different rules can be told apart
+    pub fn rules(&self) -> String {
+        format!(
+            "players: {}, walls: {}, speed: {}-{}, food: {}",
+            self.sneks.len(),
+            self.grid.wall_count(),
+            self.speed.base_rate,
+            self.speed.max_rate,
+            self.food_config.max_items
+        )
+    }
+
+    /// How many times per second the game should step
+    /// This goes up as the longest Snek grows, and doubles while boosted by Speed Food
+    pub fn tick_rate(&self) -> u32 {
+        let longest = self.sneks.iter().map(|snek| snek.len()).max().unwrap_or(0);
+        let ramp = match self.speed.ramp_every {
+            0 => 0,
+            every => (longest / every) as u32,
+        };
+
+        let rate = (self.speed.base_rate + ramp)
+            .min(self.speed.max_rate)
+            .max(1);
+
+        match self.boost {
+            0 => rate,
+            _ => rate * 2,
+        }
+    }
+
+    /// Speed the game up (or slow it down, if negative) by the specified number of ticks per second
+    pub fn adjust_speed(&mut self, delta: i32) {
+        let rate = (self.speed.base_rate as i32 + delta).max(1) as u32;
+
+        self.speed.base_rate = rate.min(self.speed.max_rate.max(1));
+        log::warn!("Base tick rate is now {}", self.speed.base_rate);
+    }
+
+    /// Ask each of the players still in the game for their next move, and turn their Sneks accordingly
+    pub fn poll_players(&mut self, keys: &HashSet<Input>) {
+        let topology = self.grid.topology();
+        for (idx, player) in self.players.iter_mut().enumerate() {
+            if !self.sneks[idx].is_alive() {
+                continue;
+            }
+
+            let view = View {
+                me: idx,
+                grid: &self.grid,
+                sneks: &self.sneks,
+                food: &self.food,
+                keys,
+            };
+
+            // Check to see if the player has made a new move the Grid allows, otherwise continue in the current direction
+            match player.make_move(&view) {
+                Some(new_orientation) if topology.allows(new_orientation) => {
+                    self.sneks[idx].set_orientation(new_orientation)
+                }
+                Some(new_orientation) => log::warn!(
+                    "Ignoring move {:?} from player {}, as the Grid doesn't allow it",
+                    new_orientation,
+                    idx
+                ),
+                None => {}
+            }
         }
     }
 
     /// Move the game forward one frame
     pub fn step(&mut self) {
-        //, orientation: Vec2) {
         // Only step if the game is still in play
         if self.play {
-            // First, update the Snek direction
-            //self.snek.set_orientation(orientation);
+            // First, remember where each Snek head was before moving, so we can spot Sneks trying to pass through each other
+            let old_heads: Vec<Vec2> = self.sneks.iter().map(|snek| *snek.head()).collect();
+            self.previous_ends = ends(&self.sneks);
+
+            // Second, advance the living Sneks, remembering which Food they ate
+            self.boost = self.boost.saturating_sub(1);
+            let mut eaten = Vec::new();
+            for (snek, replay) in self.sneks.iter_mut().zip(self.replays.iter_mut()) {
+                if !snek.is_alive() {
+                    continue;
+                }
+
+                replay.push(*snek.orientation());
+                if let Some(idx) = snek.advance(&self.food) {
+                    eaten.push(idx);
+                }
+            }
+
+            // Third, check for collisions and take any crashed Sneks out of the game
+            for (idx, death) in self.crashed(&old_heads) {
+                log::warn!("Snek {} {}!", idx, death);
+                self.sneks[idx].kill();
+                self.deaths[idx] = Some(death);
+            }
 
-            // Second, advance the Snek and reset the Food if it ate any
-            match self.snek.advance(self.grid.bounds(), &self.food.pos()) {
-                true => self.food = Food::random(self.grid.bounds(), &self.snek, &mut self.prng),
-                false => (),
+            // Fourth, clear away the Food that was eaten or has expired
+            if eaten
+                .iter()
+                .any(|&idx| self.food[idx].kind() == FoodKind::Speed)
+            {
+                self.boost = self.food_config.boost_length;
+            }
+            eaten.sort_unstable();
+            eaten.dedup();
+            for idx in eaten.into_iter().rev() {
+                self.food.remove(idx);
+            }
+            self.food.retain_mut(|food| food.age());
+
+            // Finally, top up the Food - if there's nowhere left to put it, the Sneks have filled the Grid and won the game
+            if !self.spawn_food() {
+                log::warn!("The Grid is full - you win!");
+                self.won = true;
+                self.play = false;
+                return;
             }
 
-            // Check for collisions with itself, and stop play if so
-            if self.snek.hit_self() {
+            // Single player games end when the Snek dies, and multiplayer games end when there's only one Snek left standing
+            let alive = self.sneks.iter().filter(|snek| snek.is_alive()).count();
+            if alive == 0 || (self.is_multiplayer() && alive == 1) {
                 self.play = false;
             }
         } else {
             log::warn!("GAME OVER!!");
         }
     }
+
+    /// Make sure there's always some Normal Food on the Grid, and sometimes add a special one too
+    /// Returns false if the Grid is so full that there's no Food left at all
+    fn spawn_food(&mut self) -> bool {
+        if !self.food.iter().any(|food| food.kind() == FoodKind::Normal) {
+            if let Some(food) = Food::random(
+                FoodKind::Normal,
+                &self.grid,
+                &self.sneks,
+                &self.food,
+                &mut self.prng,
+            ) {
+                self.food.push(food);
+            }
+        }
+
+        if self.food.len() < self.food_config.max_items
+            && self.prng.gen_bool(self.food_config.special_chance)
+        {
+            let special =
+                FoodKind::random_special(&self.food_config, &mut self.prng).and_then(|kind| {
+                    Food::random(kind, &self.grid, &self.sneks, &self.food, &mut self.prng)
+                });
+
+            // Golden Food doesn't stick around for long
+            if let Some(food) = special {
+                match food.kind() {
+                    FoodKind::Golden => self
+                        .food
+                        .push(food.expiring(self.food_config.golden_lifetime)),
+                    _ => self.food.push(food),
+                }
+            }
+        }
+
+        !self.food.is_empty()
+    }
+
+    /// Find the indices of the living Sneks that have just hit a wall, themselves or another Snek, along with what they hit
+    fn crashed(&self, old_heads: &[Vec2]) -> Vec<(usize, Death)> {
+        let living: Vec<usize> = (0..self.sneks.len())
+            .filter(|&idx| self.sneks[idx].is_alive())
+            .collect();
+
+        living
+            .iter()
+            .copied()
+            .filter_map(|idx| {
+                let snek = &self.sneks[idx];
+
+                // Walls take out any Snek that runs into them
+                if self.grid.is_wall(snek.head()) {
+                    return Some((idx, Death::Wall));
+                }
+                if snek.hit_self() {
+                    return Some((idx, Death::Itself));
+                }
+
+                // Head-on collisions (including swapping places) take out both Sneks, whereas running into a body only takes out the Snek doing the running
+                living
+                    .iter()
+                    .copied()
+                    .filter(|&other| other != idx)
+                    .find_map(|other| {
+                        let rival = &self.sneks[other];
+
+                        if rival.head_is_touching(snek.head())
+                            || (rival.head() == &old_heads[idx] && snek.head() == &old_heads[other])
+                        {
+                            Some((idx, Death::HeadOn(other)))
+                        } else if rival.touches(snek.head()) {
+                            Some((idx, Death::Snek(other)))
+                        } else {
+                            None
+                        }
+                    })
+            })
+            .collect()
+    }
+}
+
+/// A 64 bit FNV-1a hash, fed an explicit little-endian encoding of each value
+/// Unlike the std Hashers, this always gives the same answer, whatever the platform or version of Rust, so copies of a game on different machines can compare hashes
+struct Fnv(u64);
+
+impl Default for Fnv {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv {
+    fn bytes(&mut self, bytes: &[u8]) -> &mut Self {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+        self
+    }
+
+    fn u8(&mut self, v: u8) -> &mut Self {
+        self.bytes(&[v])
+    }
+
+    fn u32(&mut self, v: u32) -> &mut Self {
+        self.bytes(&v.to_le_bytes())
+    }
+
+    fn u64(&mut self, v: u64) -> &mut Self {
+        self.bytes(&v.to_le_bytes())
+    }
+
+    fn u128(&mut self, v: u128) -> &mut Self {
+        self.bytes(&v.to_le_bytes())
+    }
+
+    fn vec2(&mut self, v: &Vec2) -> &mut Self {
+        self.bytes(&v.x.to_le_bytes()).bytes(&v.y.to_le_bytes())
+    }
+}
+
+/// Get the (tail, head) of each Snek
+fn ends(sneks: &[Snek]) -> Vec<(Vec2, Vec2)> {
+    sneks
+        .iter()
+        .map(|snek| (snek.parts()[0], *snek.head()))
+        .collect()
+}
+
+/// Work out where each of the specified number of Sneks should start, spreading them evenly along the first row of the Grid
+fn spawn_points(grid: &Grid, count: usize) -> Vec<Spawn> {
+    (0..count)
+        .map(|idx| Spawn {
+            pos: Vec2::new((idx * grid.width() / count) as i32, 0),
+            orientation: Direction::Up, // Sneks start facing straight up
+        })
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{
-        entities::snek::{FACING_DOWN, FACING_LEFT, FACING_RIGHT, FACING_UP},
-        types::Vec2,
-    };
+    use crate::{players::replay::ReplayPlayer, types::Vec2};
 
     use super::*;
 
@@ -138,12 +598,47 @@ mod tests {
         let state = GameState::new(grid, &mut prng, &mut player);
 
         assert_eq!(state.grid.len(), 25);
-        assert_eq!(state.snek.head(), &Vec2::new(0, 0));
-        assert_eq!(state.food.pos(), &Vec2::new(4, 4));
+        assert_eq!(state.sneks[0].head(), &Vec2::new(0, 0));
+        assert_eq!(state.food[0].pos(), &Vec2::new(1, 4));
 
         println!("{}", state);
     }
 
+    #[test]
+    fn non_square_display() {
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+
+        let grid = Grid::new(4, 3).unwrap();
+        let mut player = HumanPlayer::default();
+        let state = GameState::new(grid, &mut prng, &mut player);
+
+        // The Snek starts in the bottom left corner, and the bottom row is printed last
+        let output = state.to_string();
+        let lines: Vec<&str> = output.lines().skip(1).collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines.iter().all(|line| line.len() == 4));
+        assert!(lines[2].starts_with('#'));
+        assert_eq!(output.matches('%').count(), 1);
+    }
+
+    #[test]
+    fn fill_the_grid() {
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+
+        // The only free square on a 2x1 Grid must be where the Food goes
+        let grid = Grid::new(2, 1).unwrap();
+        let mut player = HumanPlayer::default();
+        let mut state = GameState::new(grid, &mut prng, &mut player);
+        assert_eq!(state.food[0].pos(), &Vec2::new(1, 0));
+
+        // Eating it leaves nowhere for the next one
+        state.sneks[0].set_orientation(Direction::Right);
+        state.step();
+        assert!(state.won);
+        assert!(!state.play);
+        assert!(state.sneks[0].is_alive());
+    }
+
     #[test]
     fn sample_game() {
         let mut prng = ChaCha8Rng::from_seed(Default::default());
@@ -151,47 +646,352 @@ mod tests {
         let mut player = HumanPlayer::default();
 
         let mut state = GameState::new(grid, &mut prng, &mut player);
+        state.food_config.special_chance = 0.0;
 
         println!("{}", state);
 
-        // Play out a game
-        state.snek.set_orientation(FACING_LEFT);
+        // Play out a game, putting the Food down by hand so it doesn't matter where the prng would have put it
+        state.food = vec![Food::new(Vec2::new(3, 4))];
+        state.sneks[0].set_orientation(Direction::Left);
         state.step();
         state.step();
-        state.snek.set_orientation(FACING_DOWN);
+        state.sneks[0].set_orientation(Direction::Down);
         state.step();
-        assert_eq!(state.snek.len(), 1);
+        assert_eq!(state.sneks[0].len(), 1);
 
+        state.food = vec![Food::new(Vec2::new(1, 2))];
         state.step();
         state.step();
-        state.snek.set_orientation(FACING_LEFT);
+        state.sneks[0].set_orientation(Direction::Left);
         state.step();
         state.step();
-        assert_eq!(state.snek.len(), 2);
+        assert_eq!(state.sneks[0].len(), 2);
 
+        state.food = vec![Food::new(Vec2::new(2, 0))];
         state.step();
         state.step();
         state.step();
         state.step();
-        state.snek.set_orientation(FACING_DOWN);
+        state.sneks[0].set_orientation(Direction::Down);
         state.step();
         state.step();
-        assert_eq!(state.snek.len(), 3);
+        assert_eq!(state.sneks[0].len(), 3);
 
-        state.snek.set_orientation(FACING_LEFT);
+        state.food = vec![Food::new(Vec2::new(1, 4))];
+        state.sneks[0].set_orientation(Direction::Left);
         state.step();
-        state.snek.set_orientation(FACING_DOWN);
+        state.sneks[0].set_orientation(Direction::Down);
         state.step();
-        assert_eq!(state.snek.len(), 4);
+        assert_eq!(state.sneks[0].len(), 4);
 
-        state.snek.set_orientation(FACING_RIGHT);
+        state.sneks[0].set_orientation(Direction::Right);
         state.step();
-        state.snek.set_orientation(FACING_UP);
+        state.sneks[0].set_orientation(Direction::Up);
         state.step();
-        state.snek.set_orientation(FACING_LEFT);
+        state.sneks[0].set_orientation(Direction::Left);
         state.step();
-        assert_eq!(state.snek.hit_self(), true);
+        assert!(state.sneks[0].hit_self());
 
         println!("{}", state);
     }
+
+    #[test]
+    fn multiplayer_spawns() {
+        let prng = ChaCha8Rng::from_seed(Default::default());
+        let grid = Grid::new(10, 10).unwrap();
+        let players = vec![HumanPlayer::default(), HumanPlayer::default()];
+
+        let state = GameState::with_players(grid, prng, players);
+
+        assert!(state.is_multiplayer());
+        assert_eq!(state.sneks[0].head(), &Vec2::new(0, 0));
+        assert_eq!(state.sneks[1].head(), &Vec2::new(5, 0));
+        assert_eq!(state.scores(), vec![0, 0]);
+    }
+
+    #[test]
+    fn head_on_collision() {
+        let prng = ChaCha8Rng::from_seed(Default::default());
+        let grid = Grid::new(10, 10).unwrap();
+        let players = vec![HumanPlayer::default(), HumanPlayer::default()];
+
+        let mut state = GameState::with_players(grid, prng, players);
+        state.food = vec![Food::new(Vec2::new(9, 9))];
+
+        // Sneks at (0, 0) and (5, 0) driving towards each other will meet at (3, 0) or cross over between (2, 0) and (3, 0)
+        state.sneks[0].set_orientation(Direction::Right);
+        state.sneks[1].set_orientation(Direction::Left);
+
+        while state.play {
+            state.step();
+        }
+
+        assert!(!state.sneks[0].is_alive());
+        assert!(!state.sneks[1].is_alive());
+        assert_eq!(
+            state.deaths,
+            vec![Some(Death::HeadOn(1)), Some(Death::HeadOn(0))]
+        );
+    }
+
+    #[test]
+    fn body_collision() {
+        let prng = ChaCha8Rng::from_seed(Default::default());
+        let grid = Grid::new(10, 10).unwrap();
+        let players = vec![HumanPlayer::default(), HumanPlayer::default()];
+
+        let mut state = GameState::with_players(grid, prng, players);
+        state.food_config.special_chance = 0.0;
+
+        // Give the second Snek a body to run into by feeding it
+        state.food = vec![Food::new(Vec2::new(5, 1))];
+        state.sneks[0].set_orientation(Direction::Right);
+        state.sneks[1].set_orientation(Direction::Up);
+        state.step();
+        assert_eq!(state.scores(), vec![0, 1]);
+
+        // Send the second Snek along the row above the first one
+        state.food = vec![Food::new(Vec2::new(9, 9))];
+        state.sneks[1].set_orientation(Direction::Left);
+        state.step();
+        state.step();
+
+        // Then turn the first Snek up into the body of the second one
+        state.sneks[0].set_orientation(Direction::Up);
+        state.step();
+
+        assert!(!state.sneks[0].is_alive());
+        assert!(state.sneks[1].is_alive());
+        assert!(!state.play);
+        assert_eq!(state.deaths, vec![Some(Death::Snek(1)), None]);
+    }
+
+    #[test]
+    fn special_food() {
+        let prng = ChaCha8Rng::from_seed(Default::default());
+        let grid = Grid::new(10, 10).unwrap();
+        let mut state = GameState::new(grid, prng, HumanPlayer::default());
+        state.food_config.special_chance = 0.0;
+
+        // Line up some Speed Food and some Golden Food that's about to vanish
+        state.sneks[0].set_orientation(Direction::Right);
+        state.food = vec![
+            Food::new(Vec2::new(9, 9)),
+            Food::with_kind(Vec2::new(1, 0), FoodKind::Speed),
+            Food::with_kind(Vec2::new(5, 5), FoodKind::Golden).expiring(2),
+        ];
+        assert!(state.to_string().contains('>'));
+        assert!(state.to_string().contains('*'));
+
+        // Eating the Speed Food boosts the game, and the Golden Food is gone a tick later
+        state.step();
+        assert_eq!(state.boost, state.food_config.boost_length);
+        assert_eq!(state.scores(), vec![2]);
+        assert_eq!(state.food.len(), 2);
+        state.step();
+        assert_eq!(state.boost, state.food_config.boost_length - 1);
+        assert_eq!(state.food, vec![Food::new(Vec2::new(9, 9))]);
+
+        // Eating the last Normal Food always brings a new one
+        state.food = vec![Food::new(Vec2::new(3, 0))];
+        state.step();
+        assert_eq!(state.scores(), vec![3]);
+        assert_eq!(state.food.len(), 1);
+        assert_eq!(state.food[0].kind(), FoodKind::Normal);
+        assert_ne!(state.food[0].pos(), &Vec2::new(3, 0));
+    }
+
+    #[test]
+    fn special_food_spawns() {
+        let prng = ChaCha8Rng::from_seed(Default::default());
+        let grid = Grid::new(10, 10).unwrap();
+        let mut state = GameState::new(grid, prng, HumanPlayer::default());
+        state.food_config.special_chance = 1.0;
+        state.food_config.max_items = 4;
+
+        // Keep the Snek out of the way on a row of its own
+        state.sneks[0].set_orientation(Direction::Right);
+        state.food.retain(|food| food.pos().y != 0);
+        state.step();
+        state.step();
+        state.step();
+
+        assert_eq!(state.food.len(), 4);
+        assert_eq!(
+            state
+                .food
+                .iter()
+                .filter(|food| food.kind() == FoodKind::Normal)
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn level_game() {
+        let level = Level::parse(
+            "name = \"Walled\"\nfacing = [\"right\", \"left\"]\n---\n.....\n0.+.1\n..$..\n",
+        )
+        .unwrap();
+
+        // There's only room for two players
+        let players = (0..3).map(|_| HumanPlayer::default()).collect();
+        let prng = ChaCha8Rng::from_seed(Default::default());
+        assert!(GameState::from_level(&level, prng, players).is_err());
+
+        let players = vec![HumanPlayer::default(), HumanPlayer::default()];
+        let prng = ChaCha8Rng::from_seed(Default::default());
+        let mut state = GameState::from_level(&level, prng, players).unwrap();
+        assert_eq!(state.sneks[0].head(), &Vec2::new(0, 1));
+        assert_eq!(state.sneks[1].orientation(), &Direction::Left);
+        assert!(state.to_string().contains(".+."));
+
+        // The level's Bonus Food is kept, and some Normal Food is added alongside it
+        assert_eq!(state.food.len(), 2);
+        assert_eq!(
+            state.food[0],
+            Food::with_kind(Vec2::new(2, 0), FoodKind::Bonus)
+        );
+        assert!(!state.grid.is_wall(state.food[1].pos()));
+
+        // Both Sneks drive straight into the wall in the middle
+        state.food_config.special_chance = 0.0;
+        state.step();
+        state.step();
+        assert!(!state.sneks[0].is_alive());
+        assert!(!state.sneks[1].is_alive());
+        assert!(!state.play);
+        assert_eq!(state.deaths, vec![Some(Death::Wall), Some(Death::Wall)]);
+        assert_eq!(state.deaths[0].unwrap().to_string(), "hit a wall");
+    }
+
+    #[test]
+    fn tick_rate() {
+        let prng = ChaCha8Rng::from_seed(Default::default());
+        let grid = Grid::new(10, 10).unwrap();
+        let mut state = GameState::new(grid, prng, HumanPlayer::default());
+        state.speed = SpeedConfig {
+            base_rate: 5,
+            ramp_every: 2,
+            max_rate: 8,
+        };
+        assert_eq!(state.tick_rate(), 5);
+
+        // Longer Sneks speed the game up, but only so far
+        let bounds = *state.grid.bounds();
+        let parts: Vec<Vec2> = (0..5).map(|x| Vec2::new(x, 5)).collect();
+        state.sneks[0] = Snek::from_parts(parts.into(), &bounds, Direction::Right, true);
+        assert_eq!(state.tick_rate(), 7);
+        state.adjust_speed(3);
+        assert_eq!(state.tick_rate(), 8);
+
+        // Speed Food doubles whatever the rate would have been
+        state.boost = 1;
+        assert_eq!(state.tick_rate(), 16);
+
+        // The game can never stop altogether
+        state.boost = 0;
+        state.adjust_speed(-100);
+        assert_eq!(state.speed.base_rate, 1);
+        assert_eq!(state.tick_rate(), 3);
+    }
+
+    #[test]
+    fn previous_ends() {
+        let prng = ChaCha8Rng::from_seed(Default::default());
+        let grid = Grid::new(10, 10).unwrap();
+        let mut state = GameState::new(grid, prng, HumanPlayer::default());
+        state.food.clear();
+        assert_eq!(
+            state.previous_ends,
+            vec![(Vec2::new(0, 0), Vec2::new(0, 0))]
+        );
+
+        state.sneks[0].set_orientation(Direction::Right);
+        state.step();
+        state.step();
+        assert_eq!(
+            state.previous_ends,
+            vec![(Vec2::new(1, 0), Vec2::new(1, 0))]
+        );
+        assert_eq!(state.sneks[0].head(), &Vec2::new(2, 0));
+    }
+
+    #[test]
+    fn seeded_games_are_deterministic() {
+        // Two Sneks following the same loop, which slowly drifts up and to the right until they hit something
+        let loop_moves = [
+            Direction::Up,
+            Direction::Up,
+            Direction::Right,
+            Direction::Right,
+            Direction::Down,
+            Direction::Left,
+        ];
+        let script: Vec<Direction> = loop_moves.iter().cycle().take(120).copied().collect();
+
+        // Play the script through on the specified seed, hashing the game after every tick
+        let play = move |seed: u64| -> Vec<u64> {
+            let players = (0..2)
+                .map(|_| ReplayPlayer::new(Replay::new(script.clone())))
+                .collect();
+            let mut state = GameState::seeded(Grid::default(), seed, players);
+            let keys = HashSet::new();
+
+            let mut hashes = vec![state.hash()];
+            for _ in 0..script.len() {
+                state.poll_players(&keys);
+                state.step();
+                hashes.push(state.hash());
+            }
+            hashes
+        };
+
+        // The same seed always gives the same game, even played on a different thread
+        let first: Vec<Vec<u64>> = (0..50).map(play.clone()).collect();
+        let second: Vec<Vec<u64>> = std::thread::spawn(move || (0..50).map(play).collect())
+            .join()
+            .unwrap();
+        assert_eq!(first, second);
+
+        // Every seed gives a different game, and the hash moves on with the game
+        let starts: HashSet<u64> = first.iter().map(|hashes| hashes[0]).collect();
+        assert_eq!(starts.len(), 50);
+        assert!(first.iter().all(|hashes| hashes[0] != hashes[1]));
+
+        // Seeding a game is the same as seeding its prng by hand, and it remembers the seed
+        let grid = Grid::new(8, 8).unwrap();
+        let prng = ChaCha8Rng::seed_from_u64(7);
+        let by_hand = GameState::new(grid.clone(), prng, HumanPlayer::default());
+        let seeded = GameState::seeded(grid, 7, vec![HumanPlayer::default()]);
+        assert_eq!(seeded.hash(), by_hand.hash());
+        assert_eq!(seeded.seed, Some(7));
+        assert_eq!(seeded.replays[0].seed(), Some(7));
+        assert_eq!(by_hand.seed, None);
+        assert_eq!(GameState::default().seed, Some(DEFAULT_SEED));
+    }
+
+    #[test]
+    fn hashes_never_change() {
+        // Networked games compare hashes between machines, so the same game has to hash the same everywhere, forever
+        let hash = |seed: u64, grid: Grid, steps: usize| {
+            let players = vec![HumanPlayer::default(), HumanPlayer::default()];
+            let mut state = GameState::seeded(grid, seed, players);
+            for _ in 0..steps {
+                state.step();
+            }
+            state.hash()
+        };
+
+        let mut walled = Grid::new(12, 9)
+            .unwrap()
+            .with_topology(crate::entities::topology::TopologyKind::Hex);
+        walled.add_wall(&Vec2::new(4, 4));
+        walled.add_wall(&Vec2::new(5, 4));
+
+        assert_eq!(hash(0, Grid::default(), 0), 0xa096_1868_80fb_f633);
+        assert_eq!(hash(1, Grid::default(), 0), 0x19ff_9967_1e41_f2fc);
+        assert_eq!(hash(42, Grid::default(), 7), 0xb98a_518a_8f51_eea7);
+        assert_eq!(hash(1234, walled, 3), 0x32de_3c8c_2224_ef75);
+    }
 }
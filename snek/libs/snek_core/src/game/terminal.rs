@@ -0,0 +1,406 @@
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use crossterm::event::{self, Event, KeyCode as TermKey, KeyEvent, KeyModifiers};
+use crossterm::style::{self, Color, Print};
+use crossterm::{cursor, queue, terminal};
+use ggez::input::keyboard::KeyCode;
+use ggez::GameResult;
+use rand::RngCore;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::entities::food::FoodKind;
+use crate::players::human::player::{key_code, Controls};
+use crate::players::Move;
+use crate::types::Vec2;
+
+use super::engine::{self, Rgb, SNEK_COLOURS, WALL_COLOUR};
+use super::mode::Mode;
+use super::scene::{Scene, Scenes};
+use super::state::GameState;
+
+/// The longest the screen goes without being redrawn, so the status line keeps up even when the game is slow
+const FRAME_TIME: Duration = Duration::from_millis(50);
+
+/// The colour of the empty squares
+const FLOOR_COLOUR: Rgb = (30, 30, 30);
+
+/// Terminal characters are about twice as tall as they are wide, so each square is two characters across
+const SQUARE: &str = "  ";
+
+/// What's in each square of the Grid, as far as drawing it goes
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum Square {
+    Floor,
+    Wall,
+    Food(FoodKind),
+    /// Part of the Snek with the specified index, and whether it's the head
+    Snek(usize, bool),
+}
+
+impl Square {
+    /// Get the colour the square is drawn in
+    fn colour(self) -> Rgb {
+        match self {
+            Square::Floor => FLOOR_COLOUR,
+            Square::Wall => WALL_COLOUR,
+            Square::Food(kind) => engine::food_colour(kind),
+            Square::Snek(idx, head) => {
+                let (body_colour, head_colour) = SNEK_COLOURS[idx % SNEK_COLOURS.len()];
+                match head {
+                    true => head_colour,
+                    false => body_colour,
+                }
+            }
+        }
+    }
+}
+
+/// Puts the terminal into raw mode on its alternate screen, and puts it back how it was when dropped (even if the game panics)
+struct RawTerminal;
+
+impl RawTerminal {
+    fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        let mut out = io::stdout();
+        queue!(out, terminal::EnterAlternateScreen, cursor::Hide)?;
+        out.flush()?;
+
+        Ok(Self)
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let mut out = io::stdout();
+        let _ = queue!(
+            out,
+            style::ResetColor,
+            cursor::Show,
+            terminal::LeaveAlternateScreen
+        );
+        let _ = out.flush();
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Play the game in the terminal rather than a window, until the player quits
+/// Terminals only say when a key is pressed (not when it's let go), so each key is passed on to the players the moment it's pressed
+pub fn run<R, M, F>(mut scenes: Scenes<R, M, F>) -> GameResult
+where
+    R: RngCore + Clone + Serialize + DeserializeOwned,
+    M: Move,
+    F: FnMut(&Mode, &[Controls]) -> GameResult<GameState<R, M>>,
+{
+    let _raw = RawTerminal::enter()?;
+    let mut out = io::stdout();
+    let mut last_step = Instant::now();
+
+    loop {
+        // Each frame is written in one go, so it doesn't flicker as it's drawn
+        let mut frame = Vec::new();
+        draw(&mut frame, &scenes)?;
+        out.write_all(&frame)?;
+        out.flush()?;
+
+        // Wait for a key, but no longer than it takes for the next step to be due
+        let tick =
+            Duration::from_secs_f64(1.0 / scenes.state().map_or(1, |s| s.tick_rate()) as f64);
+        let timeout = tick
+            .checked_sub(last_step.elapsed())
+            .unwrap_or_default()
+            .min(FRAME_TIME);
+
+        if event::poll(timeout)? {
+            if let Event::Key(key) = event::read()? {
+                if is_quit(key) {
+                    return Ok(());
+                }
+
+                if let Some(key) = ggez_key(key) {
+                    match scenes.key_pressed(key) {
+                        Ok(true) => return Ok(()),
+                        Ok(false) => {}
+                        Err(e) => {
+                            log::warn!("Couldn't start a new game: {}", e);
+                            scenes.to_menu();
+                        }
+                    }
+
+                    // Only the key that was just pressed counts, so the last key pressed before a step always wins
+                    let mut keys = HashSet::new();
+                    keys.insert(key);
+                    scenes.poll_players(&keys);
+                }
+            }
+        }
+
+        // Only the game itself moves - everywhere else, just let the time pass so the game doesn't race to catch up when it resumes
+        if scenes.scene() != Scene::Playing {
+            last_step = Instant::now();
+            continue;
+        }
+
+        if last_step.elapsed() >= tick {
+            last_step = Instant::now();
+
+            // Let go of every key before stepping, so that turning takes a fresh press each time
+            scenes.poll_players(&HashSet::new());
+            scenes.step();
+        }
+    }
+}
+
+/// Ctrl+C doesn't stop the program in raw mode, so it has to be caught by hand
+fn is_quit(key: KeyEvent) -> bool {
+    key.modifiers.contains(KeyModifiers::CONTROL) && key.code == TermKey::Char('c')
+}
+
+/// Work out which ggez key a terminal key is, if it's one the game knows about
+pub(crate) fn ggez_key(key: KeyEvent) -> Option<KeyCode> {
+    match key.code {
+        TermKey::Up => Some(KeyCode::Up),
+        TermKey::Down => Some(KeyCode::Down),
+        TermKey::Left => Some(KeyCode::Left),
+        TermKey::Right => Some(KeyCode::Right),
+        TermKey::Enter => Some(KeyCode::Return),
+        TermKey::Esc => Some(KeyCode::Escape),
+        TermKey::Backspace => Some(KeyCode::Back),
+        TermKey::Tab => Some(KeyCode::Tab),
+        TermKey::F(n) => key_code(&format!("F{}", n)),
+        TermKey::Char(' ') => Some(KeyCode::Space),
+        TermKey::Char('+') => Some(KeyCode::Plus),
+        TermKey::Char('=') => Some(KeyCode::Equals),
+        TermKey::Char('-') => Some(KeyCode::Minus),
+        TermKey::Char(c) => key_code(&c.to_string()),
+        _ => None,
+    }
+}
+
+/// Work out what's in each square of the Grid, top row first
+pub(crate) fn squares<R, M>(state: &GameState<R, M>) -> Vec<Vec<Square>>
+where
+    R: RngCore,
+    M: Move,
+{
+    let grid = &state.grid;
+    let mut rows = vec![vec![Square::Floor; grid.width()]; grid.height()];
+
+    // The Grid counts rows from the bottom, but the terminal counts them from the top
+    let mut put = |x: i32, y: i32, square: Square| {
+        rows[grid.height() - 1 - y as usize][x as usize] = square;
+    };
+
+    for pos in grid.into_iter().filter(|pos| grid.is_wall(pos)) {
+        put(pos.x, pos.y, Square::Wall);
+    }
+    for food in &state.food {
+        put(food.pos().x, food.pos().y, Square::Food(food.kind()));
+    }
+
+    // Heads are put down last, so they always end up on top
+    for (idx, snek) in state.sneks.iter().enumerate() {
+        if snek.is_alive() {
+            for part in snek.parts() {
+                put(part.x, part.y, Square::Snek(idx, false));
+            }
+            put(snek.head().x, snek.head().y, Square::Snek(idx, true));
+        }
+    }
+
+    rows
+}
+
+/// The line under the Grid, with everyone's score and how fast the game is going
+fn status_line<R, M>(state: &GameState<R, M>) -> String
+where
+    R: RngCore,
+    M: Move,
+{
+    let scores: String = match state.scores().as_slice() {
+        [score] => format!("[score: {}]", score),
+        scores => scores
+            .iter()
+            .enumerate()
+            .map(|(idx, score)| format!("[P{}: {}]", idx + 1, score))
+            .collect(),
+    };
+    let seed = match state.seed {
+        Some(seed) => format!("[seed: {}]", seed),
+        None => String::new(),
+    };
+
+    format!("{}[speed: {}]{}", scores, state.tick_rate(), seed)
+}
+
+/// Draw whichever screen we're on
+fn draw<R, M, F>(out: &mut impl Write, scenes: &Scenes<R, M, F>) -> io::Result<()>
+where
+    R: RngCore,
+    M: Move,
+    F: FnMut(&Mode, &[Controls]) -> GameResult<GameState<R, M>>,
+{
+    let (columns, rows) = terminal::size()?;
+    queue!(
+        out,
+        style::ResetColor,
+        terminal::Clear(terminal::ClearType::All)
+    )?;
+
+    match (scenes.scene(), scenes.state()) {
+        (Scene::Menu, _) | (_, None) => {
+            draw_centred_text(out, &engine::menu_text(scenes.selected()), columns, rows)?
+        }
+        (Scene::Controls, _) => draw_centred_text(
+            out,
+            &engine::controls_text(scenes.controls(), scenes.rebinding()),
+            columns,
+            rows,
+        )?,
+        (_, Some(state)) => {
+            draw_game(out, state, columns, rows)?;
+
+            if let Some(lines) = engine::overlay_text(scenes) {
+                draw_centred_text(out, &lines, columns, rows)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Draw the Grid in the middle of the terminal, with the status line underneath
+fn draw_game<R, M>(
+    out: &mut impl Write,
+    state: &GameState<R, M>,
+    columns: u16,
+    rows: u16,
+) -> io::Result<()>
+where
+    R: RngCore,
+    M: Move,
+{
+    // Some Topologies shift each row sideways, which makes the Grid wider than its rows
+    let topology = state.grid.topology();
+    let width = (topology.extent(state.grid.bounds()).0 * SQUARE.len() as f32).ceil() as usize;
+    let height = state.grid.height() + 1;
+
+    if width > columns as usize || height > rows as usize {
+        let lines = vec![format!(
+            "Make the terminal at least {}x{} to fit the Grid",
+            width, height
+        )];
+        return draw_centred_text(out, &lines, columns, rows);
+    }
+
+    let left = (columns as usize - width) / 2;
+    let top = (rows as usize - height) / 2;
+
+    for (y, row) in squares(state).iter().enumerate() {
+        let row_y = (state.grid.height() - 1 - y) as i32;
+        let shift = topology.position(&Vec2::new(0, row_y)).0;
+        let indent = (shift * SQUARE.len() as f32) as usize;
+        queue!(
+            out,
+            cursor::MoveTo((left + indent) as u16, (top + y) as u16)
+        )?;
+        for square in row {
+            let (r, g, b) = square.colour();
+            queue!(
+                out,
+                style::SetBackgroundColor(Color::Rgb { r, g, b }),
+                Print(SQUARE)
+            )?;
+        }
+    }
+
+    queue!(
+        out,
+        style::ResetColor,
+        cursor::MoveTo(left as u16, (top + height - 1) as u16),
+        Print(truncate(&status_line(state), columns as usize - left))
+    )
+}
+
+/// Write the specified lines in the middle of the terminal, one under the other
+fn draw_centred_text(
+    out: &mut impl Write,
+    lines: &[String],
+    columns: u16,
+    rows: u16,
+) -> io::Result<()> {
+    let top = (rows as usize).saturating_sub(lines.len()) / 2;
+
+    queue!(out, style::ResetColor)?;
+    for (idx, line) in lines.iter().enumerate() {
+        let line = truncate(line, columns as usize);
+        let left = (columns as usize).saturating_sub(line.chars().count()) / 2;
+
+        queue!(
+            out,
+            cursor::MoveTo(left as u16, (top + idx) as u16),
+            Print(line)
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Cut the line down to the specified number of characters, so it doesn't wrap onto the next line
+fn truncate(line: &str, max: usize) -> String {
+    line.chars().take(max).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::entities::{food::Food, grid::Grid};
+    use crate::players::human::player::HumanPlayer;
+    use crate::types::Vec2;
+
+    use super::*;
+
+    #[test]
+    fn terminal_keys() {
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+
+        assert_eq!(ggez_key(key(TermKey::Up)), Some(KeyCode::Up));
+        assert_eq!(ggez_key(key(TermKey::Enter)), Some(KeyCode::Return));
+        assert_eq!(ggez_key(key(TermKey::Char('w'))), Some(KeyCode::W));
+        assert_eq!(ggez_key(key(TermKey::Char('W'))), Some(KeyCode::W));
+        assert_eq!(ggez_key(key(TermKey::Char('3'))), Some(KeyCode::Key3));
+        assert_eq!(ggez_key(key(TermKey::Char(' '))), Some(KeyCode::Space));
+        assert_eq!(ggez_key(key(TermKey::F(5))), Some(KeyCode::F5));
+        assert_eq!(ggez_key(key(TermKey::Char('#'))), None);
+
+        assert!(is_quit(KeyEvent::new(
+            TermKey::Char('c'),
+            KeyModifiers::CONTROL
+        )));
+        assert!(!is_quit(key(TermKey::Char('c'))));
+    }
+
+    #[test]
+    fn grid_squares() {
+        let prng = ChaCha8Rng::from_seed(Default::default());
+        let mut grid = Grid::new(4, 3).unwrap();
+        grid.add_wall(&Vec2::new(3, 2));
+        let mut state = GameState::new(grid, prng, HumanPlayer::default());
+        state.food = vec![Food::new(Vec2::new(2, 0))];
+
+        // The Snek starts in the bottom left corner, which is the start of the last row
+        let rows = squares(&state);
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[2][0], Square::Snek(0, true));
+        assert_eq!(rows[2][2], Square::Food(FoodKind::Normal));
+        assert_eq!(rows[0][3], Square::Wall);
+        assert_eq!(rows[1][1], Square::Floor);
+
+        state.sneks[0].kill();
+        assert_eq!(squares(&state)[2][0], Square::Floor);
+    }
+}
@@ -0,0 +1,417 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::entities::{
+    food::{Food, FoodKind},
+    grid::Grid,
+    topology::TopologyKind,
+};
+use crate::settings::{FoodConfig, Rules, SpeedConfig};
+use crate::types::{Direction, Vec2};
+
+/// The line that separates the TOML header from the map itself
+const SEPARATOR: &str = "---";
+
+/// The symbols used to draw empty squares and walls in a level map
+pub const EMPTY: char = '.';
+pub const WALL: char = '+';
+
+/// The levels that come with the game, as (name, contents)
+pub const BUILT_IN: [(&str, &str); 4] = [
+    ("open", include_str!("../../../levels/open.lvl")),
+    ("box", include_str!("../../../levels/box.lvl")),
+    ("maze", include_str!("../../../levels/maze.lvl")),
+    ("duel", include_str!("../../../levels/duel.lvl")),
+];
+
+/// Everything that can go wrong when loading a level
+#[derive(Debug)]
+pub enum LevelError {
+    Io(std::io::Error),
+    MissingSeparator,
+    Header(toml::de::Error),
+    BadRule(String),
+    EmptyMap,
+    TooBig,
+    RaggedRow {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    UnknownSymbol {
+        line: usize,
+        column: usize,
+        symbol: char,
+    },
+    NoSpawns,
+    DuplicateSpawn(usize),
+    MissingSpawn(usize),
+    TooManyFacings {
+        spawns: usize,
+        facings: usize,
+    },
+    NotEnoughSpawns {
+        spawns: usize,
+        players: usize,
+    },
+}
+
+impl std::fmt::Display for LevelError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LevelError::Io(e) => write!(f, "couldn't read level: {}", e),
+            LevelError::MissingSeparator => {
+                write!(f, "no '{}' line between the header and the map", SEPARATOR)
+            }
+            LevelError::Header(e) => write!(f, "bad header: {}", e),
+            LevelError::BadRule(why) => write!(f, "bad rule: {}", why),
+            LevelError::EmptyMap => write!(f, "the map is empty"),
+            LevelError::TooBig => write!(f, "the map is too big"),
+            LevelError::RaggedRow {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "line {}: expected {} squares like the first row, found {}",
+                line, expected, found
+            ),
+            LevelError::UnknownSymbol {
+                line,
+                column,
+                symbol,
+            } => write!(
+                f,
+                "line {}, column {}: unknown symbol '{}'",
+                line, column, symbol
+            ),
+            LevelError::NoSpawns => write!(f, "there are no spawn points (0-9) in the map"),
+            LevelError::DuplicateSpawn(n) => write!(f, "spawn point {} appears more than once", n),
+            LevelError::MissingSpawn(n) => write!(
+                f,
+                "spawn point {} is missing - spawn points must be numbered from 0 with no gaps",
+                n
+            ),
+            LevelError::TooManyFacings { spawns, facings } => write!(
+                f,
+                "{} facings given for only {} spawn points",
+                facings, spawns
+            ),
+            LevelError::NotEnoughSpawns { spawns, players } => write!(
+                f,
+                "the level only has room for {} players, not {}",
+                spawns, players
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LevelError {}
+
+impl From<std::io::Error> for LevelError {
+    fn from(e: std::io::Error) -> Self {
+        LevelError::Io(e)
+    }
+}
+
+/// The directions a Snek can start off facing
+#[derive(Debug, Copy, Clone, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Facing {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl From<Facing> for Direction {
+    fn from(facing: Facing) -> Self {
+        match facing {
+            Facing::Up => Direction::Up,
+            Facing::Down => Direction::Down,
+            Facing::Left => Direction::Left,
+            Facing::Right => Direction::Right,
+        }
+    }
+}
+
+/// The TOML header at the top of a level file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Header {
+    name: String,
+    #[serde(default)]
+    facing: Vec<Facing>,
+    #[serde(default)]
+    food: FoodConfig,
+    #[serde(default)]
+    speed: SpeedConfig,
+    #[serde(default)]
+    topology: TopologyKind,
+}
+
+/// Where a Snek starts, and which way it's facing
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Spawn {
+    pub pos: Vec2,
+    pub orientation: Direction,
+}
+
+/// A map to play on, along with the rules for playing on it
+#[derive(Debug, Clone)]
+pub struct Level {
+    pub name: String,
+    pub grid: Grid,
+    pub spawns: Vec<Spawn>,
+    pub food: Vec<Food>,
+    pub food_config: FoodConfig,
+    pub speed: SpeedConfig,
+}
+
+impl Level {
+    /// Load a level from the specified file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, LevelError> {
+        let contents = std::fs::read_to_string(path)?;
+        Self::parse(&contents)
+    }
+
+    /// Load one of the levels that come with the game
+    pub fn built_in(name: &str) -> Option<Result<Self, LevelError>> {
+        BUILT_IN
+            .iter()
+            .find(|(level, _)| *level == name)
+            .map(|(_, contents)| Self::parse(contents))
+    }
+
+    /// Parse a level from a TOML header, followed by a separator line, followed by a map drawn the same way as a printed GameState
+    /// The bottom row of the map is y = 0, digits mark where each player's Snek starts, and Food is drawn using the symbol for its kind
+    pub fn parse(contents: &str) -> Result<Self, LevelError> {
+        // Split the header from the map
+        let lines: Vec<&str> = contents.lines().map(str::trim_end).collect();
+        let split = lines
+            .iter()
+            .position(|line| *line == SEPARATOR)
+            .ok_or(LevelError::MissingSeparator)?;
+
+        let header: Header =
+            toml::from_str(&lines[..split].join("\n")).map_err(LevelError::Header)?;
+
+        // Levels are held to the same rules as config files and rules files
+        Rules {
+            food: header.food.clone(),
+            speed: header.speed.clone(),
+        }
+        .check()
+        .map_err(LevelError::BadRule)?;
+
+        // Ignore any blank lines at the end of the file
+        let mut rows = &lines[split + 1..];
+        while let Some((last, rest)) = rows.split_last() {
+            match last.is_empty() {
+                true => rows = rest,
+                false => break,
+            }
+        }
+
+        // Every row must be the same width
+        let width = rows.first().map_or(0, |row| row.chars().count());
+        if width == 0 {
+            return Err(LevelError::EmptyMap);
+        }
+
+        let first_line = split + 2;
+        for (idx, row) in rows.iter().enumerate() {
+            let found = row.chars().count();
+            if found != width {
+                return Err(LevelError::RaggedRow {
+                    line: first_line + idx,
+                    expected: width,
+                    found,
+                });
+            }
+        }
+
+        let mut grid = Grid::new(width, rows.len())
+            .ok_or(LevelError::TooBig)?
+            .with_topology(header.topology);
+        let mut spawns = BTreeMap::new();
+        let mut food = Vec::new();
+
+        // The first row in the file is the top of the Grid
+        for (idx, row) in rows.iter().enumerate() {
+            let y = (rows.len() - 1 - idx) as i32;
+
+            for (x, symbol) in row.chars().enumerate() {
+                let pos = Vec2::new(x as i32, y);
+
+                match symbol {
+                    EMPTY => {}
+                    WALL => {
+                        grid.add_wall(&pos);
+                    }
+                    '0'..='9' => {
+                        let n = symbol.to_digit(10).unwrap() as usize;
+                        if spawns.insert(n, pos).is_some() {
+                            return Err(LevelError::DuplicateSpawn(n));
+                        }
+                    }
+                    _ => match FoodKind::from_symbol(symbol) {
+                        Some(FoodKind::Golden) => food.push(
+                            Food::with_kind(pos, FoodKind::Golden)
+                                .expiring(header.food.golden_lifetime),
+                        ),
+                        Some(kind) => food.push(Food::with_kind(pos, kind)),
+                        None => {
+                            return Err(LevelError::UnknownSymbol {
+                                line: first_line + idx,
+                                column: x + 1,
+                                symbol,
+                            })
+                        }
+                    },
+                }
+            }
+        }
+
+        // Spawn points must be numbered 0, 1, 2... with no gaps
+        if spawns.is_empty() {
+            return Err(LevelError::NoSpawns);
+        }
+        if let Some(n) = (0..spawns.len()).find(|n| !spawns.contains_key(n)) {
+            return Err(LevelError::MissingSpawn(n));
+        }
+
+        // Sneks face up unless the header says otherwise
+        if header.facing.len() > spawns.len() {
+            return Err(LevelError::TooManyFacings {
+                spawns: spawns.len(),
+                facings: header.facing.len(),
+            });
+        }
+
+        let spawns = spawns
+            .values()
+            .enumerate()
+            .map(|(idx, &pos)| Spawn {
+                pos,
+                orientation: header.facing.get(idx).map_or(Direction::Up, |&f| f.into()),
+            })
+            .collect();
+
+        Ok(Self {
+            name: header.name,
+            grid,
+            spawns,
+            food,
+            food_config: header.food,
+            speed: header.speed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_level() {
+        let level = Level::parse(
+            r#"
+name = "Test"
+facing = ["right"]
+
+[food]
+max_items = 5
+
+[speed]
+base_rate = 8
+---
++..%.
++.$..
++0..1
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(level.name, "Test");
+        assert_eq!(level.grid.width(), 5);
+        assert_eq!(level.grid.height(), 3);
+        assert_eq!(level.grid.wall_count(), 3);
+        assert!(level.grid.is_wall(&Vec2::new(0, 2)));
+        assert_eq!(
+            level.spawns,
+            vec![
+                Spawn {
+                    pos: Vec2::new(1, 0),
+                    orientation: Direction::Right
+                },
+                Spawn {
+                    pos: Vec2::new(4, 0),
+                    orientation: Direction::Up
+                },
+            ]
+        );
+        assert_eq!(
+            level.food,
+            vec![
+                Food::new(Vec2::new(3, 2)),
+                Food::with_kind(Vec2::new(2, 1), FoodKind::Bonus)
+            ]
+        );
+        assert_eq!(level.food_config.max_items, 5);
+        assert_eq!(level.speed.base_rate, 8);
+        assert_eq!(level.speed.max_rate, SpeedConfig::default().max_rate);
+        assert_eq!(
+            level.food_config.golden_weight,
+            FoodConfig::default().golden_weight
+        );
+    }
+
+    #[test]
+    fn level_errors() {
+        let err = |contents: &str| Level::parse(contents).unwrap_err().to_string();
+
+        assert!(err("name = \"x\"\n.0.\n").contains("no '---' line"));
+        assert!(err("nmae = \"x\"\n---\n.0.\n").contains("bad header"));
+        assert!(
+            err("name = \"x\"\n[food]\nspecial_chance = 2.0\n---\n0\n").contains("special_chance")
+        );
+        assert!(err("name = \"x\"\n---\n\n").contains("empty"));
+        assert_eq!(
+            err("name = \"x\"\n---\n.0.\n..\n"),
+            "line 4: expected 3 squares like the first row, found 2"
+        );
+        assert_eq!(
+            err("name = \"x\"\n---\n.0.\n.?.\n"),
+            "line 4, column 2: unknown symbol '?'"
+        );
+        assert!(err("name = \"x\"\n---\n...\n").contains("no spawn points"));
+        assert!(err("name = \"x\"\n---\n.0.\n.0.\n").contains("more than once"));
+        assert!(err("name = \"x\"\n---\n.0.\n.2.\n").contains("spawn point 1 is missing"));
+        assert!(err("name = \"x\"\nfacing = [\"up\", \"up\"]\n---\n.0.\n").contains("2 facings"));
+    }
+
+    #[test]
+    fn level_topology() {
+        let level = Level::parse("name = \"x\"\n---\n.0.\n").unwrap();
+        assert_eq!(level.grid.topology().kind(), TopologyKind::Square);
+
+        let level = Level::parse("name = \"x\"\ntopology = \"hex\"\n---\n.0.\n").unwrap();
+        assert_eq!(level.grid.topology().kind(), TopologyKind::Hex);
+
+        let err = Level::parse("name = \"x\"\ntopology = \"round\"\n---\n.0.\n").unwrap_err();
+        assert!(err.to_string().contains("bad header"));
+    }
+
+    #[test]
+    fn built_in_levels() {
+        for (name, _) in BUILT_IN.iter() {
+            let level = Level::built_in(name).unwrap();
+            assert!(level.is_ok(), "{}: {}", name, level.unwrap_err());
+        }
+
+        assert!(Level::built_in("nope").is_none());
+    }
+}
@@ -1,5 +1,11 @@
+pub mod config;
 pub mod entities;
+pub mod env;
 pub mod game;
+pub mod level;
+pub mod net;
+pub mod observation;
 pub mod players;
+pub mod scores;
 pub mod settings;
 pub mod types;
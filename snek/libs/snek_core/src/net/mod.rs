@@ -0,0 +1,93 @@
+use std::collections::HashSet;
+
+use ggez::input::keyboard::KeyCode;
+use rand_chacha::ChaCha8Rng;
+
+use crate::game::{engine::Renderer, state::GameState};
+use crate::players::{human::input::InputState, Move, View};
+use crate::types::Direction;
+
+pub mod protocol;
+pub mod session;
+
+pub use session::{Session, Setup};
+
+/// The default port for hosting networked games
+pub const DEFAULT_PORT: u16 = 7777;
+
+/// The default number of ticks between a player choosing a move and that move taking effect
+pub const DEFAULT_DELAY: u32 = 2;
+
+/// Stands in for every player in a networked game - their moves arrive over the network rather than being made locally
+#[derive(Debug, Default)]
+pub struct RemotePlayer;
+
+impl Move for RemotePlayer {
+    fn make_move(&mut self, _view: &View) -> Option<Direction> {
+        None
+    }
+}
+
+/// Networked games must use the same seeded prng on every peer, so that they all stay in step
+pub type NetState = GameState<ChaCha8Rng, RemotePlayer>;
+
+/// A networked game of Snek, as seen by one of the peers
+pub struct NetGame {
+    pub state: NetState,
+    pub session: Session,
+    local: Box<dyn Move>,
+    due: u32,
+    pub(crate) inputs: InputState,
+    pub(crate) renderer: Renderer,
+}
+
+impl NetGame {
+    /// Start this peer's copy of the game, with the specified player making the local moves
+    pub fn new(session: Session, local: Box<dyn Move>) -> Self {
+        let state = session.setup().new_game();
+
+        Self {
+            state,
+            session,
+            local,
+            due: 0,
+            inputs: InputState::default(),
+            renderer: Renderer::default(),
+        }
+    }
+
+    /// Ask the local player for their move, with the specified keys held down (along with any gamepad buttons), and pass it on to the Session
+    pub fn poll_local(&mut self, keys: &HashSet<KeyCode>) {
+        self.inputs.set_keys(keys);
+
+        let view = View {
+            me: self.session.setup().me as usize,
+            grid: &self.state.grid,
+            sneks: &self.state.sneks,
+            food: &self.state.food,
+            keys: self.inputs.held(),
+        };
+
+        let dir = self.local.make_move(&view);
+        self.session.queue_move(dir);
+    }
+
+    /// Note that another tick's worth of time has passed
+    pub fn tick_due(&mut self) {
+        // Don't let the backlog grow without limit while we're stuck waiting for a slow peer
+        self.due = (self.due + 1).min(self.session.setup().delay + 1);
+    }
+
+    /// Simulate as many of the ticks that are due as the other players' moves allow
+    pub fn catch_up(&mut self) -> std::io::Result<()> {
+        if self.due == 0 {
+            return self.session.poll(&mut self.state);
+        }
+
+        while self.due > 0 && self.session.advance(&mut self.state)? {
+            self.due -= 1;
+        }
+
+        Ok(())
+    }
+}
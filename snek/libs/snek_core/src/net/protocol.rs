@@ -0,0 +1,608 @@
+use std::collections::VecDeque;
+use std::convert::TryInto;
+
+use rand_chacha::ChaCha8Rng;
+
+use crate::entities::{
+    food::{Food, FoodKind},
+    snek::Snek,
+};
+use crate::types::{Direction, Vec2};
+
+use super::NetState;
+
+/// Every datagram starts with these bytes, so stray packets from other programs are ignored
+const MAGIC: &[u8; 4] = b"SNEK";
+
+/// A single player's move for a single tick
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Input {
+    pub tick: u32,
+    pub player: u8,
+    pub dir: Option<Direction>,
+}
+
+/// Everything that gets sent between peers
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    /// A client asking to join the host's game
+    Hello,
+    /// The host telling a client everything it needs to start an identical copy of the game
+    Welcome {
+        seed: u64,
+        players: u8,
+        me: u8,
+        delay: u32,
+        width: u32,
+        height: u32,
+    },
+    /// A batch of moves, along with how far the sender has got and the hash of its game at that point
+    Inputs {
+        at: u32,
+        hash: u64,
+        inputs: Vec<Input>,
+    },
+    /// A client that has fallen out of step asking the host for a fresh copy of the game
+    ResyncRequest,
+    /// The host's copy of the game after the specified number of ticks
+    Snapshot { at: u32, state: Vec<u8> },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DecodeError {
+    BadMagic,
+    UnknownMessage(u8),
+    Truncated,
+    BadDirection(u8),
+    BadFoodKind(u8),
+    BadPosition(Vec2),
+    WrongSnekCount { expected: usize, found: usize },
+    BadGridSize(u32, u32),
+    BadPlayer(u8),
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::BadMagic => write!(f, "not a snek packet"),
+            DecodeError::UnknownMessage(kind) => write!(f, "unknown message type {}", kind),
+            DecodeError::Truncated => write!(f, "packet ended early"),
+            DecodeError::BadDirection(code) => write!(f, "unknown direction code {}", code),
+            DecodeError::BadFoodKind(code) => write!(f, "unknown food kind {}", code),
+            DecodeError::BadPosition(pos) => write!(f, "position {:?} is off the grid", pos),
+            DecodeError::WrongSnekCount { expected, found } => write!(
+                f,
+                "expected {} sneks, one for each player, but found {}",
+                expected, found
+            ),
+            DecodeError::BadGridSize(width, height) => {
+                write!(f, "can't play on a {}x{} grid", width, height)
+            }
+            DecodeError::BadPlayer(player) => write!(f, "there's no player {}", player),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Convert a direction into the single byte used to send it
+fn encode_dir(dir: Option<Direction>) -> u8 {
+    match dir {
+        None => 0,
+        Some(Direction::Up) => 1,
+        Some(Direction::Down) => 2,
+        Some(Direction::Left) => 3,
+        Some(Direction::Right) => 4,
+        Some(Direction::UpRight) => 5,
+        Some(Direction::DownRight) => 6,
+        Some(Direction::DownLeft) => 7,
+        Some(Direction::UpLeft) => 8,
+    }
+}
+
+/// Convert a byte back into a direction
+fn decode_dir(code: u8) -> Result<Option<Direction>, DecodeError> {
+    match code {
+        0 => Ok(None),
+        1 => Ok(Some(Direction::Up)),
+        2 => Ok(Some(Direction::Down)),
+        3 => Ok(Some(Direction::Left)),
+        4 => Ok(Some(Direction::Right)),
+        5 => Ok(Some(Direction::UpRight)),
+        6 => Ok(Some(Direction::DownRight)),
+        7 => Ok(Some(Direction::DownLeft)),
+        8 => Ok(Some(Direction::UpLeft)),
+        _ => Err(DecodeError::BadDirection(code)),
+    }
+}
+
+/// Convert a kind of Food into the single byte used to send it
+fn encode_kind(kind: FoodKind) -> u8 {
+    match kind {
+        FoodKind::Normal => 0,
+        FoodKind::Bonus => 1,
+        FoodKind::Poison => 2,
+        FoodKind::Speed => 3,
+        FoodKind::Golden => 4,
+    }
+}
+
+/// Convert a byte back into a kind of Food
+fn decode_kind(code: u8) -> Result<FoodKind, DecodeError> {
+    match code {
+        0 => Ok(FoodKind::Normal),
+        1 => Ok(FoodKind::Bonus),
+        2 => Ok(FoodKind::Poison),
+        3 => Ok(FoodKind::Speed),
+        4 => Ok(FoodKind::Golden),
+        _ => Err(DecodeError::BadFoodKind(code)),
+    }
+}
+
+/// Appends little-endian values to a buffer
+#[derive(Default)]
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, v: u8) -> &mut Self {
+        self.0.push(v);
+        self
+    }
+
+    fn u32(&mut self, v: u32) -> &mut Self {
+        self.0.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+
+    fn i32(&mut self, v: i32) -> &mut Self {
+        self.0.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+
+    fn u64(&mut self, v: u64) -> &mut Self {
+        self.0.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+
+    fn u128(&mut self, v: u128) -> &mut Self {
+        self.0.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+
+    fn bytes(&mut self, v: &[u8]) -> &mut Self {
+        self.0.extend_from_slice(v);
+        self
+    }
+
+    fn vec2(&mut self, v: &Vec2) -> &mut Self {
+        self.i32(v.x).i32(v.y)
+    }
+}
+
+/// Reads little-endian values back out of a buffer
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], DecodeError> {
+        if self.0.len() < n {
+            return Err(DecodeError::Truncated);
+        }
+
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, DecodeError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn i32(&mut self) -> Result<i32, DecodeError> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, DecodeError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn u128(&mut self) -> Result<u128, DecodeError> {
+        Ok(u128::from_le_bytes(self.take(16)?.try_into().unwrap()))
+    }
+
+    fn vec2(&mut self) -> Result<Vec2, DecodeError> {
+        Ok(Vec2::new(self.i32()?, self.i32()?))
+    }
+}
+
+impl Message {
+    /// Turn the Message into a datagram
+    pub fn encode(&self) -> Vec<u8> {
+        let mut w = Writer::default();
+        w.bytes(MAGIC);
+
+        match self {
+            Message::Hello => {
+                w.u8(0);
+            }
+            Message::Welcome {
+                seed,
+                players,
+                me,
+                delay,
+                width,
+                height,
+            } => {
+                w.u8(1)
+                    .u64(*seed)
+                    .u8(*players)
+                    .u8(*me)
+                    .u32(*delay)
+                    .u32(*width)
+                    .u32(*height);
+            }
+            Message::Inputs { at, hash, inputs } => {
+                w.u8(2).u32(*at).u64(*hash).u32(inputs.len() as u32);
+                for input in inputs {
+                    w.u32(input.tick).u8(input.player).u8(encode_dir(input.dir));
+                }
+            }
+            Message::ResyncRequest => {
+                w.u8(3);
+            }
+            Message::Snapshot { at, state } => {
+                w.u8(4).u32(*at).u32(state.len() as u32).bytes(state);
+            }
+        }
+
+        w.0
+    }
+
+    /// Turn a datagram back into a Message
+    pub fn decode(bytes: &[u8]) -> Result<Message, DecodeError> {
+        let mut r = Reader(bytes);
+
+        if r.take(MAGIC.len()).map_err(|_| DecodeError::BadMagic)? != MAGIC {
+            return Err(DecodeError::BadMagic);
+        }
+
+        match r.u8()? {
+            0 => Ok(Message::Hello),
+            1 => {
+                let seed = r.u64()?;
+                let players = r.u8()?;
+                let me = r.u8()?;
+                let delay = r.u32()?;
+                let width = r.u32()?;
+                let height = r.u32()?;
+
+                // The game has to have somewhere to be played, and room in it for us
+                if width == 0 || height == 0 {
+                    return Err(DecodeError::BadGridSize(width, height));
+                }
+                if me >= players {
+                    return Err(DecodeError::BadPlayer(me));
+                }
+
+                Ok(Message::Welcome {
+                    seed,
+                    players,
+                    me,
+                    delay,
+                    width,
+                    height,
+                })
+            }
+            2 => {
+                let at = r.u32()?;
+                let hash = r.u64()?;
+                let count = r.u32()?;
+
+                let inputs = (0..count)
+                    .map(|_| {
+                        Ok(Input {
+                            tick: r.u32()?,
+                            player: r.u8()?,
+                            dir: decode_dir(r.u8()?)?,
+                        })
+                    })
+                    .collect::<Result<Vec<Input>, DecodeError>>()?;
+
+                Ok(Message::Inputs { at, hash, inputs })
+            }
+            3 => Ok(Message::ResyncRequest),
+            4 => {
+                let at = r.u32()?;
+                let len = r.u32()? as usize;
+                let state = r.take(len)?.to_vec();
+
+                Ok(Message::Snapshot { at, state })
+            }
+            kind => Err(DecodeError::UnknownMessage(kind)),
+        }
+    }
+}
+
+/// Write out everything needed to rebuild the simulation side of a networked game
+pub fn encode_state(state: &NetState) -> Vec<u8> {
+    let mut w = Writer::default();
+
+    w.u8(state.play as u8).u8(state.won as u8).u32(state.boost);
+
+    // Food that never expires is sent with a time to live of zero
+    w.u32(state.food.len() as u32);
+    for food in &state.food {
+        w.vec2(food.pos())
+            .u8(encode_kind(food.kind()))
+            .u8(food.ttl().is_some() as u8)
+            .u32(food.ttl().unwrap_or(0));
+    }
+
+    w.u32(state.sneks.len() as u32);
+    for snek in &state.sneks {
+        let parts = snek.parts();
+        w.u8(snek.is_alive() as u8)
+            .u32(snek.score() as u32)
+            .u8(encode_dir(Some(*snek.orientation())))
+            .u32(parts.len() as u32);
+        for part in parts {
+            w.vec2(part);
+        }
+    }
+
+    w.bytes(&state.prng.get_seed())
+        .u64(state.prng.get_stream())
+        .u128(state.prng.get_word_pos());
+
+    w.0
+}
+
+/// Get the most bytes a Snapshot of a game with the specified number of players could ever take up on a Grid with the specified number of squares
+pub fn max_snapshot_len(players: usize, squares: usize) -> usize {
+    // The Message wrapped around the state, then the flags and boost
+    let header = MAGIC.len() + 1 + 4 + 4 + 1 + 1 + 4;
+
+    // Food only ever goes in empty squares
+    let food = 4 + squares * 14;
+
+    // Sneks that die are left where they are, so each of them could cover the whole Grid
+    let sneks = 4 + players * (1 + 4 + 1 + 4 + squares * 8);
+
+    header + food + sneks + 32 + 8 + 16
+}
+
+/// Overwrite the simulation side of a networked game with a previously encoded copy
+pub fn decode_state(bytes: &[u8], state: &mut NetState) -> Result<(), DecodeError> {
+    let mut r = Reader(bytes);
+
+    let play = r.u8()? != 0;
+    let won = r.u8()? != 0;
+    let boost = r.u32()?;
+
+    // Everything has to be on the Grid
+    let grid = &state.grid;
+    let on_grid = |r: &mut Reader| {
+        let pos = r.vec2()?;
+        match grid.contains(&pos) {
+            true => Ok(pos),
+            false => Err(DecodeError::BadPosition(pos)),
+        }
+    };
+
+    let count = r.u32()?;
+    let food = (0..count)
+        .map(|_| {
+            let food = Food::with_kind(on_grid(&mut r)?, decode_kind(r.u8()?)?);
+            let expires = r.u8()? != 0;
+            let ttl = r.u32()?;
+
+            Ok(match expires {
+                true => food.expiring(ttl),
+                false => food,
+            })
+        })
+        .collect::<Result<Vec<Food>, DecodeError>>()?;
+
+    // There has to be exactly one Snek for each player, or the rest of the game goes out of step with them
+    let count = r.u32()? as usize;
+    if count != state.players.len() {
+        return Err(DecodeError::WrongSnekCount {
+            expected: state.players.len(),
+            found: count,
+        });
+    }
+    let sneks = (0..count)
+        .map(|_| {
+            let alive = r.u8()? != 0;
+            let score = r.u32()? as usize;
+            let orientation = decode_dir(r.u8()?)?.ok_or(DecodeError::BadDirection(0))?;
+            let len = r.u32()?;
+            let parts = (0..len)
+                .map(|_| on_grid(&mut r))
+                .collect::<Result<VecDeque<Vec2>, DecodeError>>()?;
+
+            if parts.is_empty() {
+                return Err(DecodeError::Truncated);
+            }
+
+            Ok(Snek::from_parts(parts, grid.bounds(), orientation, alive).with_score(score))
+        })
+        .collect::<Result<Vec<Snek>, DecodeError>>()?;
+
+    let seed: [u8; 32] = r.take(32)?.try_into().unwrap();
+    let stream = r.u64()?;
+    let word_pos = r.u128()?;
+
+    let mut prng = <ChaCha8Rng as rand::SeedableRng>::from_seed(seed);
+    prng.set_stream(stream);
+    prng.set_word_pos(word_pos);
+
+    state.play = play;
+    state.won = won;
+    state.boost = boost;
+    state.food = food;
+    state.sneks = sneks;
+    state.prng = prng;
+
+    // The Sneks have jumped straight to where they are, so there's nothing to slide them along from
+    state.previous_ends = state
+        .sneks
+        .iter()
+        .map(|snek| (snek.parts()[0], *snek.head()))
+        .collect();
+
+    // Causes of death aren't sent, so only keep the ones we saw for ourselves
+    state.deaths.resize(state.sneks.len(), None);
+    for (death, snek) in state.deaths.iter_mut().zip(&state.sneks) {
+        if snek.is_alive() {
+            *death = None;
+        }
+    }
+    state.replays.resize(state.sneks.len(), Default::default());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{RngCore, SeedableRng};
+
+    use crate::entities::grid::Grid;
+    use crate::net::RemotePlayer;
+
+    use super::*;
+
+    #[test]
+    fn message_round_trip() {
+        let messages = vec![
+            Message::Hello,
+            Message::Welcome {
+                seed: 42,
+                players: 3,
+                me: 2,
+                delay: 4,
+                width: 20,
+                height: 15,
+            },
+            Message::Inputs {
+                at: 7,
+                hash: 0xDEAD_BEEF,
+                inputs: vec![
+                    Input {
+                        tick: 10,
+                        player: 0,
+                        dir: Some(Direction::Left),
+                    },
+                    Input {
+                        tick: 10,
+                        player: 1,
+                        dir: None,
+                    },
+                    Input {
+                        tick: 11,
+                        player: 0,
+                        dir: Some(Direction::UpLeft),
+                    },
+                ],
+            },
+            Message::ResyncRequest,
+            Message::Snapshot {
+                at: 3,
+                state: vec![1, 2, 3],
+            },
+        ];
+
+        for message in messages {
+            assert_eq!(Message::decode(&message.encode()), Ok(message));
+        }
+    }
+
+    #[test]
+    fn reject_junk() {
+        assert_eq!(Message::decode(b"nope"), Err(DecodeError::BadMagic));
+        assert_eq!(
+            Message::decode(b"SNEK\x09"),
+            Err(DecodeError::UnknownMessage(9))
+        );
+        assert_eq!(
+            Message::decode(b"SNEK\x02\x01"),
+            Err(DecodeError::Truncated)
+        );
+
+        // Welcomes to a game with nowhere to play, or no place in it for us, are turned away
+        let welcome = |players, me, width, height| Message::Welcome {
+            seed: 1,
+            players,
+            me,
+            delay: 2,
+            width,
+            height,
+        };
+        assert_eq!(
+            Message::decode(&welcome(2, 1, 0, 5).encode()),
+            Err(DecodeError::BadGridSize(0, 5))
+        );
+        assert_eq!(
+            Message::decode(&welcome(2, 2, 5, 5).encode()),
+            Err(DecodeError::BadPlayer(2))
+        );
+        assert_eq!(
+            Message::decode(&welcome(0, 0, 5, 5).encode()),
+            Err(DecodeError::BadPlayer(0))
+        );
+    }
+
+    #[test]
+    fn state_round_trip() {
+        let players = vec![RemotePlayer, RemotePlayer];
+        let prng = ChaCha8Rng::seed_from_u64(7);
+        let mut state = NetState::with_players(Grid::new(8, 6).unwrap(), prng, players);
+
+        state.sneks[1].set_orientation(Direction::Down);
+        for _ in 0..5 {
+            state.step();
+        }
+        state.prng.next_u32();
+        state
+            .food
+            .push(Food::with_kind(Vec2::new(7, 5), FoodKind::Golden).expiring(9));
+        state.boost = 3;
+
+        let bytes = encode_state(&state);
+
+        let players = vec![RemotePlayer, RemotePlayer];
+        let prng = ChaCha8Rng::seed_from_u64(99);
+        let mut copy = NetState::with_players(Grid::new(8, 6).unwrap(), prng, players);
+        decode_state(&bytes, &mut copy).unwrap();
+
+        assert_eq!(copy.hash(), state.hash());
+        assert_eq!(copy.food, state.food);
+        assert_eq!(copy.scores(), state.scores());
+        assert_eq!(copy.prng.next_u64(), state.prng.next_u64());
+        assert_eq!(copy.previous_ends.len(), 2);
+
+        let snapshot = Message::Snapshot {
+            at: 5,
+            state: bytes.clone(),
+        }
+        .encode();
+        assert!(snapshot.len() <= max_snapshot_len(2, copy.grid.len()));
+
+        // A copy of the game for a different number of players is turned away, leaving ours as it was
+        let players = vec![RemotePlayer, RemotePlayer, RemotePlayer];
+        let prng = ChaCha8Rng::seed_from_u64(99);
+        let mut bigger = NetState::with_players(Grid::new(8, 6).unwrap(), prng, players);
+        let before = bigger.hash();
+        assert_eq!(
+            decode_state(&bytes, &mut bigger),
+            Err(DecodeError::WrongSnekCount {
+                expected: 3,
+                found: 2
+            })
+        );
+        assert_eq!(bigger.hash(), before);
+    }
+}
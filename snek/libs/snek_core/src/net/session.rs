@@ -0,0 +1,715 @@
+use std::collections::BTreeMap;
+use std::io::{self, ErrorKind};
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+use crate::entities::{grid::Grid, topology::TopologyKind};
+use crate::types::Direction;
+
+use super::protocol::{self, Input, Message};
+use super::{NetState, RemotePlayer};
+
+/// How long a client keeps knocking on the host's door before giving up
+const JOIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often a client repeats its Hello while waiting to be let in
+const HELLO_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How many ticks of old inputs and hashes to hang on to
+const HISTORY: u32 = 128;
+
+/// Biggest datagram we expect to receive - snapshots of very large games are the only thing that get anywhere near this
+const MAX_DATAGRAM: usize = 65_507;
+
+/// Everything the peers need to agree on before they can run identical copies of the game
+#[derive(Debug, Clone, PartialEq)]
+pub struct Setup {
+    pub seed: u64,
+    pub players: u8,
+    pub me: u8,
+    pub delay: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Setup {
+    /// Create this peer's copy of the game
+    pub fn new_game(&self) -> NetState {
+        let grid = Grid::new(self.width as usize, self.height as usize)
+            .expect("Networked games need a non-empty Grid!");
+        let players = (0..self.players).map(|_| RemotePlayer).collect();
+
+        NetState::seeded(grid, self.seed, players)
+    }
+}
+
+enum Role {
+    /// The host knows where every client is (in player order, starting from player 1) and how far each of them has got
+    Host {
+        clients: Vec<SocketAddr>,
+        client_at: Vec<u32>,
+    },
+    /// Clients only ever talk to the host
+    Client { host: SocketAddr, resyncing: bool },
+}
+
+/// One peer in a lockstep game
+///
+/// Every peer runs the full simulation. Each tick, a peer decides its own move and schedules it `delay` ticks into the future, so it has time to reach everyone else before it's needed.
+/// A tick is only simulated once the moves of every player for that tick have arrived, which keeps all the copies of the game in step.
+/// Clients send their moves to the host, and the host passes them on to everyone else. Clients also compare hashes of their game with the host's, and ask for a fresh copy if they ever disagree.
+pub struct Session {
+    socket: UdpSocket,
+    role: Role,
+    setup: Setup,
+    tick: u32,
+    pending: Option<Direction>,
+    inputs: BTreeMap<u32, Vec<Option<Option<Direction>>>>,
+    hashes: BTreeMap<u32, u64>,
+}
+
+impl Session {
+    /// Host a new game on the specified address, and wait for enough clients to join to fill the remaining player slots
+    pub fn host(
+        addr: impl ToSocketAddrs,
+        players: u8,
+        seed: u64,
+        delay: u32,
+        grid: &Grid,
+    ) -> io::Result<Self> {
+        Self::host_on(UdpSocket::bind(addr)?, players, seed, delay, grid)
+    }
+
+    /// Host a new game on a socket that has already been bound, and wait for enough clients to join to fill the remaining player slots
+    pub fn host_on(
+        socket: UdpSocket,
+        players: u8,
+        seed: u64,
+        delay: u32,
+        grid: &Grid,
+    ) -> io::Result<Self> {
+        assert!(
+            delay > 0,
+            "Lockstep needs at least one tick of input delay!"
+        );
+
+        if players == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "a networked game needs at least one player",
+            ));
+        }
+
+        // Clients are only told the size of the Grid, so they'd be playing on square ones whatever the host picked
+        if grid.topology().kind() != TopologyKind::Square {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "networked games can only be played on square Grids",
+            ));
+        }
+        if grid.wall_count() > 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "networked games can only be played on Grids without walls",
+            ));
+        }
+
+        // Snapshots are sent in a single datagram, so the whole game has to fit in one
+        if protocol::max_snapshot_len(players as usize, grid.len()) > MAX_DATAGRAM {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the Grid is too big to keep a networked game in step on",
+            ));
+        }
+
+        log::warn!("Hosting on {}", socket.local_addr()?);
+
+        let setup = Setup {
+            seed,
+            players,
+            me: 0,
+            delay,
+            width: grid.width() as u32,
+            height: grid.height() as u32,
+        };
+
+        // Wait for the clients to say hello, and tell each of them which player they are
+        let mut clients = Vec::new();
+        let mut buf = vec![0; MAX_DATAGRAM];
+        while clients.len() + 1 < players as usize {
+            let (len, from) = socket.recv_from(&mut buf)?;
+
+            if let Ok(Message::Hello) = Message::decode(&buf[..len]) {
+                if !clients.contains(&from) {
+                    log::warn!("Player {} joined from {}", clients.len() + 1, from);
+                    clients.push(from);
+                }
+
+                let me = clients.iter().position(|c| *c == from).unwrap() as u8 + 1;
+                socket.send_to(&setup.welcome(me).encode(), from)?;
+            }
+        }
+
+        socket.set_nonblocking(true)?;
+
+        let client_at = vec![0; clients.len()];
+
+        Ok(Self::new(socket, Role::Host { clients, client_at }, setup))
+    }
+
+    /// Join a game being hosted at the specified address
+    pub fn join(host: impl ToSocketAddrs) -> io::Result<Self> {
+        let host = host
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "no host address"))?;
+
+        // Listen on the loopback interface when playing locally, otherwise on everything
+        let socket = if host.ip().is_loopback() {
+            UdpSocket::bind("127.0.0.1:0")?
+        } else {
+            UdpSocket::bind("0.0.0.0:0")?
+        };
+        socket.set_read_timeout(Some(HELLO_INTERVAL))?;
+
+        // Keep saying hello until the host answers
+        let started = Instant::now();
+        let mut buf = vec![0; MAX_DATAGRAM];
+        let setup = loop {
+            if started.elapsed() > JOIN_TIMEOUT {
+                return Err(io::Error::new(
+                    ErrorKind::TimedOut,
+                    format!("no answer from host at {}", host),
+                ));
+            }
+
+            socket.send_to(&Message::Hello.encode(), host)?;
+
+            match socket.recv_from(&mut buf) {
+                Ok((len, from)) if from == host => {
+                    if let Ok(Message::Welcome {
+                        seed,
+                        players,
+                        me,
+                        delay,
+                        width,
+                        height,
+                    }) = Message::decode(&buf[..len])
+                    {
+                        break Setup {
+                            seed,
+                            players,
+                            me,
+                            delay,
+                            width,
+                            height,
+                        };
+                    }
+                }
+                Ok(_) => (),
+                Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {}
+                Err(e) => return Err(e),
+            }
+        };
+
+        log::warn!("Joined {} as player {}", host, setup.me);
+        socket.set_nonblocking(true)?;
+
+        let role = Role::Client {
+            host,
+            resyncing: false,
+        };
+
+        Ok(Self::new(socket, role, setup))
+    }
+
+    fn new(socket: UdpSocket, role: Role, setup: Setup) -> Self {
+        let mut session = Self {
+            socket,
+            role,
+            tick: 0,
+            pending: None,
+            inputs: BTreeMap::new(),
+            hashes: BTreeMap::new(),
+            setup,
+        };
+
+        // Nobody gets to move during the first few ticks, as there hasn't been time for any moves to arrive yet
+        for tick in 0..session.setup.delay {
+            for player in 0..session.setup.players {
+                session.record(Input {
+                    tick,
+                    player,
+                    dir: None,
+                });
+            }
+        }
+
+        session
+    }
+
+    /// Get the settings every peer agreed on
+    pub fn setup(&self) -> &Setup {
+        &self.setup
+    }
+
+    /// How many ticks have been simulated so far
+    pub fn tick(&self) -> u32 {
+        self.tick
+    }
+
+    /// Remember the local player's latest move, ready to be scheduled at the next tick
+    pub fn queue_move(&mut self, dir: Option<Direction>) {
+        if dir.is_some() {
+            self.pending = dir;
+        }
+    }
+
+    /// Deal with anything that has arrived from the other peers, without advancing the game
+    pub fn poll(&mut self, state: &mut NetState) -> io::Result<()> {
+        if self.hashes.is_empty() {
+            self.hashes.insert(self.tick, state.hash());
+        }
+
+        self.receive(state)?;
+        self.send()
+    }
+
+    /// Block until something arrives from one of the other peers or the timeout runs out, rather than spinning on `advance`
+    pub fn wait(&self, timeout: Duration) -> io::Result<()> {
+        let mut buf = vec![0; MAX_DATAGRAM];
+
+        self.socket.set_read_timeout(Some(timeout))?;
+        self.socket.set_nonblocking(false)?;
+        let peeked = self.socket.peek_from(&mut buf);
+        self.socket.set_nonblocking(true)?;
+
+        match peeked {
+            Ok(_) => Ok(()),
+            Err(e)
+                if e.kind() == ErrorKind::WouldBlock
+                    || e.kind() == ErrorKind::TimedOut
+                    || e.kind() == ErrorKind::ConnectionReset =>
+            {
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Try to simulate the next tick, returning false if we're still waiting on moves from one of the other players
+    pub fn advance(&mut self, state: &mut NetState) -> io::Result<bool> {
+        self.poll(state)?;
+
+        let moves = match self.inputs.get(&self.tick) {
+            Some(moves) if moves.iter().all(|m| m.is_some()) => moves.clone(),
+            _ => return Ok(false),
+        };
+
+        // Schedule our own move for later, giving it time to reach everyone else
+        let dir = self.pending.take();
+        self.record(Input {
+            tick: self.tick + self.setup.delay,
+            player: self.setup.me,
+            dir,
+        });
+
+        // Everyone has the same moves for this tick, so everyone ends up in the same place
+        for (idx, dir) in moves.into_iter().enumerate() {
+            if let Some(Some(dir)) = dir {
+                state.sneks[idx].set_orientation(dir);
+            }
+        }
+        state.step();
+
+        self.tick += 1;
+        self.hashes.insert(self.tick, state.hash());
+        self.forget_old();
+
+        self.send()?;
+
+        Ok(true)
+    }
+
+    /// Store a move, unless we already have one for that player and tick - the first one to arrive always wins
+    fn record(&mut self, input: Input) {
+        let players = self.setup.players as usize;
+        if input.player as usize >= players {
+            log::warn!("Ignoring move for unknown player {}", input.player);
+            return;
+        }
+
+        let slots = self
+            .inputs
+            .entry(input.tick)
+            .or_insert_with(|| vec![None; players]);
+
+        if slots[input.player as usize].is_none() {
+            slots[input.player as usize] = Some(input.dir);
+        }
+    }
+
+    /// Collect all the moves we know about for the specified range of ticks
+    fn known_inputs(&self, ticks: std::ops::Range<u32>, only: Option<u8>) -> Vec<Input> {
+        self.inputs
+            .range(ticks)
+            .flat_map(|(&tick, slots)| {
+                slots.iter().enumerate().filter_map(move |(player, dir)| {
+                    dir.map(|dir| Input {
+                        tick,
+                        player: player as u8,
+                        dir,
+                    })
+                })
+            })
+            .filter(|input| !matches!(only, Some(player) if input.player != player))
+            .collect()
+    }
+
+    /// Send the other peers everything they might still be waiting for
+    fn send(&self) -> io::Result<()> {
+        let at = self.tick;
+        let hash = self.hashes.get(&at).copied().unwrap_or_default();
+        let horizon = self.tick + self.setup.delay + 1;
+
+        match &self.role {
+            Role::Host { clients, client_at } => {
+                for (addr, &from) in clients.iter().zip(client_at) {
+                    let inputs = self.known_inputs(from..horizon.max(from), None);
+                    self.send_to(&Message::Inputs { at, hash, inputs }, *addr)?;
+                }
+            }
+            Role::Client { host, .. } => {
+                let inputs = self.known_inputs(at..horizon, Some(self.setup.me));
+                self.send_to(&Message::Inputs { at, hash, inputs }, *host)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn send_to(&self, message: &Message, addr: SocketAddr) -> io::Result<()> {
+        match self.socket.send_to(&message.encode(), addr) {
+            Ok(_) => Ok(()),
+            // The other end isn't listening (yet) - it's UDP, so just carry on and try again later
+            Err(e)
+                if e.kind() == ErrorKind::ConnectionRefused
+                    || e.kind() == ErrorKind::WouldBlock =>
+            {
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Read everything waiting on the socket
+    fn receive(&mut self, state: &mut NetState) -> io::Result<()> {
+        let mut buf = vec![0; MAX_DATAGRAM];
+
+        loop {
+            let (len, from) = match self.socket.recv_from(&mut buf) {
+                Ok(received) => received,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e) if e.kind() == ErrorKind::ConnectionReset => continue,
+                Err(e) => return Err(e),
+            };
+
+            match Message::decode(&buf[..len]) {
+                Ok(message) => self.handle(message, from, state)?,
+                Err(e) => log::warn!("Ignoring bad packet from {}: {}", from, e),
+            }
+        }
+    }
+
+    fn handle(
+        &mut self,
+        message: Message,
+        from: SocketAddr,
+        state: &mut NetState,
+    ) -> io::Result<()> {
+        match message {
+            Message::Inputs { at, hash, inputs } => self.receive_inputs(at, hash, inputs, from),
+            Message::Hello => self.welcome_again(from),
+            Message::ResyncRequest => self.send_snapshot(from, state),
+            Message::Snapshot { at, state: bytes } => {
+                self.load_snapshot(at, &bytes, from, state);
+                Ok(())
+            }
+            message => {
+                log::warn!("Ignoring unexpected {:?} from {}", message, from);
+                Ok(())
+            }
+        }
+    }
+
+    /// Which client is at the specified address (only the host knows about clients)
+    fn client_index(&self, addr: SocketAddr) -> Option<usize> {
+        match &self.role {
+            Role::Host { clients, .. } => clients.iter().position(|c| *c == addr),
+            Role::Client { .. } => None,
+        }
+    }
+
+    /// Is the specified address the host of our game
+    fn is_host(&self, addr: SocketAddr) -> bool {
+        matches!(&self.role, Role::Client { host, .. } if *host == addr)
+    }
+
+    fn receive_inputs(
+        &mut self,
+        at: u32,
+        hash: u64,
+        inputs: Vec<Input>,
+        from: SocketAddr,
+    ) -> io::Result<()> {
+        if let Some(idx) = self.client_index(from) {
+            if let Role::Host { client_at, .. } = &mut self.role {
+                client_at[idx] = client_at[idx].max(at);
+            }
+
+            // Clients only get a say over their own Snek
+            let player = idx as u8 + 1;
+            for input in inputs.into_iter().filter(|i| i.player == player) {
+                self.record(input);
+            }
+        } else if self.is_host(from) {
+            // If we've both simulated the same tick but ended up in different places, then something has gone wrong
+            let desynced = matches!(self.hashes.get(&at), Some(ours) if *ours != hash);
+
+            if let Role::Client { resyncing, .. } = &mut self.role {
+                if desynced && !*resyncing {
+                    log::warn!(
+                        "Out of step with the host at tick {}, asking for a resync",
+                        at
+                    );
+                    *resyncing = true;
+                    self.send_to(&Message::ResyncRequest, from)?;
+                }
+            }
+
+            for input in inputs {
+                self.record(input);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// A client that missed its Welcome will keep saying hello, so tell it again
+    fn welcome_again(&self, from: SocketAddr) -> io::Result<()> {
+        match self.client_index(from) {
+            Some(idx) => {
+                let welcome = self.setup.welcome(idx as u8 + 1);
+                self.send_to(&welcome, from)
+            }
+            None => {
+                log::warn!("{} tried to join, but isn't one of our players", from);
+                Ok(())
+            }
+        }
+    }
+
+    /// Send a client that has fallen out of step a copy of the host's game
+    fn send_snapshot(&self, from: SocketAddr, state: &NetState) -> io::Result<()> {
+        if self.client_index(from).is_none() {
+            return Ok(());
+        }
+
+        log::warn!(
+            "Sending {} a fresh copy of the game at tick {}",
+            from,
+            self.tick
+        );
+        let snapshot = Message::Snapshot {
+            at: self.tick,
+            state: protocol::encode_state(state),
+        };
+
+        self.send_to(&snapshot, from)
+    }
+
+    /// Replace our copy of the game with the host's
+    fn load_snapshot(&mut self, at: u32, bytes: &[u8], from: SocketAddr, state: &mut NetState) {
+        if !self.is_host(from) {
+            return;
+        }
+
+        if let Err(e) = protocol::decode_state(bytes, state) {
+            log::warn!("Ignoring bad snapshot from host: {}", e);
+            return;
+        }
+
+        if let Role::Client { resyncing, .. } = &mut self.role {
+            *resyncing = false;
+        }
+
+        self.tick = at;
+        self.hashes.clear();
+        self.hashes.insert(at, state.hash());
+
+        // Make sure we don't leave a gap in our own moves if the host was further ahead than us
+        for tick in at..at + self.setup.delay {
+            self.record(Input {
+                tick,
+                player: self.setup.me,
+                dir: None,
+            });
+        }
+    }
+
+    /// Drop inputs and hashes that are too old to be useful to anyone
+    fn forget_old(&mut self) {
+        let oldest = match &self.role {
+            Role::Host { client_at, .. } => client_at.iter().copied().fold(self.tick, u32::min),
+            Role::Client { .. } => self.tick,
+        }
+        .saturating_sub(HISTORY);
+
+        self.inputs = self.inputs.split_off(&oldest);
+        self.hashes = self.hashes.split_off(&oldest);
+    }
+}
+
+impl Setup {
+    /// The Welcome message telling the specified player about this Setup
+    fn welcome(&self, me: u8) -> Message {
+        Message::Welcome {
+            seed: self.seed,
+            players: self.players,
+            me,
+            delay: self.delay,
+            width: self.width,
+            height: self.height,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    use crate::entities::snek::Snek;
+    use crate::types::Vec2;
+
+    use super::*;
+
+    /// Run a peer until it has simulated the specified number of ticks, turning its Snek according to the script
+    /// Each peer then keeps going until every peer has got there and they all agree on where they are, or until it gives up
+    fn play(
+        mut session: Session,
+        ticks: u32,
+        script: &[(u32, Direction)],
+        corrupt_at: Option<u32>,
+        results: &Mutex<Vec<Option<(u32, u64)>>>,
+    ) -> (u32, u64) {
+        let mut state = session.setup().new_game();
+        let me = session.setup().me as usize;
+        let mut corrupted = false;
+        let started = Instant::now();
+
+        while started.elapsed() < Duration::from_secs(30) {
+            if session.tick() >= ticks {
+                session.poll(&mut state).unwrap();
+
+                let mut results = results.lock().unwrap();
+                results[me] = Some((session.tick(), state.hash()));
+                if results.iter().all(|r| *r == results[me]) {
+                    break;
+                }
+                drop(results);
+
+                session.wait(Duration::from_millis(50)).unwrap();
+                continue;
+            }
+
+            if let Some(&(_, dir)) = script.iter().find(|(t, _)| *t == session.tick()) {
+                session.queue_move(Some(dir));
+            }
+
+            if corrupt_at == Some(session.tick()) && !corrupted {
+                // Knock this peer's copy of the game out of step with everyone else's
+                state.sneks[me] = Snek::new(Vec2::new(3, 3), state.grid.bounds());
+                corrupted = true;
+            }
+
+            if !session.advance(&mut state).unwrap() {
+                session.wait(Duration::from_millis(50)).unwrap();
+            }
+        }
+
+        (session.tick(), state.hash())
+    }
+
+    fn host_and_client(
+        host_script: &'static [(u32, Direction)],
+        client_script: &'static [(u32, Direction)],
+        corrupt_at: Option<u32>,
+    ) -> ((u32, u64), (u32, u64)) {
+        let grid = Grid::new(12, 12).unwrap();
+        let results = Arc::new(Mutex::new(vec![None; 2]));
+
+        // Bind the host's socket up front, so the client knows exactly where to find it
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = socket.local_addr().unwrap();
+
+        let host_results = Arc::clone(&results);
+        let host = thread::spawn(move || {
+            let session = Session::host_on(socket, 2, 1234, 2, &grid).unwrap();
+            play(session, 40, host_script, None, &host_results)
+        });
+
+        let client = thread::spawn(move || {
+            let session = Session::join(addr).unwrap();
+            assert_eq!(session.setup().me, 1);
+            play(session, 40, client_script, corrupt_at, &results)
+        });
+
+        (host.join().unwrap(), client.join().unwrap())
+    }
+
+    #[test]
+    fn peers_stay_in_step() {
+        let (host, client) = host_and_client(
+            &[(3, Direction::Right), (9, Direction::Up)],
+            &[(5, Direction::Left), (12, Direction::Up)],
+            None,
+        );
+
+        assert_eq!(host, client);
+    }
+
+    #[test]
+    fn client_resyncs_after_desync() {
+        let (host, client) =
+            host_and_client(&[(3, Direction::Right)], &[(5, Direction::Left)], Some(10));
+
+        assert_eq!(host, client);
+    }
+
+    #[test]
+    fn refuses_games_that_cant_be_played() {
+        let grid = Grid::new(100, 100).unwrap();
+        let err = Session::host("127.0.0.1:0", 2, 1234, 2, &grid)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+
+        let grid = Grid::new(10, 10).unwrap();
+        let err = Session::host("127.0.0.1:0", 0, 1234, 2, &grid)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+
+        // The clients would never hear about the walls
+        let mut grid = Grid::new(10, 10).unwrap();
+        grid.add_wall(&Vec2::new(3, 3));
+        let err = Session::host("127.0.0.1:0", 2, 1234, 2, &grid)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+
+        // Filling a small Grid right up still fits
+        assert!(protocol::max_snapshot_len(4, Grid::new(20, 20).unwrap().len()) <= MAX_DATAGRAM);
+    }
+}
@@ -0,0 +1,567 @@
+use serde::{Deserialize, Serialize};
+
+use crate::entities::grid::Grid;
+use crate::players::View;
+use crate::types::Vec2;
+
+/// Turns what a player can see into a list of numbers for an AI to think about
+/// Every encoder always produces the same number of values, so that it can be wired straight into a neural network
+pub trait Encoder: std::fmt::Debug + Send {
+    /// How many values `encode` produces
+    fn size(&self) -> usize;
+
+    /// How the values are arranged, e.g. [channels, rows, columns] for encoders that produce a grid - the sizes always multiply up to `size()`
+    fn shape(&self) -> Vec<usize> {
+        vec![self.size()]
+    }
+
+    /// The smallest and largest values `encode` can produce
+    fn range(&self) -> (f32, f32) {
+        (0.0, 1.0)
+    }
+
+    /// Turn the view into exactly `size()` values
+    fn encode(&self, view: &View) -> Vec<f32>;
+
+    /// Describe the encoder, so that it can be saved alongside the brain that was trained on it
+    fn kind(&self) -> EncoderKind;
+}
+
+/// A description of one of the encoders, which can be saved to a file and turned back into the encoder later
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum EncoderKind {
+    Senses,
+    Rays,
+    Window { side: usize },
+    Board { width: usize, height: usize },
+}
+
+impl EncoderKind {
+    /// Build the encoder that this describes, unless the description makes no sense (e.g. a Window with an even side)
+    pub fn build(&self) -> Option<Box<dyn Encoder>> {
+        match *self {
+            EncoderKind::Senses => Some(Box::new(Senses)),
+            EncoderKind::Rays => Some(Box::new(Rays)),
+            EncoderKind::Window { side } if side % 2 == 1 => Some(Box::new(Window::new(side))),
+            EncoderKind::Window { .. } => None,
+            EncoderKind::Board { width, height } => Some(Box::new(Board { width, height })),
+        }
+    }
+}
+
+/// The different things an encoder can spot in a square, in the order they appear in one-hot encodings
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Channel {
+    Wall,
+    Head,
+    Body,
+    Rival,
+    Food,
+}
+
+impl Channel {
+    pub const ALL: [Channel; 5] = [
+        Channel::Wall,
+        Channel::Head,
+        Channel::Body,
+        Channel::Rival,
+        Channel::Food,
+    ];
+
+    /// Work out what's in the specified square, from the point of view of the player - empty squares have nothing in them
+    pub fn of(view: &View, pos: &Vec2) -> Option<Channel> {
+        if view.grid.is_wall(pos) {
+            return Some(Channel::Wall);
+        }
+
+        let snek = view.snek();
+        if snek.head() == pos {
+            return Some(Channel::Head);
+        }
+        if snek.touches(pos) {
+            return Some(Channel::Body);
+        }
+        if view.rivals().any(|rival| rival.touches(pos)) {
+            return Some(Channel::Rival);
+        }
+        if view.food.iter().any(|food| food.pos() == pos) {
+            return Some(Channel::Food);
+        }
+
+        None
+    }
+
+    /// The position of this channel in one-hot encodings
+    pub fn index(&self) -> usize {
+        Channel::ALL
+            .iter()
+            .position(|channel| channel == self)
+            .unwrap()
+    }
+}
+
+/// The ahead, left and right directions for the player's Snek, where left and right are the next ways round the Grid's Topology lets it turn
+fn bearings(view: &View) -> (Vec2, Vec2, Vec2) {
+    let ahead = *view.snek().orientation();
+    let topology = view.grid.topology();
+
+    (
+        ahead.to_vec2(),
+        topology.turn_left(ahead).to_vec2(),
+        topology.turn_right(ahead).to_vec2(),
+    )
+}
+
+/// A handful of simple senses: whether there's danger ahead, to the left and to the right, plus how far ahead and to the left the nearest Food is
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Senses;
+
+impl Encoder for Senses {
+    fn size(&self) -> usize {
+        5
+    }
+
+    fn range(&self) -> (f32, f32) {
+        (-1.0, 1.0)
+    }
+
+    fn kind(&self) -> EncoderKind {
+        EncoderKind::Senses
+    }
+
+    fn encode(&self, view: &View) -> Vec<f32> {
+        let head = view.snek().head();
+        let (ahead, left, right) = bearings(view);
+
+        // Is the square in each direction already taken?
+        let danger = |dir: Vec2| {
+            let pos = Grid::wrap(view.grid.bounds(), &(head + &dir));
+            if view.is_occupied(&pos) {
+                1.0
+            } else {
+                0.0
+            }
+        };
+
+        // Where is the nearest Food relative to the way the Snek is facing?
+        let to_food = view
+            .nearest_food()
+            .map_or(Vec2::default(), |food| food.pos() - head);
+        let size = view.grid.width().max(view.grid.height()) as f32;
+        // Diagonal steps cover two squares' worth of distance at once, so they're scaled down to keep within the range
+        let towards = |dir: Vec2| {
+            let length = (dir.x.abs() + dir.y.abs()) as f32;
+            (to_food.x * dir.x + to_food.y * dir.y) as f32 / (size * length)
+        };
+
+        vec![
+            danger(ahead),
+            danger(left),
+            danger(right),
+            towards(ahead),
+            towards(left),
+        ]
+    }
+}
+
+/// Looks out from the Snek head in 8 directions (starting straight ahead and going round clockwise), reporting how close the nearest wall, Snek body and Food are in each
+/// The rays always go along the rows, columns and diagonals of the Grid, whichever ways its Topology lets the Snek move
+/// Closer things give bigger values - 1 for the very next square, down towards 0 for things far away, and exactly 0 for things that can't be seen at all (walls block the view)
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Rays;
+
+impl Rays {
+    /// How many directions the Snek looks in
+    pub const DIRECTIONS: usize = 8;
+
+    /// How many values each direction produces: wall, body and Food
+    pub const PER_DIRECTION: usize = 3;
+}
+
+impl Encoder for Rays {
+    fn size(&self) -> usize {
+        Self::DIRECTIONS * Self::PER_DIRECTION
+    }
+
+    fn kind(&self) -> EncoderKind {
+        EncoderKind::Rays
+    }
+
+    fn encode(&self, view: &View) -> Vec<f32> {
+        let head = *view.snek().head();
+        let ahead = *view.snek().orientation();
+        let directions =
+            (0..Self::DIRECTIONS as i32).map(|eighths| ahead.rotate(eighths).to_vec2());
+
+        // The Grid wraps around, so stop looking before the ray comes back round to where it started
+        let range = view.grid.width().max(view.grid.height());
+
+        let mut values = Vec::with_capacity(self.size());
+        for dir in directions {
+            let mut seen = [0.0; Self::PER_DIRECTION];
+            let mut pos = head;
+
+            for distance in 1..range {
+                pos = Grid::wrap(view.grid.bounds(), &(pos + dir));
+                if pos == head {
+                    break;
+                }
+
+                let slot = match Channel::of(view, &pos) {
+                    Some(Channel::Wall) => {
+                        seen[0] = 1.0 / distance as f32;
+                        break;
+                    }
+                    Some(Channel::Head) | Some(Channel::Body) | Some(Channel::Rival) => 1,
+                    Some(Channel::Food) => 2,
+                    None => continue,
+                };
+
+                // Only the nearest body and Food count
+                if seen[slot] == 0.0 {
+                    seen[slot] = 1.0 / distance as f32;
+                }
+            }
+
+            values.extend_from_slice(&seen);
+        }
+
+        values
+    }
+}
+
+/// A square patch of the Grid centred on the Snek head, turned so that the Snek is always facing up the patch, with a one-hot value per Channel for each square
+/// Diagonal headings don't line up with the squares, so for those the patch faces the next way round anticlockwise instead
+/// Rows run from furthest ahead to furthest behind, and columns from left to right
+#[derive(Debug, Copy, Clone)]
+pub struct Window {
+    /// How many squares along each side of the patch - always odd, so the head is right in the middle
+    side: usize,
+}
+
+impl Window {
+    /// Create a new Window with the specified number of squares along each side, which must be odd
+    pub fn new(side: usize) -> Self {
+        assert!(
+            side % 2 == 1,
+            "Windows need an odd number of squares per side"
+        );
+
+        Self { side }
+    }
+
+    pub fn side(&self) -> usize {
+        self.side
+    }
+}
+
+impl Default for Window {
+    fn default() -> Self {
+        Self::new(7)
+    }
+}
+
+impl Encoder for Window {
+    fn size(&self) -> usize {
+        self.side * self.side * Channel::ALL.len()
+    }
+
+    fn shape(&self) -> Vec<usize> {
+        vec![self.side, self.side, Channel::ALL.len()]
+    }
+
+    fn kind(&self) -> EncoderKind {
+        EncoderKind::Window { side: self.side }
+    }
+
+    fn encode(&self, view: &View) -> Vec<f32> {
+        let head = view.snek().head();
+        let facing = *view.snek().orientation();
+        let facing = match facing.to_vec2() {
+            Vec2 { x: 0, .. } | Vec2 { y: 0, .. } => facing,
+            _ => facing.rotate(-1),
+        };
+        let (ahead, right) = (facing.to_vec2(), facing.turn_right().to_vec2());
+        let half = (self.side / 2) as i32;
+
+        let mut values = vec![0.0; self.size()];
+        for row in 0..self.side {
+            for column in 0..self.side {
+                let forward = half - row as i32;
+                let across = column as i32 - half;
+                let offset = Vec2::new(
+                    ahead.x * forward + right.x * across,
+                    ahead.y * forward + right.y * across,
+                );
+                let pos = Grid::wrap(view.grid.bounds(), &(head + &offset));
+
+                if let Some(channel) = Channel::of(view, &pos) {
+                    let square = row * self.side + column;
+                    values[square * Channel::ALL.len() + channel.index()] = 1.0;
+                }
+            }
+        }
+
+        values
+    }
+}
+
+/// The whole Grid as it stands, with a one-hot value per Channel for each square
+/// Values are laid out channel by channel, each one a full Grid from the bottom row (y = 0) up, so it can be treated as a (channel, y, x) tensor
+#[derive(Debug, Copy, Clone)]
+pub struct Board {
+    width: usize,
+    height: usize,
+}
+
+impl Board {
+    /// Create a new Board encoder for Grids the same size as the specified Grid
+    pub fn new(grid: &Grid) -> Self {
+        Self {
+            width: grid.width(),
+            height: grid.height(),
+        }
+    }
+}
+
+impl Encoder for Board {
+    fn size(&self) -> usize {
+        self.width * self.height * Channel::ALL.len()
+    }
+
+    fn shape(&self) -> Vec<usize> {
+        vec![Channel::ALL.len(), self.height, self.width]
+    }
+
+    fn kind(&self) -> EncoderKind {
+        EncoderKind::Board {
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    fn encode(&self, view: &View) -> Vec<f32> {
+        assert_eq!(
+            (view.grid.width(), view.grid.height()),
+            (self.width, self.height),
+            "Board encoders only work on Grids of the size they were made for"
+        );
+
+        let squares = self.width * self.height;
+        let mut values = vec![0.0; self.size()];
+
+        for pos in view.grid {
+            if let Some(channel) = Channel::of(view, &pos) {
+                let square = pos.y as usize * self.width + pos.x as usize;
+                values[channel.index() * squares + square] = 1.0;
+            }
+        }
+
+        values
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashSet, VecDeque};
+
+    use crate::entities::{food::Food, snek::Snek, topology::TopologyKind};
+    use crate::env::{Action, Environment, SnekEnv};
+    use crate::types::Direction;
+
+    use super::*;
+
+    /// A 7x7 Grid with a wall, two Sneks and some Food - the player's Snek is 3 long, with its head in the middle, and faces along +y
+    fn scene() -> (Grid, Vec<Snek>, Vec<Food>) {
+        let mut grid = Grid::new(7, 7).unwrap();
+        grid.add_wall(&Vec2::new(3, 6));
+
+        let parts: VecDeque<Vec2> = vec![Vec2::new(3, 1), Vec2::new(3, 2), Vec2::new(3, 3)].into();
+        let me = Snek::from_parts(parts, grid.bounds(), Direction::Up, true);
+        let rival = Snek::new(Vec2::new(5, 3), grid.bounds());
+
+        (grid, vec![me, rival], vec![Food::new(Vec2::new(1, 5))])
+    }
+
+    #[test]
+    fn encoders_match_their_size() {
+        let (grid, sneks, food) = scene();
+        let keys = HashSet::new();
+        let view = View {
+            me: 0,
+            grid: &grid,
+            sneks: &sneks,
+            food: &food,
+            keys: &keys,
+        };
+
+        let encoders: Vec<Box<dyn Encoder>> = vec![
+            Box::new(Senses),
+            Box::new(Rays),
+            Box::new(Window::new(5)),
+            Box::new(Board::new(&grid)),
+        ];
+        for encoder in encoders {
+            let values = encoder.encode(&view);
+            assert_eq!(values.len(), encoder.size(), "{:?}", encoder);
+            assert_eq!(encoder.shape().iter().product::<usize>(), encoder.size());
+            let (low, high) = encoder.range();
+            assert!(
+                values.iter().all(|v| (low..=high).contains(v)),
+                "{:?}",
+                encoder
+            );
+
+            // Each encoder can be described and built again from its description
+            let rebuilt = encoder.kind().build().unwrap();
+            assert_eq!(rebuilt.kind(), encoder.kind());
+            assert_eq!(rebuilt.size(), encoder.size());
+        }
+
+        assert!(EncoderKind::Window { side: 4 }.build().is_none());
+    }
+
+    #[test]
+    fn encoders_work_on_every_topology() {
+        let kinds = [
+            EncoderKind::Senses,
+            EncoderKind::Rays,
+            EncoderKind::Window { side: 5 },
+            EncoderKind::Board {
+                width: 8,
+                height: 6,
+            },
+        ];
+
+        for topology in TopologyKind::ALL.iter().copied() {
+            for kind in kinds.iter() {
+                let encoder = kind.build().unwrap();
+                let size = encoder.size();
+                let (low, high) = encoder.range();
+                let mut env = SnekEnv::new(8, 6, encoder).with_topology(topology);
+                assert_eq!(env.state().grid.topology().kind(), topology);
+
+                // Wander about, turning now and then, and starting again whenever the game ends
+                let mut observation = env.reset(5);
+                for step in 0..100 {
+                    assert_eq!(observation.len(), size, "{:?} on {:?}", kind, topology);
+                    assert!(
+                        observation.iter().all(|v| (low..=high).contains(v)),
+                        "{:?} on {:?}",
+                        kind,
+                        topology
+                    );
+
+                    let transition = env.step(Action::ALL[step % 5 % 3]);
+                    observation = match transition.is_over() {
+                        true => env.reset(step as u64),
+                        false => transition.observation,
+                    };
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rays() {
+        let (grid, sneks, food) = scene();
+        let keys = HashSet::new();
+        let view = View {
+            me: 0,
+            grid: &grid,
+            sneks: &sneks,
+            food: &food,
+            keys: &keys,
+        };
+
+        let values = Rays.encode(&view);
+        let ray = |idx: usize| &values[idx * Rays::PER_DIRECTION..(idx + 1) * Rays::PER_DIRECTION];
+
+        // Straight ahead is the wall, 3 squares away, hiding the Snek's own tail on the other side of the Grid
+        assert_eq!(ray(0), &[1.0 / 3.0, 0.0, 0.0]);
+        // Straight behind is the Snek's own body, right next to the head
+        assert_eq!(ray(4)[1], 1.0);
+        // The Food is 2 squares diagonally ahead and to the left
+        assert_eq!(ray(7)[2], 0.5);
+    }
+
+    #[test]
+    fn window_turns_with_the_snek() {
+        let (grid, mut sneks, food) = scene();
+        let keys = HashSet::new();
+        let window = Window::new(3);
+        let at = |values: &[f32], row: usize, column: usize, channel: Channel| {
+            values[(row * 3 + column) * Channel::ALL.len() + channel.index()]
+        };
+
+        let view = View {
+            me: 0,
+            grid: &grid,
+            sneks: &sneks,
+            food: &food,
+            keys: &keys,
+        };
+        let values = window.encode(&view);
+        assert_eq!(at(&values, 1, 1, Channel::Head), 1.0);
+        assert_eq!(at(&values, 2, 1, Channel::Body), 1.0);
+
+        // Facing along +x instead, the body is now off to the right, even though it hasn't moved
+        sneks[0].set_orientation(Direction::Right);
+        let view = View {
+            me: 0,
+            grid: &grid,
+            sneks: &sneks,
+            food: &food,
+            keys: &keys,
+        };
+        let values = window.encode(&view);
+        assert_eq!(at(&values, 1, 1, Channel::Head), 1.0);
+        assert_eq!(at(&values, 2, 1, Channel::Body), 0.0);
+        assert_eq!(at(&values, 1, 2, Channel::Body), 1.0);
+        assert_eq!(values.iter().filter(|&&v| v == 1.0).count(), 2);
+
+        // Heading diagonally up and to the right, the patch faces straight up as it did to start with
+        sneks[0].set_orientation(Direction::UpRight);
+        let view = View {
+            me: 0,
+            grid: &grid,
+            sneks: &sneks,
+            food: &food,
+            keys: &keys,
+        };
+        let values = window.encode(&view);
+        assert_eq!(at(&values, 1, 1, Channel::Head), 1.0);
+        assert_eq!(at(&values, 2, 1, Channel::Body), 1.0);
+    }
+
+    #[test]
+    fn board_is_one_hot() {
+        let (grid, sneks, food) = scene();
+        let keys = HashSet::new();
+        let view = View {
+            me: 1,
+            grid: &grid,
+            sneks: &sneks,
+            food: &food,
+            keys: &keys,
+        };
+
+        let values = Board::new(&grid).encode(&view);
+        let squares = grid.len();
+        let count = |channel: Channel| {
+            values[channel.index() * squares..(channel.index() + 1) * squares]
+                .iter()
+                .filter(|&&v| v == 1.0)
+                .count()
+        };
+
+        // Seen from the other Snek, the first Snek is the rival
+        assert_eq!(count(Channel::Wall), 1);
+        assert_eq!(count(Channel::Head), 1);
+        assert_eq!(count(Channel::Body), 0);
+        assert_eq!(count(Channel::Rival), 3);
+        assert_eq!(count(Channel::Food), 1);
+        assert_eq!(values[Channel::Food.index() * squares + 5 * 7 + 1], 1.0);
+    }
+}
@@ -0,0 +1,153 @@
+use crate::players::{Move, View};
+use crate::types::{Direction, Vec2};
+
+use super::{direction, room, safe_moves, search, Timeline};
+
+/// Finds the shortest path to some Food, but only takes it if there's still a way out afterwards
+/// With no safe path to any Food it follows its own tail around to buy time, and failing that heads for wherever there's the most room
+#[derive(Debug, Default)]
+pub struct AStarBot;
+
+impl AStarBot {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Move for AStarBot {
+    fn make_move(&mut self, view: &View) -> Option<Direction> {
+        let timeline = Timeline::new(view);
+        let snek = view.snek();
+        let head = snek.head();
+
+        // First, try the Food worth eating, nearest first
+        let mut food: Vec<Vec2> = view
+            .food
+            .iter()
+            .filter(|food| food.kind().points() > 0)
+            .map(|food| *food.pos())
+            .collect();
+        food.sort_by_key(|pos| view.grid.distance(head, pos));
+
+        for target in food {
+            if let Some(path) = search(&timeline, head, &target) {
+                if has_escape(view, &path) {
+                    return direction(view.grid, head, &path[0]);
+                }
+            }
+        }
+
+        // Second, chase the tail, which keeps a way out open until some Food turns up somewhere safer
+        if !snek.is_empty() {
+            if let Some(path) = search(&timeline, head, &snek.parts()[0]) {
+                return direction(view.grid, head, &path[0]);
+            }
+        }
+
+        // Finally, just try to stay alive for as long as possible
+        safe_moves(view, &timeline)
+            .into_iter()
+            .max_by_key(|(dir, next)| (room(&timeline, next), dir == snek.orientation()))
+            .map(|(dir, _)| dir)
+    }
+}
+
+/// Check if the Snek could still reach its own tail after following the specified path to some Food, so it can't get boxed in by eating
+fn has_escape(view: &View, path: &[Vec2]) -> bool {
+    // Work out where the Snek will be after following the path and growing by one
+    let parts = view.snek().parts();
+    let length = parts.len() + 1;
+    let moved: Vec<Vec2> = parts.iter().chain(path.iter()).copied().collect();
+    let moved = &moved[moved.len().saturating_sub(length)..];
+
+    let head = moved[moved.len() - 1];
+    let tail = moved[0];
+    let timeline = Timeline::with_parts(view.grid, moved, view.rivals());
+
+    search(&timeline, &head, &tail).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashSet, VecDeque};
+
+    use crate::entities::{food::Food, grid::Grid, snek::Snek};
+
+    use super::*;
+
+    /// Get the square the Snek would end up in after the specified move
+    fn next_square(grid: &Grid, head: Vec2, dir: Direction) -> Vec2 {
+        grid.step(&head, dir)
+    }
+
+    #[test]
+    fn astar_bot() {
+        // A wall stands between the Snek and the Food, with a gap at the very top
+        let mut grid = Grid::new(7, 7).unwrap();
+        for y in 0..7 {
+            grid.add_wall(&Vec2::new(0, y));
+        }
+        for y in 0..6 {
+            grid.add_wall(&Vec2::new(3, y));
+        }
+        for x in 0..7 {
+            grid.add_wall(&Vec2::new(x, 0));
+        }
+        let sneks = vec![Snek::new(Vec2::new(2, 3), grid.bounds())];
+        let food = vec![Food::new(Vec2::new(5, 3))];
+        let keys = HashSet::new();
+
+        let view = View {
+            me: 0,
+            grid: &grid,
+            sneks: &sneks,
+            food: &food,
+            keys: &keys,
+        };
+        let dir = AStarBot::new().make_move(&view).unwrap();
+        assert_eq!(next_square(&grid, Vec2::new(2, 3), dir), Vec2::new(2, 4));
+    }
+
+    #[test]
+    fn astar_escape() {
+        // The Food sits at the end of a dead end, with walls all around the edge of the Grid
+        let mut grid = Grid::new(7, 6).unwrap();
+        for pos in grid.into_iter().collect::<Vec<_>>() {
+            if pos.x == 0 || pos.x == 6 || pos.y == 0 || pos.y == 5 {
+                grid.add_wall(&pos);
+            }
+        }
+        grid.add_wall(&Vec2::new(2, 3));
+        grid.add_wall(&Vec2::new(2, 4));
+
+        let parts = VecDeque::from(vec![Vec2::new(3, 2), Vec2::new(2, 2), Vec2::new(1, 2)]);
+        let sneks = vec![Snek::from_parts(
+            parts,
+            grid.bounds(),
+            Direction::Left,
+            true,
+        )];
+        let food = vec![Food::new(Vec2::new(1, 4))];
+        let keys = HashSet::new();
+        let view = View {
+            me: 0,
+            grid: &grid,
+            sneks: &sneks,
+            food: &food,
+            keys: &keys,
+        };
+
+        // Eating the Food would leave the Snek's own body blocking the only way out, so it goes after its tail instead
+        assert!(!has_escape(&view, &[Vec2::new(1, 3), Vec2::new(1, 4)]));
+        let dir = AStarBot::new().make_move(&view).unwrap();
+        assert_eq!(next_square(&grid, Vec2::new(1, 2), dir), Vec2::new(1, 1));
+
+        // Food out in the open is fine though
+        let food = vec![Food::new(Vec2::new(1, 1))];
+        let view = View {
+            food: &food,
+            ..view
+        };
+        assert!(has_escape(&view, &[Vec2::new(1, 1)]));
+    }
+}
@@ -0,0 +1,75 @@
+use crate::players::{Move, View};
+use crate::types::Direction;
+
+use super::{safe_moves, Timeline};
+
+/// Heads straight for the nearest Food, only looking one move ahead to avoid crashing
+#[derive(Debug, Default)]
+pub struct GreedyBot;
+
+impl GreedyBot {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Move for GreedyBot {
+    fn make_move(&mut self, view: &View) -> Option<Direction> {
+        let timeline = Timeline::new(view);
+        let snek = view.snek();
+
+        // Of the moves that won't crash straight away, pick the one that gets closest to the Food, sticking with the current direction if it's a tie
+        let target = view.nearest_food().map(|food| *food.pos());
+        safe_moves(view, &timeline)
+            .into_iter()
+            .min_by_key(|(dir, next)| {
+                let distance = target.map_or(0, |food| view.grid.distance(next, &food));
+                (distance, dir != snek.orientation())
+            })
+            .map(|(dir, _)| dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::entities::{food::Food, grid::Grid, snek::Snek};
+    use crate::types::Vec2;
+
+    use super::*;
+
+    #[test]
+    fn greedy_bot() {
+        let mut grid = Grid::new(5, 5).unwrap();
+        let sneks = vec![Snek::new(Vec2::new(2, 2), grid.bounds())];
+        let food = vec![Food::new(Vec2::new(4, 2))];
+        let keys = HashSet::new();
+
+        let mut bot = GreedyBot::new();
+        let mut make_move = |grid: &Grid| {
+            let view = View {
+                me: 0,
+                grid,
+                sneks: &sneks,
+                food: &food,
+                keys: &keys,
+            };
+            bot.make_move(&view)
+        };
+
+        // Head straight for the Food
+        assert_eq!(make_move(&grid), Some(Direction::Right));
+
+        // Don't run into a wall to get there, even if it's the shortest way
+        grid.add_wall(&Vec2::new(3, 2));
+        let dir = make_move(&grid).unwrap();
+        assert!([Direction::Up, Direction::Down, Direction::Left].contains(&dir));
+
+        // Give up if there's nowhere safe to go
+        for wall in &[Vec2::new(2, 1), Vec2::new(2, 3), Vec2::new(1, 2)] {
+            grid.add_wall(wall);
+        }
+        assert_eq!(make_move(&grid), None);
+    }
+}
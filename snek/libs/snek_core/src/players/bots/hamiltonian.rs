@@ -0,0 +1,204 @@
+use crate::entities::grid::Grid;
+use crate::players::{Move, View};
+use crate::types::{Direction, Vec2};
+
+use super::{direction, AStarBot, Timeline};
+
+/// A route that visits every square of the Grid exactly once before coming back to where it started
+#[derive(Debug)]
+struct Cycle {
+    bounds: Vec2,
+    /// The squares in the order they're visited
+    squares: Vec<Vec2>,
+    /// How far along the route each square is, indexed the same way as the Grid
+    order: Vec<usize>,
+}
+
+impl Cycle {
+    /// Build a cycle for the specified Grid, as long as it has an even number of rows or columns and no walls
+    fn new(grid: &Grid) -> Option<Self> {
+        if grid.wall_count() > 0 || grid.width() < 2 || grid.height() < 2 {
+            return None;
+        }
+
+        // Zigzag along one dimension, so it needs to be even for the route to end up back next to the start
+        let squares = match (grid.width() % 2, grid.height() % 2) {
+            (_, 0) => zigzag(grid.width(), grid.height()),
+            (0, _) => zigzag(grid.height(), grid.width())
+                .into_iter()
+                .map(|pos| Vec2::new(pos.y, pos.x))
+                .collect(),
+            _ => return None,
+        };
+
+        let mut order = vec![0; grid.len()];
+        for (idx, pos) in squares.iter().enumerate() {
+            order[grid.index_at_xy(*pos)?] = idx;
+        }
+
+        Some(Self {
+            bounds: *grid.bounds(),
+            squares,
+            order,
+        })
+    }
+
+    /// How far along the route the specified square is
+    fn position(&self, pos: &Vec2) -> usize {
+        self.order[pos.x as usize + pos.y as usize * self.bounds.x as usize]
+    }
+
+    /// Get the square that comes after the specified one
+    fn next(&self, pos: &Vec2) -> Vec2 {
+        self.squares[(self.position(pos) + 1) % self.squares.len()]
+    }
+
+    /// How many moves it takes to get from a to b by following the route
+    fn distance(&self, a: &Vec2, b: &Vec2) -> usize {
+        let len = self.squares.len();
+        (self.position(b) + len - self.position(a)) % len
+    }
+}
+
+/// Visit every square of a width x height Grid, where height is even
+/// Go along the bottom row, snake back and forth over every row above it (skipping the first column), then come back down the first column
+fn zigzag(width: usize, height: usize) -> Vec<Vec2> {
+    let mut squares: Vec<Vec2> = (0..width).map(|x| Vec2::new(x as i32, 0)).collect();
+
+    for y in 1..height {
+        let row = (1..width).map(|x| Vec2::new(x as i32, y as i32));
+        if y % 2 == 1 {
+            squares.extend(row.rev());
+        } else {
+            squares.extend(row);
+        }
+    }
+
+    squares.extend((1..height).rev().map(|y| Vec2::new(0, y as i32)));
+
+    squares
+}
+
+/// Follows a fixed route around every square of the Grid, which is slow but guaranteed to fill the whole thing
+/// While the Snek is still short it takes shortcuts towards the Food, as long as they can't cut it off from its own tail
+/// Grids without a route (odd by odd ones, or ones with walls) and games with rivals getting in the way are left to an AStarBot
+#[derive(Debug, Default)]
+pub struct HamiltonianBot {
+    cycle: Option<Cycle>,
+    fallback: AStarBot,
+}
+
+impl HamiltonianBot {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Move for HamiltonianBot {
+    fn make_move(&mut self, view: &View) -> Option<Direction> {
+        // Only work out the route again if the Grid has changed size
+        let stale = match &self.cycle {
+            Some(cycle) => &cycle.bounds != view.grid.bounds(),
+            None => true,
+        };
+        if stale {
+            self.cycle = Cycle::new(view.grid);
+        }
+
+        let cycle = match &self.cycle {
+            Some(cycle) if view.grid.wall_count() == 0 => cycle,
+            _ => return self.fallback.make_move(view),
+        };
+
+        let timeline = Timeline::new(view);
+        let snek = view.snek();
+        let head = snek.head();
+
+        let mut best = cycle.next(head);
+        if !timeline.is_free(&best, 1) {
+            return self.fallback.make_move(view);
+        }
+
+        // Everything between the head and the tail along the route is empty, so it's safe to skip ahead as long as the Snek doesn't overtake its tail or pass by the Food
+        if view.rivals().next().is_none() && snek.len() < view.grid.len() / 2 {
+            let gap = match snek.is_empty() {
+                true => cycle.squares.len(),
+                false => cycle.distance(head, &snek.parts()[0]),
+            };
+            let target = view
+                .nearest_food()
+                .map_or(gap, |food| cycle.distance(head, food.pos()));
+
+            for next in view.grid.neighbours(head) {
+                let skip = cycle.distance(head, &next);
+                if skip < gap
+                    && skip <= target
+                    && skip > cycle.distance(head, &best)
+                    && timeline.is_free(&next, 1)
+                {
+                    best = next;
+                }
+            }
+        }
+
+        direction(view.grid, head, &best)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::entities::topology::TopologyKind;
+    use crate::game::state::GameState;
+
+    use super::*;
+
+    #[test]
+    fn hamiltonian_cycle() {
+        for &(width, height) in &[(2, 2), (4, 4), (5, 4), (4, 5), (6, 3)] {
+            let grid = Grid::new(width, height).unwrap();
+            let cycle = Cycle::new(&grid).unwrap();
+
+            // Every square is visited exactly once, and each step (including the one back to the start) only moves one square, without wrapping around the edges
+            assert_eq!(cycle.squares.len(), grid.len());
+            for pos in &grid {
+                assert_eq!(cycle.squares[cycle.position(&pos)], pos);
+                let next = cycle.next(&pos);
+                assert_eq!((next.x - pos.x).abs() + (next.y - pos.y).abs(), 1);
+            }
+        }
+
+        // Odd by odd Grids don't have a route, and neither do ones with walls
+        assert!(Cycle::new(&Grid::new(5, 5).unwrap()).is_none());
+        let mut grid = Grid::new(4, 4).unwrap();
+        grid.add_wall(&Vec2::new(1, 1));
+        assert!(Cycle::new(&grid).is_none());
+    }
+
+    #[test]
+    fn hamiltonian_fills_grid() {
+        // The route only ever steps up, down, left or right, which every Topology allows
+        for kind in TopologyKind::ALL.iter().copied() {
+            let prng = ChaCha8Rng::from_seed(Default::default());
+            let grid = Grid::new(4, 4).unwrap().with_topology(kind);
+            let mut state = GameState::new(grid, prng, HamiltonianBot::new());
+            state.food_config.special_chance = 0.0;
+
+            let keys = HashSet::new();
+            for _ in 0..1000 {
+                if !state.play {
+                    break;
+                }
+                state.poll_players(&keys);
+                state.step();
+            }
+
+            assert!(state.won, "{:?}", kind);
+            assert!(state.sneks[0].is_alive(), "{:?}", kind);
+        }
+    }
+}
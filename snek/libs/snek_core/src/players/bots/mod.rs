@@ -0,0 +1,246 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+
+use crate::entities::{grid::Grid, snek::Snek};
+use crate::players::View;
+use crate::types::{Direction, Vec2};
+
+pub mod astar;
+pub mod greedy;
+pub mod hamiltonian;
+
+pub use astar::AStarBot;
+pub use greedy::GreedyBot;
+pub use hamiltonian::HamiltonianBot;
+
+/// How many moves from now each square of the Grid will be free to move into
+/// Sneks clear away from the tail end as they move, so a square near the tail is only blocked for a little while
+struct Timeline<'a> {
+    grid: &'a Grid,
+    free_after: Vec<usize>,
+}
+
+impl<'a> Timeline<'a> {
+    /// Work out when each square will be free, as the player sees the game right now
+    fn new(view: &View<'a>) -> Self {
+        let own: Vec<Vec2> = view.snek().parts().iter().copied().collect();
+        Self::with_parts(view.grid, &own, view.rivals())
+    }
+
+    /// Work out when each square will be free, if the player's Snek was made of the specified parts (tail first)
+    fn with_parts<'b>(
+        grid: &'a Grid,
+        own: &[Vec2],
+        rivals: impl Iterator<Item = &'b Snek>,
+    ) -> Self {
+        let mut timeline = Self {
+            grid,
+            free_after: vec![0; grid.len()],
+        };
+
+        for pos in grid.into_iter().filter(|pos| grid.is_wall(pos)) {
+            timeline.block(&pos, usize::MAX);
+        }
+
+        // The player's own tail moves out of the way just in time for the head to follow it
+        for (idx, part) in own.iter().enumerate() {
+            timeline.block(part, idx + 1);
+        }
+
+        // Rivals might eat something and grow, so give them an extra move to get out of the way
+        for snek in rivals {
+            for (idx, part) in snek.parts().iter().enumerate() {
+                timeline.block(part, idx + 2);
+            }
+        }
+
+        timeline
+    }
+
+    /// Make sure the specified square isn't free for at least the specified number of moves
+    fn block(&mut self, pos: &Vec2, moves: usize) {
+        if let Some(idx) = self.grid.index_at_xy(*pos) {
+            self.free_after[idx] = self.free_after[idx].max(moves);
+        }
+    }
+
+    /// Check if the specified square will be free to move into after the specified number of moves
+    fn is_free(&self, pos: &Vec2, moves: usize) -> bool {
+        match self.grid.index_at_xy(*pos) {
+            Some(idx) => self.free_after[idx] <= moves,
+            None => false,
+        }
+    }
+}
+
+/// Get the direction that leads from one square to the square next to it
+fn direction(grid: &Grid, from: &Vec2, to: &Vec2) -> Option<Direction> {
+    grid.topology()
+        .directions()
+        .iter()
+        .copied()
+        .find(|&dir| &grid.step(from, dir) == to)
+}
+
+/// Find the shortest path from start to goal, as a list of the squares to move through (not counting start)
+fn search(timeline: &Timeline, start: &Vec2, goal: &Vec2) -> Option<Vec<Vec2>> {
+    let grid = timeline.grid;
+
+    // Squares waiting to be looked at, cheapest first, along with the number of moves it took to get to them
+    let mut open = BinaryHeap::new();
+    let mut moves: HashMap<Vec2, usize> = HashMap::new();
+    let mut came_from: HashMap<Vec2, Vec2> = HashMap::new();
+
+    open.push(Reverse((grid.distance(start, goal), 0, start.x, start.y)));
+    moves.insert(*start, 0);
+
+    while let Some(Reverse((_, so_far, x, y))) = open.pop() {
+        let pos = Vec2::new(x, y);
+        if &pos == goal && so_far > 0 {
+            // Walk back along the path to see how we got here
+            let mut path = vec![pos];
+            let mut current = pos;
+            while let Some(prev) = came_from.get(&current) {
+                if prev == start {
+                    break;
+                }
+                path.push(*prev);
+                current = *prev;
+            }
+            path.reverse();
+
+            return Some(path);
+        }
+
+        // Skip squares that have since been reached more cheaply
+        if matches!(moves.get(&pos), Some(&best) if best < so_far) {
+            continue;
+        }
+
+        for next in grid.neighbours(&pos) {
+            let cost = so_far + 1;
+            if !timeline.is_free(&next, cost) {
+                continue;
+            }
+            // The start square counts as already visited, unless it's also where we want to end up
+            if &next == start && &next != goal {
+                continue;
+            }
+            if !matches!(moves.get(&next), Some(&best) if best <= cost) {
+                moves.insert(next, cost);
+                came_from.insert(next, pos);
+                open.push(Reverse((
+                    cost + grid.distance(&next, goal),
+                    cost,
+                    next.x,
+                    next.y,
+                )));
+            }
+        }
+    }
+
+    None
+}
+
+/// Count how many squares can be reached from the specified square, which is a rough measure of how much room there is to manoeuvre
+fn room(timeline: &Timeline, start: &Vec2) -> usize {
+    let mut seen: HashMap<Vec2, usize> = HashMap::new();
+    let mut queue = VecDeque::new();
+
+    seen.insert(*start, 1);
+    queue.push_back((*start, 1));
+
+    while let Some((pos, moves)) = queue.pop_front() {
+        for next in timeline.grid.neighbours(&pos) {
+            if !seen.contains_key(&next) && timeline.is_free(&next, moves + 1) {
+                seen.insert(next, moves + 1);
+                queue.push_back((next, moves + 1));
+            }
+        }
+    }
+
+    seen.len()
+}
+
+/// Get the moves that won't crash the Snek straight away, along with the square each one leads to
+fn safe_moves(view: &View, timeline: &Timeline) -> Vec<(Direction, Vec2)> {
+    let head = view.snek().head();
+
+    view.grid
+        .topology()
+        .directions()
+        .iter()
+        .map(|&dir| (dir, view.grid.step(head, dir)))
+        .filter(|(_, next)| timeline.is_free(next, 1))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::entities::topology::TopologyKind;
+    use crate::game::state::GameState;
+    use crate::players::Move;
+
+    use super::*;
+
+    #[test]
+    fn timeline() {
+        let mut grid = Grid::new(5, 5).unwrap();
+        grid.add_wall(&Vec2::new(4, 4));
+        let own = vec![Vec2::new(0, 0), Vec2::new(1, 0), Vec2::new(2, 0)];
+        let rival = Snek::new(Vec2::new(3, 3), grid.bounds());
+
+        let timeline = Timeline::with_parts(&grid, &own, std::iter::once(&rival));
+
+        // The tail clears out of the way straight away, but the head takes a while
+        assert!(timeline.is_free(&Vec2::new(0, 0), 1));
+        assert!(!timeline.is_free(&Vec2::new(2, 0), 2));
+        assert!(timeline.is_free(&Vec2::new(2, 0), 3));
+
+        // Rivals get an extra move, and walls never go anywhere
+        assert!(!timeline.is_free(&Vec2::new(3, 3), 1));
+        assert!(timeline.is_free(&Vec2::new(3, 3), 2));
+        assert!(!timeline.is_free(&Vec2::new(4, 4), 1000));
+
+        // Paths can't cut back through the body before it has cleared out of the way, so have to go around the edge instead
+        let path = search(&timeline, &Vec2::new(2, 0), &Vec2::new(0, 0)).unwrap();
+        assert_eq!(
+            path,
+            vec![Vec2::new(3, 0), Vec2::new(4, 0), Vec2::new(0, 0)]
+        );
+    }
+
+    #[test]
+    fn bots_play_on_every_topology() {
+        for kind in TopologyKind::ALL.iter().copied() {
+            let bots: Vec<Box<dyn Move>> = vec![
+                Box::new(GreedyBot::new()),
+                Box::new(AStarBot::new()),
+                Box::new(HamiltonianBot::new()),
+            ];
+
+            for (idx, bot) in bots.into_iter().enumerate() {
+                let grid = Grid::new(12, 10).unwrap().with_topology(kind);
+                let mut state = GameState::seeded(grid, 8, vec![bot]);
+                let topology = state.grid.topology();
+
+                // Every move the bot makes is one the Grid allows, and it manages to find some Food
+                let keys = HashSet::new();
+                for _ in 0..100 {
+                    state.poll_players(&keys);
+                    assert!(
+                        topology.allows(*state.sneks[0].orientation()),
+                        "{:?} {}",
+                        kind,
+                        idx
+                    );
+                    state.step();
+                }
+
+                assert!(state.sneks[0].score() > 0, "{:?} {}", kind, idx);
+            }
+        }
+    }
+}
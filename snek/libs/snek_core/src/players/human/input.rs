@@ -0,0 +1,265 @@
+use std::collections::HashSet;
+use std::convert::TryFrom;
+
+use ggez::input::gamepad::gilrs::{Axis, Button};
+use ggez::input::gamepad::GamepadId;
+use ggez::input::keyboard::KeyCode;
+use serde::{Deserialize, Serialize};
+
+use super::player::key_code;
+
+/// How far a stick has to be pushed before it counts as being held in that direction
+const STICK_THRESHOLD: f32 = 0.5;
+
+/// Every gamepad button, so they can be looked up by name
+const BUTTONS: [Button; 20] = [
+    Button::South,
+    Button::East,
+    Button::North,
+    Button::West,
+    Button::C,
+    Button::Z,
+    Button::LeftTrigger,
+    Button::LeftTrigger2,
+    Button::RightTrigger,
+    Button::RightTrigger2,
+    Button::Select,
+    Button::Start,
+    Button::Mode,
+    Button::LeftThumb,
+    Button::RightThumb,
+    Button::DPadUp,
+    Button::DPadDown,
+    Button::DPadLeft,
+    Button::DPadRight,
+    Button::Unknown,
+];
+
+/// Every gamepad axis, so they can be looked up by name
+const AXES: [Axis; 9] = [
+    Axis::LeftStickX,
+    Axis::LeftStickY,
+    Axis::LeftZ,
+    Axis::RightStickX,
+    Axis::RightStickY,
+    Axis::RightZ,
+    Axis::DPadX,
+    Axis::DPadY,
+    Axis::Unknown,
+];
+
+/// Something a human player can hold down
+/// Gamepads are numbered from 0 in the order they're first used, but written from 1 to match the players, e.g. "Pad1:DPadUp"
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Input {
+    Key(KeyCode),
+    Button(usize, Button),
+    /// A stick pushed along an axis, towards the positive end or the negative end, e.g. "Pad1:LeftStickY+" for up
+    Stick(usize, Axis, bool),
+}
+
+impl Input {
+    /// Get the inputs for each of the specified keys
+    pub fn keys(keys: &HashSet<KeyCode>) -> HashSet<Input> {
+        keys.iter().map(|&key| Input::Key(key)).collect()
+    }
+}
+
+impl std::fmt::Display for Input {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Input::Key(key) => write!(f, "{:?}", key),
+            Input::Button(pad, button) => write!(f, "Pad{}:{:?}", pad + 1, button),
+            Input::Stick(pad, axis, positive) => {
+                let sign = match positive {
+                    true => '+',
+                    false => '-',
+                };
+                write!(f, "Pad{}:{:?}{}", pad + 1, axis, sign)
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for Input {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("there's no key or button called '{}'", s);
+        let name = s.trim();
+
+        // Anything that isn't on a gamepad is a key
+        let (pad, name) = match name.to_lowercase().strip_prefix("pad") {
+            Some(rest) => {
+                let (pad, name) = rest.split_once(':').ok_or_else(err)?;
+                let pad: usize = pad.parse().map_err(|_| err())?;
+                (pad.checked_sub(1).ok_or_else(err)?, name.to_owned())
+            }
+            None => return key_code(name).map(Input::Key).ok_or_else(err),
+        };
+
+        let named = |value: &dyn std::fmt::Debug| format!("{:?}", value).to_lowercase() == name;
+        if let Some(&button) = BUTTONS.iter().find(|button| named(*button)) {
+            return Ok(Input::Button(pad, button));
+        }
+
+        let (axis, positive) = match (name.strip_suffix('+'), name.strip_suffix('-')) {
+            (Some(axis), _) => (axis, true),
+            (_, Some(axis)) => (axis, false),
+            _ => return Err(err()),
+        };
+        AXES.iter()
+            .find(|a| format!("{:?}", a).to_lowercase() == axis)
+            .map(|&axis| Input::Stick(pad, axis, positive))
+            .ok_or_else(err)
+    }
+}
+
+impl TryFrom<String> for Input {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Input> for String {
+    fn from(input: Input) -> Self {
+        input.to_string()
+    }
+}
+
+/// Keeps track of which keys, gamepad buttons and stick directions are being held down
+/// Keys can be read from ggez whenever they're needed, but gamepads only say what's changed, so their state is built up here
+#[derive(Debug, Default)]
+pub struct InputState {
+    held: HashSet<Input>,
+    /// Each gamepad seen so far, in the order they were first used
+    pads: Vec<GamepadId>,
+}
+
+impl InputState {
+    /// Get everything being held down right now
+    pub fn held(&self) -> &HashSet<Input> {
+        &self.held
+    }
+
+    /// Replace the keys being held down with the specified ones, leaving the gamepads alone
+    pub fn set_keys(&mut self, keys: &HashSet<KeyCode>) {
+        self.held.retain(|input| !matches!(input, Input::Key(_)));
+        self.held.extend(keys.iter().map(|&key| Input::Key(key)));
+    }
+
+    /// Get the number of the specified gamepad, numbering it if it hasn't been seen before
+    pub fn pad(&mut self, id: GamepadId) -> usize {
+        match self.pads.iter().position(|&pad| pad == id) {
+            Some(pad) => pad,
+            None => {
+                self.pads.push(id);
+                self.pads.len() - 1
+            }
+        }
+    }
+
+    /// Note that a button has been pressed, returning it as an Input
+    pub fn button_down(&mut self, pad: usize, button: Button) -> Input {
+        let input = Input::Button(pad, button);
+        self.held.insert(input);
+        input
+    }
+
+    /// Note that a button has been let go
+    pub fn button_up(&mut self, pad: usize, button: Button) {
+        self.held.remove(&Input::Button(pad, button));
+    }
+
+    /// Note where a stick has moved to along one of its axes, returning the direction it's just been pushed in (if any)
+    pub fn axis(&mut self, pad: usize, axis: Axis, value: f32) -> Option<Input> {
+        let positive = Input::Stick(pad, axis, true);
+        let negative = Input::Stick(pad, axis, false);
+        let pushed = match value {
+            v if v >= STICK_THRESHOLD => Some(positive),
+            v if v <= -STICK_THRESHOLD => Some(negative),
+            _ => None,
+        };
+
+        // Only a push that wasn't already being held counts as new
+        let new = pushed.filter(|input| !self.held.contains(input));
+
+        self.held.remove(&positive);
+        self.held.remove(&negative);
+        self.held.extend(pushed);
+
+        new
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn input_names() {
+        let inputs = [
+            Input::Key(KeyCode::Left),
+            Input::Key(KeyCode::Key3),
+            Input::Button(0, Button::DPadUp),
+            Input::Button(3, Button::South),
+            Input::Stick(1, Axis::LeftStickY, true),
+            Input::Stick(0, Axis::RightStickX, false),
+        ];
+
+        // Every input survives being written out and read back in
+        for input in &inputs {
+            assert_eq!(input.to_string().parse::<Input>(), Ok(*input));
+        }
+
+        assert_eq!(inputs[2].to_string(), "Pad1:DPadUp");
+        assert_eq!(inputs[4].to_string(), "Pad2:LeftStickY+");
+        assert_eq!("w".parse(), Ok(Input::Key(KeyCode::W)));
+        assert_eq!("pad2:south".parse(), Ok(Input::Button(1, Button::South)));
+
+        assert!("Pad0:South".parse::<Input>().is_err());
+        assert!("Pad1:LeftStickY".parse::<Input>().is_err());
+        assert!("Pad1:Banana".parse::<Input>().is_err());
+        assert!("Banana".parse::<Input>().is_err());
+    }
+
+    #[test]
+    fn held_inputs() {
+        let mut state = InputState::default();
+
+        let keys: HashSet<_> = vec![KeyCode::A].into_iter().collect();
+        state.set_keys(&keys);
+        assert_eq!(
+            state.button_down(0, Button::South),
+            Input::Button(0, Button::South)
+        );
+
+        // Sticks only count once they're pushed far enough, and only say so when they're first pushed
+        assert_eq!(state.axis(0, Axis::LeftStickX, 0.2), None);
+        assert_eq!(
+            state.axis(0, Axis::LeftStickX, -0.9),
+            Some(Input::Stick(0, Axis::LeftStickX, false))
+        );
+        assert_eq!(state.axis(0, Axis::LeftStickX, -1.0), None);
+        assert!(state
+            .held()
+            .contains(&Input::Stick(0, Axis::LeftStickX, false)));
+
+        // New keys replace the old ones, without letting go of the gamepad
+        state.set_keys(&HashSet::new());
+        let expected: HashSet<_> = vec![
+            Input::Button(0, Button::South),
+            Input::Stick(0, Axis::LeftStickX, false),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(state.held(), &expected);
+
+        state.button_up(0, Button::South);
+        state.axis(0, Axis::LeftStickX, 0.0);
+        assert!(state.held().is_empty());
+    }
+}
@@ -1,20 +1,314 @@
+use std::collections::HashSet;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use ggez::input::gamepad::gilrs::{Axis, Button};
 use ggez::input::keyboard;
+use serde::{Deserialize, Deserializer, Serialize};
 
-use crate::entities::snek::{FACING_DOWN, FACING_LEFT, FACING_RIGHT, FACING_UP};
-use crate::players::Move;
-use crate::types::Vec2;
+use crate::players::{Move, View};
+use crate::scores;
+use crate::types::Direction;
 
-#[derive(Debug)]
+use super::input::Input;
+
+const FILE_NAME: &str = "controls.toml";
+
+/// The things a human player can ask their Snek to do
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Control {
+    Left,
+    Right,
+    Up,
+    Down,
+    /// Turn left from wherever the Snek is heading, rather than heading left across the screen
+    TurnLeft,
+    TurnRight,
+}
+
+impl Control {
+    /// Every Control, in the order they're checked - if more than one is held down, the first one wins
+    pub const ALL: [Control; 6] = [
+        Control::Left,
+        Control::Right,
+        Control::Up,
+        Control::Down,
+        Control::TurnLeft,
+        Control::TurnRight,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Control::Left => "Left",
+            Control::Right => "Right",
+            Control::Up => "Up",
+            Control::Down => "Down",
+            Control::TurnLeft => "Turn left",
+            Control::TurnRight => "Turn right",
+        }
+    }
+}
+
+/// Which keys, buttons and sticks do what, with any number of them bound to each Control
+/// In a file, each Control is a list of input names (see `Input`), or a single name
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Controls {
-    left: keyboard::KeyCode,
-    right: keyboard::KeyCode,
-    up: keyboard::KeyCode,
-    down: keyboard::KeyCode,
+    #[serde(deserialize_with = "one_or_many")]
+    left: Vec<Input>,
+    #[serde(deserialize_with = "one_or_many")]
+    right: Vec<Input>,
+    #[serde(deserialize_with = "one_or_many")]
+    up: Vec<Input>,
+    #[serde(deserialize_with = "one_or_many")]
+    down: Vec<Input>,
+    #[serde(deserialize_with = "one_or_many")]
+    turn_left: Vec<Input>,
+    #[serde(deserialize_with = "one_or_many")]
+    turn_right: Vec<Input>,
+}
+
+/// The layout of a saved controls file
+#[derive(Serialize, Deserialize)]
+struct SavedControls {
+    controls: Vec<Controls>,
+}
+
+impl Controls {
+    /// Create Controls with a single key for each direction, and nothing to turn with
+    pub fn keys(
+        left: keyboard::KeyCode,
+        right: keyboard::KeyCode,
+        up: keyboard::KeyCode,
+        down: keyboard::KeyCode,
+    ) -> Self {
+        Self {
+            left: vec![Input::Key(left)],
+            right: vec![Input::Key(right)],
+            up: vec![Input::Key(up)],
+            down: vec![Input::Key(down)],
+            ..Self::default()
+        }
+    }
+
+    /// Also steer with the d-pad and left stick of the specified gamepad
+    pub fn with_gamepad(mut self, pad: usize) -> Self {
+        self.bind(Control::Left, Input::Button(pad, Button::DPadLeft));
+        self.bind(Control::Right, Input::Button(pad, Button::DPadRight));
+        self.bind(Control::Up, Input::Button(pad, Button::DPadUp));
+        self.bind(Control::Down, Input::Button(pad, Button::DPadDown));
+        self.bind(Control::Left, Input::Stick(pad, Axis::LeftStickX, false));
+        self.bind(Control::Right, Input::Stick(pad, Axis::LeftStickX, true));
+        self.bind(Control::Up, Input::Stick(pad, Axis::LeftStickY, true));
+        self.bind(Control::Down, Input::Stick(pad, Axis::LeftStickY, false));
+        self
+    }
+
+    /// The arrow keys, or the first gamepad
+    pub fn arrows() -> Self {
+        Self::keys(
+            keyboard::KeyCode::Left,
+            keyboard::KeyCode::Right,
+            keyboard::KeyCode::Up,
+            keyboard::KeyCode::Down,
+        )
+        .with_gamepad(0)
+    }
+
+    /// The W, A, S and D keys, or the second gamepad
+    pub fn wasd() -> Self {
+        Self::keys(
+            keyboard::KeyCode::A,
+            keyboard::KeyCode::D,
+            keyboard::KeyCode::W,
+            keyboard::KeyCode::S,
+        )
+        .with_gamepad(1)
+    }
+
+    /// The I, J, K and L keys, or the third gamepad
+    pub fn ijkl() -> Self {
+        Self::keys(
+            keyboard::KeyCode::J,
+            keyboard::KeyCode::L,
+            keyboard::KeyCode::I,
+            keyboard::KeyCode::K,
+        )
+        .with_gamepad(2)
+    }
+
+    /// Get the built-in control schemes, in the order they are handed out to human players
+    /// This is used to make sure every human in a multiplayer game has their own keys
+    pub fn presets() -> Vec<Self> {
+        vec![Self::arrows(), Self::wasd(), Self::ijkl()]
+    }
+
+    /// Get everything bound to the specified Control
+    pub fn bindings(&self, control: Control) -> &[Input] {
+        match control {
+            Control::Left => &self.left,
+            Control::Right => &self.right,
+            Control::Up => &self.up,
+            Control::Down => &self.down,
+            Control::TurnLeft => &self.turn_left,
+            Control::TurnRight => &self.turn_right,
+        }
+    }
+
+    fn bindings_mut(&mut self, control: Control) -> &mut Vec<Input> {
+        match control {
+            Control::Left => &mut self.left,
+            Control::Right => &mut self.right,
+            Control::Up => &mut self.up,
+            Control::Down => &mut self.down,
+            Control::TurnLeft => &mut self.turn_left,
+            Control::TurnRight => &mut self.turn_right,
+        }
+    }
+
+    /// Add another input for the specified Control, unless it's already bound to it
+    pub fn bind(&mut self, control: Control, input: Input) {
+        let bindings = self.bindings_mut(control);
+        if !bindings.contains(&input) {
+            bindings.push(input);
+        }
+    }
+
+    /// Forget everything bound to the specified Control
+    pub fn clear(&mut self, control: Control) {
+        self.bindings_mut(control).clear();
+    }
+
+    /// Get the first Control with any of its inputs held down, if there is one
+    pub fn held(&self, inputs: &HashSet<Input>) -> Option<Control> {
+        Control::ALL.iter().copied().find(|&control| {
+            self.bindings(control)
+                .iter()
+                .any(|input| inputs.contains(input))
+        })
+    }
+
+    /// Where rebound controls are kept, following the conventions of the current platform
+    pub fn default_path() -> Option<PathBuf> {
+        scores::data_dir().map(|dir| dir.join(FILE_NAME))
+    }
+
+    /// Load a set of Controls for each player from a file written by `save_all`
+    pub fn load_all(path: impl AsRef<Path>) -> std::io::Result<Vec<Self>> {
+        let contents = std::fs::read_to_string(path)?;
+        let saved: SavedControls = toml::from_str(&contents)
+            .map_err(|e| std::io::Error::new(ErrorKind::InvalidData, e))?;
+
+        match saved.controls.is_empty() {
+            true => Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                "there must be at least one set of controls",
+            )),
+            false => Ok(saved.controls),
+        }
+    }
+
+    /// Save a set of Controls for each player to the specified file
+    pub fn save_all(path: impl AsRef<Path>, controls: &[Self]) -> std::io::Result<()> {
+        let saved = SavedControls {
+            controls: controls.to_vec(),
+        };
+        let contents =
+            toml::to_string(&saved).map_err(|e| std::io::Error::new(ErrorKind::InvalidData, e))?;
+
+        if let Some(dir) = path.as_ref().parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, contents)
+    }
+}
+
+/// Accept either a single input name, or a list of them
+fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<Input>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(Input),
+        Many(Vec<Input>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(input) => vec![input],
+        OneOrMany::Many(inputs) => inputs,
+    })
+}
+
+/// Look up a key by name (ignoring case), e.g. "Left", "W", "3", "Numpad8", "F1" or "Space"
+pub fn key_code(name: &str) -> Option<keyboard::KeyCode> {
+    use keyboard::KeyCode::*;
+
+    const LETTERS: [keyboard::KeyCode; 26] = [
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    ];
+    const DIGITS: [keyboard::KeyCode; 10] =
+        [Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9];
+    const NUMPAD: [keyboard::KeyCode; 10] = [
+        Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9,
+    ];
+    const FUNCTION: [keyboard::KeyCode; 12] = [F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12];
+
+    let name = name.trim().to_lowercase();
+
+    // Letters and digits are just themselves
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return match c {
+            'a'..='z' => Some(LETTERS[c as usize - 'a' as usize]),
+            '0'..='9' => Some(DIGITS[c as usize - '0' as usize]),
+            _ => None,
+        };
+    }
+
+    // Numbered keys
+    let numbered = |prefix: &str, keys: &[keyboard::KeyCode], first: usize| {
+        name.strip_prefix(prefix)
+            .and_then(|n| n.parse::<usize>().ok())
+            .and_then(|n| n.checked_sub(first))
+            .and_then(|idx| keys.get(idx).copied())
+    };
+    if let Some(key) = numbered("numpad", &NUMPAD, 0)
+        .or_else(|| numbered("key", &DIGITS, 0))
+        .or_else(|| numbered("f", &FUNCTION, 1))
+    {
+        return Some(key);
+    }
+
+    match name.as_str() {
+        "left" => Some(Left),
+        "right" => Some(Right),
+        "up" => Some(Up),
+        "down" => Some(Down),
+        "space" => Some(Space),
+        "return" | "enter" => Some(Return),
+        "back" | "backspace" => Some(Back),
+        "tab" => Some(Tab),
+        "escape" | "esc" => Some(Escape),
+        "minus" => Some(Minus),
+        "equals" => Some(Equals),
+        "plus" => Some(Plus),
+        "numpadadd" => Some(NumpadAdd),
+        "numpadsubtract" => Some(NumpadSubtract),
+        "lshift" => Some(LShift),
+        "rshift" => Some(RShift),
+        "lcontrol" => Some(LControl),
+        "rcontrol" => Some(RControl),
+        _ => None,
+    }
 }
 
 #[derive(Debug)]
 pub struct HumanPlayer {
     controls: Controls,
+    /// What was held down last time the player was asked for a move, so that turns only happen once per press
+    last: Option<Control>,
 }
 
 impl HumanPlayer {
@@ -24,13 +318,14 @@ impl HumanPlayer {
         up: keyboard::KeyCode,
         down: keyboard::KeyCode,
     ) -> HumanPlayer {
+        Self::with_controls(Controls::keys(left, right, up, down))
+    }
+
+    /// Create a new HumanPlayer using the specified Controls
+    pub fn with_controls(controls: Controls) -> HumanPlayer {
         let player = HumanPlayer {
-            controls: Controls {
-                left,
-                right,
-                up,
-                down,
-            },
+            controls,
+            last: None,
         };
 
         log::warn!("New HumanPlayer: {:?}", &player);
@@ -42,12 +337,8 @@ impl HumanPlayer {
 impl Default for HumanPlayer {
     fn default() -> Self {
         let player = HumanPlayer {
-            controls: Controls {
-                left: keyboard::KeyCode::Left,
-                right: keyboard::KeyCode::Right,
-                up: keyboard::KeyCode::Up,
-                down: keyboard::KeyCode::Down,
-            },
+            controls: Controls::arrows(),
+            last: None,
         };
 
         log::warn!("New default HumanPlayer: {:?}", &player);
@@ -57,24 +348,35 @@ impl Default for HumanPlayer {
 }
 
 impl Move for HumanPlayer {
-    fn make_move(&self, _ctx: &mut ggez::Context) -> Option<Vec2> {
+    fn make_move(&mut self, view: &View) -> Option<Direction> {
         // Check for key presses and return a new direction for the Snek accordingly
-        if keyboard::is_key_pressed(_ctx, self.controls.left) {
-            Some(FACING_LEFT)
-        } else if keyboard::is_key_pressed(_ctx, self.controls.right) {
-            Some(FACING_RIGHT)
-        } else if keyboard::is_key_pressed(_ctx, self.controls.up) {
-            Some(FACING_UP)
-        } else if keyboard::is_key_pressed(_ctx, self.controls.down) {
-            Some(FACING_DOWN)
-        } else {
-            None
+        let held = self.controls.held(view.keys);
+        let pressed = held != self.last;
+        self.last = held;
+
+        // Players are asked for their move every frame, so holding a turn down mustn't keep on turning
+        // Turns step through whichever ways the Grid lets a Snek head, so they go round the diagonals too where there are any
+        let orientation = *view.snek().orientation();
+        let topology = view.grid.topology();
+        match held {
+            Some(Control::Left) => Some(Direction::Left),
+            Some(Control::Right) => Some(Direction::Right),
+            Some(Control::Up) => Some(Direction::Up),
+            Some(Control::Down) => Some(Direction::Down),
+            Some(Control::TurnLeft) if pressed => Some(topology.turn_left(orientation)),
+            Some(Control::TurnRight) if pressed => Some(topology.turn_right(orientation)),
+            _ => None,
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashSet;
+
+    use crate::entities::{food::Food, grid::Grid, snek::Snek};
+    use crate::types::Vec2;
+
     use super::*;
 
     #[test]
@@ -85,18 +387,139 @@ mod tests {
             keyboard::KeyCode::W,
             keyboard::KeyCode::S,
         );
-        assert_eq!(player.controls.left, keyboard::KeyCode::A);
-        assert_eq!(player.controls.right, keyboard::KeyCode::D);
-        assert_eq!(player.controls.up, keyboard::KeyCode::W);
-        assert_eq!(player.controls.down, keyboard::KeyCode::S);
+        let key = |control| player.controls.bindings(control);
+        assert_eq!(key(Control::Left), &[Input::Key(keyboard::KeyCode::A)]);
+        assert_eq!(key(Control::Right), &[Input::Key(keyboard::KeyCode::D)]);
+        assert_eq!(key(Control::Up), &[Input::Key(keyboard::KeyCode::W)]);
+        assert_eq!(key(Control::Down), &[Input::Key(keyboard::KeyCode::S)]);
+        assert!(key(Control::TurnLeft).is_empty());
     }
 
     #[test]
     fn default_human_player() {
         let player = HumanPlayer::default();
-        assert_eq!(player.controls.left, keyboard::KeyCode::Left);
-        assert_eq!(player.controls.right, keyboard::KeyCode::Right);
-        assert_eq!(player.controls.up, keyboard::KeyCode::Up);
-        assert_eq!(player.controls.down, keyboard::KeyCode::Down);
+        let key = |control| player.controls.bindings(control)[0];
+        assert_eq!(key(Control::Left), Input::Key(keyboard::KeyCode::Left));
+        assert_eq!(key(Control::Right), Input::Key(keyboard::KeyCode::Right));
+        assert_eq!(key(Control::Up), Input::Key(keyboard::KeyCode::Up));
+        assert_eq!(key(Control::Down), Input::Key(keyboard::KeyCode::Down));
+
+        // ...along with the first gamepad
+        assert!(player
+            .controls
+            .bindings(Control::Up)
+            .contains(&Input::Button(0, Button::DPadUp)));
+    }
+
+    #[test]
+    fn key_names() {
+        assert_eq!(key_code("Left"), Some(keyboard::KeyCode::Left));
+        assert_eq!(key_code("w"), Some(keyboard::KeyCode::W));
+        assert_eq!(key_code("7"), Some(keyboard::KeyCode::Key7));
+        assert_eq!(key_code("Key0"), Some(keyboard::KeyCode::Key0));
+        assert_eq!(key_code("Numpad8"), Some(keyboard::KeyCode::Numpad8));
+        assert_eq!(key_code("F12"), Some(keyboard::KeyCode::F12));
+        assert_eq!(key_code(" ENTER "), Some(keyboard::KeyCode::Return));
+
+        assert_eq!(key_code("F0"), None);
+        assert_eq!(key_code("F13"), None);
+        assert_eq!(key_code("Numpad10"), None);
+        assert_eq!(key_code("?"), None);
+        assert_eq!(key_code("Banana"), None);
+    }
+
+    #[test]
+    fn distinct_controls() {
+        // Two human players on the same keyboard must not react to each other's keys
+        let mut p1 = HumanPlayer::with_controls(Controls::arrows());
+        let mut p2 = HumanPlayer::with_controls(Controls::wasd());
+
+        let grid = Grid::default();
+        let sneks = vec![Snek::default(), Snek::new(Vec2::new(5, 0), grid.bounds())];
+        let food = Food::new(Vec2::new(3, 3));
+        let keys: HashSet<_> = vec![Input::Key(keyboard::KeyCode::A)].into_iter().collect();
+
+        let view = View {
+            me: 0,
+            grid: &grid,
+            sneks: &sneks,
+            food: std::slice::from_ref(&food),
+            keys: &keys,
+        };
+        assert_eq!(p1.make_move(&view), None);
+
+        let view = View { me: 1, ..view };
+        assert_eq!(p2.make_move(&view), Some(Direction::Left));
+    }
+
+    #[test]
+    fn gamepads_and_turning() {
+        let mut controls = Controls::keys(
+            keyboard::KeyCode::A,
+            keyboard::KeyCode::D,
+            keyboard::KeyCode::W,
+            keyboard::KeyCode::S,
+        )
+        .with_gamepad(1);
+        controls.bind(Control::TurnLeft, Input::Key(keyboard::KeyCode::Q));
+        controls.bind(Control::TurnLeft, Input::Button(1, Button::West));
+        let mut player = HumanPlayer::with_controls(controls);
+
+        let grid = Grid::default();
+        let sneks = vec![Snek::default()];
+        let food = Food::new(Vec2::new(3, 3));
+        let turn = Some(sneks[0].orientation().turn_left());
+        let mut move_with = |inputs: Vec<Input>| {
+            let keys: HashSet<Input> = inputs.into_iter().collect();
+            let view = View {
+                me: 0,
+                grid: &grid,
+                sneks: &sneks,
+                food: std::slice::from_ref(&food),
+                keys: &keys,
+            };
+            player.make_move(&view)
+        };
+
+        // The d-pad and stick of the player's own gamepad steer, but nobody else's
+        assert_eq!(
+            move_with(vec![Input::Button(1, Button::DPadDown)]),
+            Some(Direction::Down)
+        );
+        assert_eq!(
+            move_with(vec![Input::Stick(1, Axis::LeftStickX, false)]),
+            Some(Direction::Left)
+        );
+        assert_eq!(move_with(vec![Input::Button(0, Button::DPadDown)]), None);
+
+        // Turns only happen once for each press, whichever of the bindings is used
+        assert_eq!(move_with(vec![Input::Key(keyboard::KeyCode::Q)]), turn);
+        assert_eq!(move_with(vec![Input::Key(keyboard::KeyCode::Q)]), None);
+        assert_eq!(move_with(vec![]), None);
+        assert_eq!(move_with(vec![Input::Button(1, Button::West)]), turn);
+    }
+
+    #[test]
+    fn save_and_load_controls() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(FILE_NAME);
+
+        let mut controls = Controls::presets();
+        controls[0].clear(Control::Left);
+        controls[0].bind(Control::TurnRight, Input::Stick(0, Axis::RightStickX, true));
+        Controls::save_all(&path, &controls).unwrap();
+        assert_eq!(Controls::load_all(&path).unwrap(), controls);
+
+        // Single names are fine too, and anything left out isn't bound to anything
+        std::fs::write(&path, "[[controls]]\nup = \"Pad1:North\"\n").unwrap();
+        let loaded = Controls::load_all(&path).unwrap();
+        assert_eq!(
+            loaded[0].bindings(Control::Up),
+            &[Input::Button(0, Button::North)]
+        );
+        assert!(loaded[0].bindings(Control::Down).is_empty());
+
+        std::fs::write(&path, "[[controls]]\nup = \"Pad1:Banana\"\n").unwrap();
+        assert!(Controls::load_all(&path).is_err());
     }
 }
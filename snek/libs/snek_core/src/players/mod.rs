@@ -1,6 +1,13 @@
-use crate::types::Vec2;
+use std::collections::HashSet;
 
+use crate::entities::{food::Food, grid::Grid, snek::Snek};
+use crate::types::{Direction, Vec2};
+
+use human::input::Input;
+
+pub mod bots;
 pub mod human;
+pub mod replay;
 
 #[derive(Debug)]
 pub enum Player {
@@ -8,21 +15,68 @@ pub enum Player {
     Computer,
 }
 
+/// Everything a player gets to see when deciding on its next move
+pub struct View<'a> {
+    /// The index of the player's own Snek in `sneks`
+    pub me: usize,
+    pub grid: &'a Grid,
+    pub sneks: &'a [Snek],
+    pub food: &'a [Food],
+    /// The keys, gamepad buttons and sticks currently being held down (always empty for headless games)
+    pub keys: &'a HashSet<Input>,
+}
+
+impl<'a> View<'a> {
+    /// Get an immutable ref to the player's own Snek
+    pub fn snek(&self) -> &Snek {
+        &self.sneks[self.me]
+    }
+
+    /// Get an iterator over every other Snek that is still alive
+    pub fn rivals(&self) -> impl Iterator<Item = &Snek> {
+        let me = self.me;
+        self.sneks
+            .iter()
+            .enumerate()
+            .filter(move |(idx, snek)| *idx != me && snek.is_alive())
+            .map(|(_, snek)| snek)
+    }
+
+    /// Get the closest Food to the player's Snek head that is actually worth eating (i.e. not Poison)
+    pub fn nearest_food(&self) -> Option<&Food> {
+        let head = self.snek().head();
+
+        self.food
+            .iter()
+            .filter(|food| food.kind().points() > 0)
+            .min_by_key(|food| self.grid.distance(head, food.pos()))
+    }
+
+    /// Check if the specified position is a wall, or is occupied by any living Snek
+    pub fn is_occupied(&self, pos: &Vec2) -> bool {
+        self.grid.is_wall(pos)
+            || self
+                .sneks
+                .iter()
+                .any(|snek| snek.is_alive() && snek.touches(pos))
+    }
+}
+
 /// All players should implement Move in order to play the game
 pub trait Move {
-    fn make_move(&self, _ctx: &mut ggez::Context) -> Option<Vec2>;
+    fn make_move(&mut self, view: &View) -> Option<Direction>;
 }
 
-/// Implement `Move` for boxed references to a `Move`
-impl<'a, M: Move + ?Sized> Move for &'a mut M {
-    fn make_move(&self, _ctx: &mut ggez::Context) -> Option<Vec2> {
-        (**self).make_move(_ctx)
+/// Implement `Move` for mutable references to a `Move`
+impl<M: Move + ?Sized> Move for &mut M {
+    fn make_move(&mut self, view: &View) -> Option<Direction> {
+        (**self).make_move(view)
     }
 }
 
 /// Implement `Move` for boxed trait objects
 impl Move for Box<dyn Move> {
-    fn make_move(&self, _ctx: &mut ggez::Context) -> Option<Vec2> {
-        self.as_ref().make_move(_ctx)
+    fn make_move(&mut self, view: &View) -> Option<Direction> {
+        self.as_mut().make_move(view)
     }
 }
@@ -0,0 +1,232 @@
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::players::{Move, View};
+use crate::scores;
+use crate::types::{Direction, Vec2};
+
+/// The name of the file the last human game is saved to, inside the game's data directory
+const FILE_NAME: &str = "last_game.toml";
+
+/// The way a Snek was heading on every tick of a game, so that the game can be played back later
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    moves: Vec<Direction>,
+    /// The seed the game was played on, if it was known, so the Food turns up in the same places when it's played back
+    seed: Option<u64>,
+}
+
+/// The layout of a replay file - the moves are written as one letter per tick (U, D, L or R) to keep the files small
+/// Diagonal moves are written as the digit in that corner of a numeric keypad (7, 9, 1 or 3)
+/// TOML numbers only go up to i64::MAX, so the seed is written as a string
+#[derive(Serialize, Deserialize)]
+struct ReplayFile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    seed: Option<String>,
+    moves: String,
+}
+
+impl Replay {
+    /// Create a new Replay from the specified moves, one for each tick
+    pub fn new(moves: Vec<Direction>) -> Self {
+        Self { moves, seed: None }
+    }
+
+    /// Where the last human game is saved by default, following the conventions of the current platform
+    pub fn default_path() -> Option<PathBuf> {
+        scores::data_dir().map(|dir| dir.join(FILE_NAME))
+    }
+
+    /// Get an immutable ref to the moves, one for each tick
+    pub fn moves(&self) -> &[Direction] {
+        &self.moves
+    }
+
+    /// Get the seed the game was played on, if it's known
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub(crate) fn set_seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+    }
+
+    /// Add the next tick's move to the end of the Replay
+    pub(crate) fn push(&mut self, orientation: Direction) {
+        self.moves.push(orientation);
+    }
+
+    /// Read a Replay from the contents of a replay file
+    pub fn parse(contents: &str) -> std::io::Result<Self> {
+        let invalid = |msg: String| std::io::Error::new(ErrorKind::InvalidData, msg);
+
+        let file: ReplayFile = toml::from_str(contents).map_err(|e| invalid(e.to_string()))?;
+        let moves = file
+            .moves
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| match c {
+                'U' => Ok(Direction::Up),
+                'D' => Ok(Direction::Down),
+                'L' => Ok(Direction::Left),
+                'R' => Ok(Direction::Right),
+                '9' => Ok(Direction::UpRight),
+                '3' => Ok(Direction::DownRight),
+                '1' => Ok(Direction::DownLeft),
+                '7' => Ok(Direction::UpLeft),
+                _ => Err(invalid(format!("'{}' isn't a move", c))),
+            })
+            .collect::<Result<_, _>>()?;
+        let seed = file
+            .seed
+            .map(|seed| {
+                seed.trim()
+                    .parse()
+                    .map_err(|_| invalid(format!("'{}' isn't a seed", seed)))
+            })
+            .transpose()?;
+
+        Ok(Self { moves, seed })
+    }
+
+    /// Load a Replay from the specified file
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Write the Replay out to the specified file
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let moves = self
+            .moves
+            .iter()
+            .map(|dir| match *dir {
+                Direction::Up => 'U',
+                Direction::Down => 'D',
+                Direction::Left => 'L',
+                Direction::Right => 'R',
+                Direction::UpRight => '9',
+                Direction::DownRight => '3',
+                Direction::DownLeft => '1',
+                Direction::UpLeft => '7',
+            })
+            .collect();
+        let contents = toml::to_string(&ReplayFile {
+            seed: self.seed.map(|seed| seed.to_string()),
+            moves,
+        })
+        .map_err(|e| std::io::Error::new(ErrorKind::InvalidData, e))?;
+
+        if let Some(dir) = path.as_ref().parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, contents)
+    }
+}
+
+/// Plays back a Replay, making the recorded move for each tick and carrying straight on once the moves run out
+/// Players can be asked for their move more than once a tick, so a new tick is spotted by the Snek's head having moved on
+#[derive(Debug)]
+pub struct ReplayPlayer {
+    replay: Replay,
+    tick: usize,
+    last_head: Option<Vec2>,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            replay,
+            tick: 0,
+            last_head: None,
+        }
+    }
+}
+
+impl Move for ReplayPlayer {
+    fn make_move(&mut self, view: &View) -> Option<Direction> {
+        let head = *view.snek().head();
+        if matches!(self.last_head, Some(last) if last != head) {
+            self.tick += 1;
+        }
+        self.last_head = Some(head);
+
+        self.replay.moves.get(self.tick).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::entities::grid::Grid;
+    use crate::game::state::GameState;
+    use crate::players::bots::GreedyBot;
+
+    use super::*;
+
+    #[test]
+    fn replay_file() {
+        let mut replay = Replay::parse("moves = \"UURR\\nDL\"").unwrap();
+        assert_eq!(
+            replay.moves(),
+            &[
+                Direction::Up,
+                Direction::Up,
+                Direction::Right,
+                Direction::Right,
+                Direction::Down,
+                Direction::Left
+            ]
+        );
+
+        assert_eq!(replay.seed(), None);
+        assert_eq!(
+            Replay::parse("seed = \"7\"\nmoves = \"U\"").unwrap().seed(),
+            Some(7)
+        );
+        assert!(Replay::parse("seed = \"banana\"\nmoves = \"U\"").is_err());
+
+        assert!(Replay::parse("moves = \"UX\"").is_err());
+        assert_eq!(
+            Replay::parse("moves = \"U93R\"").unwrap().moves()[1..3],
+            [Direction::UpRight, Direction::DownRight]
+        );
+        assert!(Replay::parse("steps = 3").is_err());
+
+        // Seeds can be bigger than TOML's numbers, and diagonal moves survive the trip too
+        replay.set_seed(u64::MAX);
+        replay.push(Direction::UpLeft);
+        replay.push(Direction::DownLeft);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(FILE_NAME);
+        replay.save(&path).unwrap();
+        assert_eq!(Replay::load(&path).unwrap(), replay);
+    }
+
+    #[test]
+    fn replay_matches_original_game() {
+        // Record a game played by a bot
+        let mut original = GameState::seeded(Grid::default(), 42, vec![GreedyBot::new()]);
+        let keys = HashSet::new();
+        for _ in 0..50 {
+            original.poll_players(&keys);
+            original.step();
+        }
+
+        // Playing the recording back on the seed it remembers ends up in exactly the same place, even when the player is asked for its move twice a tick
+        let replay = original.replays[0].clone();
+        let seed = replay.seed().unwrap();
+        let mut copy = GameState::seeded(Grid::default(), seed, vec![ReplayPlayer::new(replay)]);
+        for _ in 0..50 {
+            copy.poll_players(&keys);
+            copy.poll_players(&keys);
+            copy.step();
+        }
+
+        assert_eq!(copy.sneks[0].parts(), original.sneks[0].parts());
+        assert_eq!(copy.sneks[0].score(), original.sneks[0].score());
+        assert_eq!(copy.hash(), original.hash());
+    }
+}
@@ -0,0 +1,264 @@
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+
+use crate::settings::GAME_TITLE;
+
+/// How many scores the table keeps hold of
+pub const MAX_ENTRIES: usize = 10;
+
+/// The name of the high score file, inside the game's data directory
+const FILE_NAME: &str = "high_scores.toml";
+
+/// A single entry in the high score table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HighScore {
+    pub name: String,
+    pub score: usize,
+    pub width: usize,
+    pub height: usize,
+    /// A short description of the rules the game was played under, as scores from different rules aren't really comparable
+    pub rules: String,
+    pub date: DateTime<Utc>,
+}
+
+/// The layout of the high score file
+#[derive(Serialize)]
+struct Table<'a> {
+    entries: &'a [HighScore],
+}
+
+/// The best scores so far, highest first, along with where they're saved
+#[derive(Debug, Default)]
+pub struct HighScores {
+    /// Tables without a path only live in memory
+    path: Option<PathBuf>,
+    entries: Vec<HighScore>,
+}
+
+impl HighScores {
+    /// Where the high score file lives by default, following the conventions of the current platform
+    pub fn default_path() -> Option<PathBuf> {
+        data_dir().map(|dir| dir.join(FILE_NAME))
+    }
+
+    /// Load the high scores from the default path, or keep them in memory if the platform has nowhere to put them
+    pub fn load_default() -> Self {
+        match Self::default_path() {
+            Some(path) => Self::load(path),
+            None => {
+                log::warn!("Nowhere to keep the high scores - they won't be saved!");
+                Self::default()
+            }
+        }
+    }
+
+    /// Load the high scores from the specified file
+    /// A missing file just means nobody has played yet, and any entries that can't be read are skipped rather than throwing away the whole table
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+
+        let entries = match std::fs::read_to_string(&path) {
+            Ok(contents) => parse(&path, &contents),
+            Err(e) if e.kind() == ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                log::warn!("Couldn't read high scores from {}: {}", path.display(), e);
+                Vec::new()
+            }
+        };
+
+        let mut high_scores = Self {
+            path: Some(path),
+            entries: Vec::new(),
+        };
+        for entry in entries {
+            high_scores.insert(entry);
+        }
+
+        high_scores
+    }
+
+    /// Get an immutable ref to the entries, highest score first
+    pub fn entries(&self) -> &[HighScore] {
+        &self.entries
+    }
+
+    /// Add a new entry to the table, as long as it's good enough to make it in
+    /// Returns where the new entry ended up, if it made it in at all
+    pub fn insert(&mut self, entry: HighScore) -> Option<usize> {
+        // Ties go to whoever got there first
+        let rank = self
+            .entries
+            .iter()
+            .position(|other| other.score < entry.score)
+            .unwrap_or(self.entries.len());
+
+        if rank >= MAX_ENTRIES {
+            return None;
+        }
+
+        self.entries.insert(rank, entry);
+        self.entries.truncate(MAX_ENTRIES);
+
+        Some(rank)
+    }
+
+    /// Write the table out to its file, if it has one
+    /// The new table is written alongside the old one before replacing it, so a crash part way through can't leave a half-written file behind
+    pub fn save(&self) -> std::io::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let table = Table {
+            entries: &self.entries,
+        };
+        let contents =
+            toml::to_string(&table).map_err(|e| std::io::Error::new(ErrorKind::InvalidData, e))?;
+
+        let tmp = path.with_extension("toml.tmp");
+        std::fs::write(&tmp, contents)?;
+        std::fs::rename(&tmp, path)
+    }
+}
+
+/// Read as many entries as possible from the contents of a high score file
+/// If the file isn't even valid TOML then it's moved out of the way, so that it isn't overwritten in case someone wants to rescue it
+fn parse(path: &Path, contents: &str) -> Vec<HighScore> {
+    let table = match contents.parse::<toml::Value>() {
+        Ok(table) => table,
+        Err(e) => {
+            let backup = path.with_extension("toml.bak");
+            log::warn!(
+                "High score file {} is corrupt ({}) - moving it to {}",
+                path.display(),
+                e,
+                backup.display()
+            );
+            if let Err(e) = std::fs::rename(path, &backup) {
+                log::warn!("Couldn't move the corrupt high score file: {}", e);
+            }
+
+            return Vec::new();
+        }
+    };
+
+    let entries = match table.get("entries").and_then(toml::Value::as_array) {
+        Some(entries) => entries,
+        None => return Vec::new(),
+    };
+
+    entries
+        .iter()
+        .filter_map(|entry| match entry.clone().try_into::<HighScore>() {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                log::warn!("Skipping unreadable high score: {}", e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Where the game keeps the files it writes for itself, following the conventions of the current platform
+pub(crate) fn data_dir() -> Option<PathBuf> {
+    ProjectDirs::from("com", "rufus-stone", GAME_TITLE).map(|dirs| dirs.data_dir().to_owned())
+}
+
+/// The name to put in the high score table for a human player, taken from whoever is logged in
+pub fn player_name() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "Player".to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, score: usize) -> HighScore {
+        HighScore {
+            name: name.to_owned(),
+            score,
+            width: 10,
+            height: 10,
+            rules: "standard".to_owned(),
+            date: DateTime::parse_from_rfc3339("2021-06-01T12:00:00Z")
+                .unwrap()
+                .with_timezone(&Utc),
+        }
+    }
+
+    #[test]
+    fn insert_keeps_order() {
+        let mut scores = HighScores::default();
+
+        assert_eq!(scores.insert(entry("a", 5)), Some(0));
+        assert_eq!(scores.insert(entry("b", 10)), Some(0));
+        assert_eq!(scores.insert(entry("c", 5)), Some(2));
+
+        let names: Vec<&str> = scores.entries().iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["b", "a", "c"]);
+
+        // Once the table is full, only better scores get in
+        for _ in 0..MAX_ENTRIES {
+            scores.insert(entry("d", 7));
+        }
+        assert_eq!(scores.entries().len(), MAX_ENTRIES);
+        assert_eq!(scores.insert(entry("e", 1)), None);
+        assert_eq!(scores.insert(entry("f", 8)), Some(1));
+        assert_eq!(scores.entries().last().unwrap().score, 7);
+    }
+
+    #[test]
+    fn save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(FILE_NAME);
+
+        let mut scores = HighScores::load(&path);
+        assert!(scores.entries().is_empty());
+
+        scores.insert(entry("a", 5));
+        scores.insert(entry("b", 10));
+        scores.save().unwrap();
+
+        let loaded = HighScores::load(&path);
+        assert_eq!(loaded.entries(), scores.entries());
+    }
+
+    #[test]
+    fn corrupt_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(FILE_NAME);
+
+        // Bad entries are skipped, but the good ones survive
+        let mut scores = HighScores::load(&path);
+        scores.insert(entry("good", 3));
+        scores.save().unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(
+            &path,
+            format!("{}\n[[entries]]\nname = \"bad\"\nscore = -1\n", contents),
+        )
+        .unwrap();
+
+        let loaded = HighScores::load(&path);
+        assert_eq!(loaded.entries().len(), 1);
+        assert_eq!(loaded.entries()[0].name, "good");
+
+        // Files that aren't TOML at all are moved out of the way
+        std::fs::write(&path, "\u{0}\u{0} not toml [[[").unwrap();
+        let loaded = HighScores::load(&path);
+        assert!(loaded.entries().is_empty());
+        assert!(!path.exists());
+        assert!(path.with_extension("toml.bak").exists());
+    }
+}
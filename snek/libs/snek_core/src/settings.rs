@@ -1,4 +1,10 @@
-use crate::{game::mode::Mode, players::Player};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    game::mode::Mode,
+    observation::{Encoder, Senses},
+    players::Player,
+};
 
 pub const GAME_TITLE: &str = "Snek";
 
@@ -8,6 +14,17 @@ pub const SCREEN_HEIGHT: f32 = 600.0;
 pub const HUMAN: Mode = Mode::OnePlayer(Player::Human);
 pub const COMPUTER: Mode = Mode::OnePlayer(Player::Computer);
 pub const TRAIN_AI: Mode = Mode::TrainAi(Player::Computer);
+pub const HUMAN_VS_HUMAN: Mode = Mode::MultiPlayer(&[Player::Human, Player::Human]);
+pub const HUMAN_VS_COMPUTER: Mode = Mode::MultiPlayer(&[Player::Human, Player::Computer]);
+
+/// The game modes that can be picked from the title menu, along with their labels
+/// Training has its own window, so it's started with `--mode train` instead
+pub const MENU: [(&str, Mode); 4] = [
+    ("Play", HUMAN),
+    ("Watch the computer play", COMPUTER),
+    ("Two players", HUMAN_VS_HUMAN),
+    ("Play against the computer", HUMAN_VS_COMPUTER),
+];
 
 #[derive(Clone, Debug)]
 pub struct Config {
@@ -19,13 +36,121 @@ pub struct Config {
 }
 
 impl Default for Config {
+    /// The default Config sees the game through the simple Senses encoder
     fn default() -> Self {
+        Self::for_encoder(&Senses)
+    }
+}
+
+impl Config {
+    /// Create a Config for AIs that see the game through the specified encoder
+    pub fn for_encoder(encoder: &dyn Encoder) -> Self {
         Self {
-            eye_photoreceptors: 5, // Paddle Y, Ball X, Ball Y, Ball VX, Ball VY
+            eye_photoreceptors: encoder.size(), // One for each value the encoder produces
             brain_neurons: 15,
-            outputs: 1,            // Whether the move the paddle up or down
-            generation_length: 10, // How many serves to play for
+            outputs: 3,            // Whether to go ahead, turn left, or turn right
+            generation_length: 10, // How many games to play for
             population_size: 100,  // How big should the pool of trainee AIs be
         }
     }
 }
+
+/// How often each kind of Food turns up, and how long the special ones last
+/// Levels can override any of these in their header, with the rest keeping their default values
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FoodConfig {
+    pub max_items: usize,
+    pub special_chance: f64,
+    pub bonus_weight: u32,
+    pub poison_weight: u32,
+    pub speed_weight: u32,
+    pub golden_weight: u32,
+    pub golden_lifetime: u32,
+    pub boost_length: u32,
+}
+
+impl Default for FoodConfig {
+    fn default() -> Self {
+        Self {
+            max_items: 3,         // How many Food items can be on the Grid at once
+            special_chance: 0.05, // The chance of a special Food turning up each tick
+            bonus_weight: 4,      // How likely each special Food is, relative to the others
+            poison_weight: 3,
+            speed_weight: 2,
+            golden_weight: 1,
+            golden_lifetime: 25, // How many ticks Golden Food stays on the Grid
+            boost_length: 20,    // How many ticks the game stays sped up after eating Speed Food
+        }
+    }
+}
+
+/// How fast the game runs, and how quickly it speeds up as the Sneks grow
+/// Levels can override any of these in their header, with the rest keeping their default values
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpeedConfig {
+    pub base_rate: u32,
+    pub ramp_every: usize,
+    pub max_rate: u32,
+}
+
+impl Default for SpeedConfig {
+    fn default() -> Self {
+        Self {
+            base_rate: 5,  // How many ticks per second the game starts at
+            ramp_every: 5, // Add another tick per second each time the longest Snek grows by this much (0 to never speed up)
+            max_rate: 20,  // The game never runs faster than this, not counting Speed Food
+        }
+    }
+}
+
+/// The rules of the game, which can be loaded from a TOML file with the same [food] and [speed] tables as a level header
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Rules {
+    pub food: FoodConfig,
+    pub speed: SpeedConfig,
+}
+
+impl Rules {
+    /// Parse the rules from a TOML string
+    pub fn parse(contents: &str) -> std::io::Result<Self> {
+        let invalid = |msg: String| std::io::Error::new(std::io::ErrorKind::InvalidData, msg);
+
+        let rules: Rules = toml::from_str(contents).map_err(|e| invalid(e.to_string()))?;
+        rules.check().map_err(invalid)?;
+
+        Ok(rules)
+    }
+
+    /// Make sure the rules make sense, or say what's wrong with them
+    pub fn check(&self) -> Result<(), String> {
+        match (0.0..=1.0).contains(&self.food.special_chance) {
+            true => Ok(()),
+            false => Err("special_chance must be between 0 and 1".to_owned()),
+        }
+    }
+
+    /// Load the rules from the specified file
+    pub fn load(path: impl AsRef<std::path::Path>) -> std::io::Result<Self> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_rules() {
+        let rules = Rules::parse("[food]\nmax_items = 1\n\n[speed]\nbase_rate = 9\n").unwrap();
+        assert_eq!(rules.food.max_items, 1);
+        assert_eq!(rules.speed.base_rate, 9);
+        assert_eq!(rules.speed.max_rate, SpeedConfig::default().max_rate);
+
+        assert!(Rules::parse("").is_ok());
+        assert!(Rules::parse("[food]\nspecial_chance = 1.5\n").is_err());
+        assert!(Rules::parse("[colour]\nsnek = \"red\"\n").is_err());
+    }
+}
@@ -1,14 +1,111 @@
+use std::convert::TryFrom;
 use std::ops::{Add, Sub};
 
-pub mod direction {
-    use std::f64::consts::FRAC_PI_2;
+use serde::{Deserialize, Serialize};
+
+/// The ways a Snek can head across the Grid, where up is towards the top row (the one with the biggest y)
+/// Turning is done by stepping through the Directions, so there's no rounding involved
+/// Which of them a Snek can actually use depends on the Topology of the Grid it's on
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Direction {
+    Up,
+    UpRight,
+    Right,
+    DownRight,
+    Down,
+    DownLeft,
+    Left,
+    UpLeft,
+}
+
+impl Direction {
+    /// Every Direction, going clockwise from Up
+    pub const ALL: [Direction; 8] = [
+        Direction::Up,
+        Direction::UpRight,
+        Direction::Right,
+        Direction::DownRight,
+        Direction::Down,
+        Direction::DownLeft,
+        Direction::Left,
+        Direction::UpLeft,
+    ];
+
+    /// How many eighth turns clockwise from Up this Direction is
+    fn index(self) -> i32 {
+        match self {
+            Direction::Up => 0,
+            Direction::UpRight => 1,
+            Direction::Right => 2,
+            Direction::DownRight => 3,
+            Direction::Down => 4,
+            Direction::DownLeft => 5,
+            Direction::Left => 6,
+            Direction::UpLeft => 7,
+        }
+    }
+
+    /// The Direction the specified number of eighth turns clockwise from this one (or anticlockwise, if negative)
+    pub fn rotate(self, eighths: i32) -> Self {
+        Direction::ALL[(self.index() + eighths).rem_euclid(8) as usize]
+    }
 
-    pub const LEFT: f64 = FRAC_PI_2;
-    pub const RIGHT: f64 = -FRAC_PI_2;
-    pub const AHEAD: f64 = 0.0;
+    /// The Direction a quarter turn anticlockwise from this one
+    pub fn turn_left(self) -> Self {
+        self.rotate(-2)
+    }
+
+    /// The Direction a quarter turn clockwise from this one
+    pub fn turn_right(self) -> Self {
+        self.rotate(2)
+    }
+
+    /// The Direction pointing straight back the other way
+    pub fn opposite(self) -> Self {
+        self.rotate(4)
+    }
+
+    /// The step taken on the Grid when heading this way
+    pub fn to_vec2(self) -> Vec2 {
+        match self {
+            Direction::Up => Vec2::new(0, 1),
+            Direction::UpRight => Vec2::new(1, 1),
+            Direction::Right => Vec2::new(1, 0),
+            Direction::DownRight => Vec2::new(1, -1),
+            Direction::Down => Vec2::new(0, -1),
+            Direction::DownLeft => Vec2::new(-1, -1),
+            Direction::Left => Vec2::new(-1, 0),
+            Direction::UpLeft => Vec2::new(-1, 1),
+        }
+    }
+}
+
+impl From<Direction> for Vec2 {
+    fn from(dir: Direction) -> Self {
+        dir.to_vec2()
+    }
+}
+
+impl TryFrom<Vec2> for Direction {
+    type Error = Vec2;
+
+    /// Only a single step to one of the eight squares around a position is a Direction - anything else is handed back
+    fn try_from(step: Vec2) -> Result<Self, Self::Error> {
+        Direction::ALL
+            .iter()
+            .copied()
+            .find(|dir| dir.to_vec2() == step)
+            .ok_or(step)
+    }
 }
 
-#[derive(Debug, Copy, Clone, Default, PartialEq)]
+impl PartialEq<Vec2> for Direction {
+    fn eq(&self, other: &Vec2) -> bool {
+        self.to_vec2() == *other
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Vec2 {
     pub x: i32,
     pub y: i32,
@@ -19,20 +116,6 @@ impl Vec2 {
     pub fn new(x: i32, y: i32) -> Self {
         Self { x, y }
     }
-
-    /// Create a new Vec2 that is the result of rotating self by the specified angle
-    pub fn rotate(&self, radians: f64) -> Self {
-        let x1 = self.x as f64;
-        let y1 = self.y as f64;
-
-        let x2 = (radians.cos() * x1) - (radians.sin() * y1);
-        let xy = (radians.sin() * x1) + (radians.cos() * y1);
-
-        Self {
-            x: x2 as i32,
-            y: xy as i32,
-        }
-    }
 }
 
 impl Add for Vec2 {
@@ -51,6 +134,22 @@ impl Sub for Vec2 {
     }
 }
 
+impl Add<Direction> for Vec2 {
+    type Output = Vec2;
+
+    fn add(self, rhs: Direction) -> Self::Output {
+        self + rhs.to_vec2()
+    }
+}
+
+impl Add<Direction> for &Vec2 {
+    type Output = Vec2;
+
+    fn add(self, rhs: Direction) -> Self::Output {
+        *self + rhs.to_vec2()
+    }
+}
+
 impl Add for &Vec2 {
     type Output = Vec2;
 
@@ -109,22 +208,39 @@ mod tests {
     }
 
     #[test]
-    fn rotation() {
-        let up = Vec2::new(0, 1);
+    fn turning() {
+        let up = Direction::Up;
+        assert_eq!(up, Vec2::new(0, 1));
 
-        let left = up.rotate(direction::LEFT);
+        let left = up.turn_left();
         assert_eq!(left, Vec2::new(-1, 0));
 
-        let right = up.rotate(direction::RIGHT);
+        let right = up.turn_right();
         assert_eq!(right, Vec2::new(1, 0));
 
-        let down = up.rotate(2.0 * direction::LEFT);
+        let down = up.turn_left().turn_left();
         assert_eq!(down, Vec2::new(0, -1));
+        assert_eq!(up.turn_right().turn_right(), down);
+        assert_eq!(up.opposite(), down);
+
+        // Four quarter turns either way always ends up back where it started
+        for dir in Direction::ALL.iter().copied() {
+            assert_eq!(dir.turn_left().turn_left().turn_left().turn_left(), dir);
+            assert_eq!(dir.turn_right().turn_left(), dir);
+            assert_eq!(dir.opposite().opposite(), dir);
+            assert_eq!(Direction::try_from(dir.to_vec2()), Ok(dir));
+            assert_eq!(Vec2::new(3, 3) + dir, Vec2::new(3, 3) + dir.to_vec2());
+        }
 
-        let down = up.rotate(2.0 * direction::RIGHT);
-        assert_eq!(down, Vec2::new(0, -1));
+        // Eighth turns go through the diagonals
+        assert_eq!(up.rotate(1), Direction::UpRight);
+        assert_eq!(up.rotate(-1), Direction::UpLeft);
+        assert_eq!(up.rotate(11), Direction::DownRight);
+        assert_eq!(Direction::UpRight.turn_right(), Direction::DownRight);
+        assert_eq!(Direction::DownLeft.opposite(), Vec2::new(1, 1));
 
-        let same = up.rotate(direction::AHEAD);
-        assert_eq!(same, up);
+        assert_eq!(Direction::try_from(Vec2::new(-1, 1)), Ok(Direction::UpLeft));
+        assert_eq!(Direction::try_from(Vec2::new(2, 1)), Err(Vec2::new(2, 1)));
+        assert_eq!(Direction::try_from(Vec2::new(0, 0)), Err(Vec2::new(0, 0)));
     }
 }